@@ -35,6 +35,21 @@ pub enum Message {
         #[structopt(flatten)]
         output_format: OutputFormat,
     },
+
+    /// get the node's logs of the given fragments. Unlike `logs`, only the
+    /// logs of the requested fragments are downloaded. The fragments the node
+    /// has no log for are reported as `not_found`.
+    Status {
+        #[structopt(flatten)]
+        addr: HostAddr,
+        #[structopt(flatten)]
+        debug: DebugFlag,
+        #[structopt(flatten)]
+        output_format: OutputFormat,
+        /// hex-encoded IDs of the fragments
+        #[structopt(required = true)]
+        fragment_ids: Vec<String>,
+    },
 }
 
 impl Message {
@@ -46,6 +61,12 @@ impl Message {
                 debug,
                 output_format,
             } => get_logs(addr, debug, output_format),
+            Message::Status {
+                addr,
+                debug,
+                output_format,
+                fragment_ids,
+            } => get_statuses(addr, debug, output_format, fragment_ids),
         }
     }
 }
@@ -61,6 +82,26 @@ fn get_logs(addr: HostAddr, debug: DebugFlag, output_format: OutputFormat) -> Re
     Ok(())
 }
 
+fn get_statuses(
+    addr: HostAddr,
+    debug: DebugFlag,
+    output_format: OutputFormat,
+    fragment_ids: Vec<String>,
+) -> Result<(), Error> {
+    let url = addr
+        .with_segments(&["v0", "fragment", "statuses"])?
+        .into_url();
+    let builder = reqwest::blocking::Client::new().post(url);
+    let response = RestApiSender::new(builder, &debug)
+        .with_json_body(&fragment_ids)?
+        .send()?;
+    response.ok_response()?;
+    let status = response.body().json_value()?;
+    let formatted = output_format.format_json(status)?;
+    println!("{}", formatted);
+    Ok(())
+}
+
 fn post_message(file: Option<PathBuf>, addr: HostAddr, debug: DebugFlag) -> Result<(), Error> {
     let msg_hex = io::read_line(&file)?;
     let msg_bin = hex::decode(&msg_hex)?;
@@ -1,6 +1,8 @@
+mod account;
 mod epoch;
 mod history;
 
+use self::account::Account;
 use self::epoch::Epoch;
 use self::history::History;
 
@@ -14,6 +16,8 @@ pub enum Rewards {
     History(History),
     /// Rewards distribution for a specific epoch
     Epoch(Epoch),
+    /// Rewards received by an account in every epoch
+    Account(Account),
 }
 
 impl Rewards {
@@ -21,6 +25,7 @@ impl Rewards {
         match self {
             Rewards::History(history) => history.exec(),
             Rewards::Epoch(epoch) => epoch.exec(),
+            Rewards::Account(account) => account.exec(),
         }
     }
 }
@@ -1,3 +1,4 @@
+use crate::time::Duration;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
@@ -6,6 +7,17 @@ pub struct PoolMaxEntries(usize);
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub struct LogMaxEntries(usize);
 
+/// algorithm used by the leader to choose which fragments of the mempool
+/// go into the block it is building
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FragmentSelection {
+    /// fragments are picked in the order they have been received
+    OldestFirst,
+    /// fragments paying the highest fee per byte are picked first
+    FeeRate,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Mempool {
@@ -15,6 +27,23 @@ pub struct Mempool {
     /// maximum number of entries in the fragment logs
     #[serde(default)]
     pub log_max_entries: LogMaxEntries,
+    /// algorithm used to select the fragments to put in a new block
+    #[serde(default)]
+    pub selection_algorithm: FragmentSelection,
+    /// time after which a pending fragment is removed from the mempool and
+    /// marked as expired in the fragment logs. Fragments do not expire if
+    /// not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fragment_ttl: Option<Duration>,
+    /// number of slots after which a pending fragment is removed from the
+    /// mempool and marked as expired in the fragment logs. If both this and
+    /// `fragment_ttl` are set, the fragment expires at the earliest of both.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fragment_ttl_slots: Option<u32>,
+    /// keep a journal of the mempool and of the fragment logs in the
+    /// storage directory so they survive a restart of the node
+    #[serde(default)]
+    pub persistent: bool,
 }
 
 impl Default for PoolMaxEntries {
@@ -29,11 +58,21 @@ impl Default for LogMaxEntries {
     }
 }
 
+impl Default for FragmentSelection {
+    fn default() -> Self {
+        FragmentSelection::OldestFirst
+    }
+}
+
 impl Default for Mempool {
     fn default() -> Self {
         Mempool {
             pool_max_entries: PoolMaxEntries::default(),
             log_max_entries: LogMaxEntries::default(),
+            selection_algorithm: FragmentSelection::default(),
+            fragment_ttl: None,
+            fragment_ttl_slots: None,
+            persistent: false,
         }
     }
 }
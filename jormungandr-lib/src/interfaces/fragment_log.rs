@@ -27,6 +27,9 @@ pub enum FragmentStatus {
     Rejected { reason: String },
     /// The fragment has been added in a block
     InABlock { date: BlockDate, block: Hash },
+    /// the fragment stayed pending for too long and has been dropped
+    /// from the pool, it won't be added in a block
+    Expired,
 }
 
 /// the log associated to a given fragment
@@ -62,6 +65,11 @@ impl FragmentStatus {
             false
         }
     }
+
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        self == &FragmentStatus::Expired
+    }
 }
 
 impl FragmentLog {
@@ -92,6 +100,11 @@ impl FragmentLog {
         self.status().is_in_a_block()
     }
 
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        self.status().is_expired()
+    }
+
     /// set the new status
     #[inline]
     pub fn modify(&mut self, new_status: FragmentStatus) {
@@ -124,3 +137,42 @@ impl FragmentLog {
         &self.status
     }
 }
+
+/// the log of a fragment requested by its identifier
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum FragmentLookup {
+    /// the node has a log for the fragment
+    Found(FragmentLog),
+    /// the node has no log for the fragment, it has never received it or
+    /// the log has been dropped since
+    NotFound { fragment_id: Hash },
+}
+
+impl FragmentLookup {
+    pub fn not_found(fragment_id: key::Hash) -> Self {
+        FragmentLookup::NotFound {
+            fragment_id: fragment_id.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lookup_serde_round_trip() {
+        let fragment_id = key::Hash::hash_bytes(&[0]);
+        let found = FragmentLookup::Found(FragmentLog::new(fragment_id, FragmentOrigin::Rest));
+        let not_found = FragmentLookup::not_found(key::Hash::hash_bytes(&[1]));
+
+        let encoded = serde_json::to_string(&vec![found.clone(), not_found.clone()]).unwrap();
+        let decoded: Vec<FragmentLookup> = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(decoded, vec![found, not_found]);
+
+        let encoded = serde_json::to_value(&FragmentLookup::not_found(fragment_id)).unwrap();
+        assert_eq!(encoded["result"], "not_found");
+        assert_eq!(encoded["fragment_id"], fragment_id.to_string());
+    }
+}
@@ -0,0 +1,62 @@
+use crate::crypto::hash::Hash;
+use chain_impl_mockchain::key;
+use serde::{Deserialize, Serialize};
+
+/// outcome of the admission of a submitted fragment in the mempool
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FragmentSubmissionStatus {
+    /// the fragment has been admitted in the mempool
+    Accepted,
+    /// the fragment has not been admitted in the mempool
+    Rejected { reason: String },
+    /// the fragment cannot be checked before the fragments it depends upon
+    /// are received, it is held until then
+    Held { reason: String },
+}
+
+/// the result of the submission of a fragment
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FragmentSubmission {
+    /// identifier of the fragment, `None` if the fragment could not
+    /// be decoded
+    fragment_id: Option<Hash>,
+    status: FragmentSubmissionStatus,
+}
+
+impl FragmentSubmission {
+    pub fn accepted(fragment_id: key::Hash) -> Self {
+        FragmentSubmission {
+            fragment_id: Some(fragment_id.into()),
+            status: FragmentSubmissionStatus::Accepted,
+        }
+    }
+
+    pub fn rejected(fragment_id: Option<key::Hash>, reason: String) -> Self {
+        FragmentSubmission {
+            fragment_id: fragment_id.map(Into::into),
+            status: FragmentSubmissionStatus::Rejected { reason },
+        }
+    }
+
+    pub fn held(fragment_id: key::Hash, reason: String) -> Self {
+        FragmentSubmission {
+            fragment_id: Some(fragment_id.into()),
+            status: FragmentSubmissionStatus::Held { reason },
+        }
+    }
+
+    #[inline]
+    pub fn is_accepted(&self) -> bool {
+        self.status == FragmentSubmissionStatus::Accepted
+    }
+
+    #[inline]
+    pub fn fragment_id(&self) -> Option<&Hash> {
+        self.fragment_id.as_ref()
+    }
+
+    #[inline]
+    pub fn status(&self) -> &FragmentSubmissionStatus {
+        &self.status
+    }
+}
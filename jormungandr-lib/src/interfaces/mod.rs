@@ -7,10 +7,12 @@ mod certificate;
 mod committee;
 mod config;
 mod fragment_log;
+mod fragment_submission;
 mod leadership_log;
 mod linear_fee;
 mod old_address;
 mod peer_stats;
+mod pending_fragments;
 mod ratio;
 mod reward_parameters;
 mod rewards_info;
@@ -38,7 +40,8 @@ pub use self::certificate::{
 };
 pub use self::committee::CommitteeIdDef;
 pub use self::config::*;
-pub use self::fragment_log::{FragmentLog, FragmentOrigin, FragmentStatus};
+pub use self::fragment_log::{FragmentLog, FragmentLookup, FragmentOrigin, FragmentStatus};
+pub use self::fragment_submission::{FragmentSubmission, FragmentSubmissionStatus};
 pub use self::leadership_log::{
     EnclaveLeaderId, LeadershipLog, LeadershipLogId, LeadershipLogStatus,
 };
@@ -47,6 +50,7 @@ pub use self::old_address::OldAddress;
 pub use self::peer_stats::{
     Info, Logs, PeerRecord, PeerStats, Profile, Record, Strike, Subscription, When,
 };
+pub use self::pending_fragments::{MempoolStats, PendingFragment, PendingFragments};
 pub use self::ratio::{ParseRatioError, Ratio};
 pub use self::reward_parameters::RewardParams;
 pub use self::rewards_info::EpochRewardsInfo;
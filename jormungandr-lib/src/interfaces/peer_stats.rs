@@ -11,6 +11,9 @@ pub struct PeerStats {
     pub last_block_received: Option<SystemTime>,
     pub last_fragment_received: Option<SystemTime>,
     pub last_gossip_received: Option<SystemTime>,
+    /// reputation score of the peer, neutral at 0
+    #[serde(default)]
+    pub score: i32,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -0,0 +1,42 @@
+use crate::{
+    crypto::hash::Hash,
+    interfaces::{FragmentOrigin, Value},
+    time::SystemTime,
+};
+use serde::{Deserialize, Serialize};
+
+/// a fragment waiting in the mempool to be added in a block
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PendingFragment {
+    pub fragment_id: Hash,
+    pub received_from: FragmentOrigin,
+    pub received_at: SystemTime,
+    /// size of the fragment, in bytes
+    pub size: usize,
+    /// fee paid by the fragment
+    pub fee: Value,
+    /// accumulated fee of the pending fragments this one depends upon
+    pub ancestors_fee: Value,
+    /// accumulated size of the pending fragments this one depends upon
+    pub ancestors_size: usize,
+}
+
+/// aggregated statistics of the mempool
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MempoolStats {
+    /// number of fragments in the mempool
+    pub count: usize,
+    /// maximum number of fragments the mempool can hold
+    pub capacity: usize,
+    /// accumulated size of the fragments, in bytes
+    pub total_size: usize,
+    /// accumulated fee of the fragments
+    pub total_fee: Value,
+}
+
+/// the content of the mempool, from the oldest fragment to the most recent
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PendingFragments {
+    pub stats: MempoolStats,
+    pub fragments: Vec<PendingFragment>,
+}
@@ -18,10 +18,40 @@ pub struct EpochRewardsInfo {
 }
 
 impl EpochRewardsInfo {
+    pub fn new(
+        epoch: Epoch,
+        drawn: Value,
+        fees: Value,
+        treasury: Value,
+        stake_pools: BTreeMap<Hash, (Value, Value)>,
+        accounts: BTreeMap<Identifier, Value>,
+    ) -> Self {
+        Self {
+            epoch,
+            drawn,
+            fees,
+            treasury,
+            stake_pools,
+            accounts,
+        }
+    }
+
     pub fn epoch(&self) -> Epoch {
         self.epoch
     }
 
+    pub fn drawn(&self) -> Value {
+        self.drawn
+    }
+
+    pub fn fees(&self) -> Value {
+        self.fees
+    }
+
+    pub fn treasury(&self) -> Value {
+        self.treasury
+    }
+
     pub fn stake_pools(&self) -> &BTreeMap<Hash, (Value, Value)> {
         &self.stake_pools
     }
@@ -28,6 +28,7 @@ pub struct NodeStats {
     pub peer_quarantined_cnt: usize,
     pub peer_total_cnt: usize,
     pub peer_unreachable_cnt: usize,
+    pub rejected_deep_fork_cnt: u64,
     pub tx_recv_cnt: u64,
     pub uptime: Option<u64>,
 }
@@ -1,6 +1,6 @@
 use crate::blockchain::Ref;
 use futures::stream::{FuturesUnordered, StreamExt};
-use std::{iter::FromIterator, sync::Arc};
+use std::{iter::FromIterator, sync::Arc, time::SystemTime};
 use tokio::sync::RwLock;
 
 #[derive(Clone)]
@@ -22,7 +22,7 @@ struct BranchData {
     /// reference to the block where the branch points to
     reference: Arc<Ref>,
 
-    last_updated: std::time::SystemTime,
+    last_updated: SystemTime,
 }
 
 impl Branches {
@@ -52,6 +52,21 @@ impl Branches {
         guard.branches().await
     }
 
+    /// the tip of every branch with the time it was last updated
+    pub async fn branches_with_last_updated(&self) -> Vec<(Arc<Ref>, SystemTime)> {
+        let guard = self.inner.read().await;
+        guard.branches_with_last_updated().await
+    }
+
+    /// remove the branches whose tip is more than `max_lag` blocks behind
+    /// the given tip, returns the tips of the removed branches.
+    ///
+    /// The branch of the given tip is never removed.
+    pub async fn remove_stale(&mut self, tip: &Ref, max_lag: u32) -> Vec<Arc<Ref>> {
+        let mut guard = self.inner.write().await;
+        guard.remove_stale(tip, max_lag).await
+    }
+
     async fn apply(&mut self, candidate: Arc<Ref>) -> Option<Branch> {
         let mut guard = self.inner.write().await;
         guard.apply(candidate).await
@@ -86,6 +101,33 @@ impl BranchesData {
             .collect()
             .await
     }
+
+    async fn branches_with_last_updated(&self) -> Vec<(Arc<Ref>, SystemTime)> {
+        FuturesUnordered::from_iter(self.branches.iter().map(|b| b.get_ref_and_last_updated()))
+            .collect()
+            .await
+    }
+
+    async fn remove_stale(&mut self, tip: &Ref, max_lag: u32) -> Vec<Arc<Ref>> {
+        let tip_chain_length: u32 = tip.chain_length().into();
+        let mut kept = Vec::with_capacity(self.branches.len());
+        let mut removed = Vec::new();
+
+        for branch in self.branches.drain(..) {
+            let reference = branch.get_ref().await;
+            let chain_length: u32 = reference.chain_length().into();
+            let is_stale = reference.hash() != tip.hash()
+                && tip_chain_length.saturating_sub(chain_length) > max_lag;
+            if is_stale {
+                removed.push(reference);
+            } else {
+                kept.push(branch);
+            }
+        }
+
+        self.branches = kept;
+        removed
+    }
 }
 
 impl Branch {
@@ -100,6 +142,11 @@ impl Branch {
         guard.reference()
     }
 
+    async fn get_ref_and_last_updated(&self) -> (Arc<Ref>, SystemTime) {
+        let guard = self.inner.read().await;
+        (guard.reference(), guard.last_updated)
+    }
+
     pub async fn update_ref(&mut self, new_ref: Arc<Ref>) -> Arc<Ref> {
         let mut guard = self.inner.write().await;
         guard.update(new_ref)
@@ -121,13 +168,13 @@ impl BranchData {
     fn new(reference: Arc<Ref>) -> Self {
         BranchData {
             reference,
-            last_updated: std::time::SystemTime::now(),
+            last_updated: SystemTime::now(),
         }
     }
 
     fn update(&mut self, reference: Arc<Ref>) -> Arc<Ref> {
         let old_reference = std::mem::replace(&mut self.reference, reference);
-        self.last_updated = std::time::SystemTime::now();
+        self.last_updated = SystemTime::now();
 
         old_reference
     }
@@ -145,3 +192,52 @@ impl BranchData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::test_chain::{apply_all, TestChain};
+
+    #[tokio::test]
+    async fn remove_stale_keeps_the_recent_branches() {
+        let chain = TestChain::new("branches-remove-stale", 10);
+        let blockchain = chain.blockchain(64, None);
+        blockchain
+            .load_from_block0(chain.block0.clone())
+            .await
+            .unwrap();
+        let blocks = chain.branch(&chain.block0.header, 9, 0);
+        let hashes: Vec<_> = blocks.iter().map(|block| block.header.hash()).collect();
+        apply_all(&blockchain, blocks).await;
+
+        let mut references = Vec::new();
+        for hash in hashes {
+            references.push(blockchain.get_ref(hash).await.unwrap().unwrap());
+        }
+        let tip = Arc::clone(&references[7]);
+
+        let mut branches = Branches::new();
+        // four and two blocks behind the tip, the tip and one block ahead
+        for index in &[3, 5, 7, 8] {
+            branches
+                .add(Branch::new(Arc::clone(&references[*index])))
+                .await;
+        }
+
+        let removed = branches.remove_stale(&tip, 2).await;
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].hash(), references[3].hash());
+        assert_eq!(branches.branches().await.len(), 3);
+
+        // the branch of the tip is never removed
+        let removed = branches.remove_stale(&references[8], 0).await;
+        let mut removed: Vec<_> = removed.iter().map(|reference| reference.hash()).collect();
+        removed.sort();
+        let mut expected = vec![references[5].hash(), references[7].hash()];
+        expected.sort();
+        assert_eq!(removed, expected);
+        let remaining = branches.branches().await;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].hash(), references[8].hash());
+    }
+}
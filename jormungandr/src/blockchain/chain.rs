@@ -49,7 +49,13 @@ See Internal documentation for more details: doc/internal_design.md
 [`Branch`]: ./struct.Branch.html
 */
 
-use super::{branch::Branches, reference_cache::RefCache};
+use super::{
+    branch::Branches,
+    reference_cache::RefCache,
+    rewards_dump::RewardsDump,
+    rewards_index::RewardsIndex,
+    snapshot::{Snapshot, Snapshots},
+};
 use crate::{
     blockcfg::{
         Block, Block0Error, BlockDate, ChainLength, Epoch, EpochRewardsInfo, Header, HeaderHash,
@@ -60,7 +66,7 @@ use crate::{
 use chain_impl_mockchain::{leadership::Verification, ledger};
 use chain_time::TimeFrame;
 use slog::Logger;
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 use tokio::stream::StreamExt;
 
 // derive
@@ -106,6 +112,15 @@ error_chain! {
             display("The block header verification failed: {}", reason),
         }
 
+        ReplayTooDeep (hash: HeaderHash, max: u32) {
+            description("The state of the block is too far from a known state to be rebuilt"),
+            display(
+                "Cannot rebuild the state of block '{}', more than {} blocks would be applied",
+                hash,
+                max,
+            ),
+        }
+
         BlockNotRequested (hash: HeaderHash) {
             description("Received an unknown block"),
             display("Received block {} is not known from previously received headers", hash)
@@ -114,6 +129,11 @@ error_chain! {
         CannotApplyBlock {
             description("Block cannot be applied on top of the previous block's ledger state"),
         }
+
+        LedgerSnapshotMismatch (hash: HeaderHash) {
+            description("The ledger snapshot does not match its block"),
+            display("The ledger snapshot is not the one of the first block of an epoch '{}'", hash),
+        }
     }
 }
 
@@ -130,6 +150,10 @@ pub enum HeaderChainVerifyError {
 
 pub const MAIN_BRANCH_TAG: &str = "HEAD";
 
+/// the maximum number of blocks applied to rebuild the state of a block that
+/// is no longer in the cache
+const MAX_REPLAY_DEPTH: u32 = 4096;
+
 /// Performs lightweight sanity checks on information fields of a block header
 /// against those in the header of the block's parent.
 /// The `parent` header must have been retrieved based on, or otherwise
@@ -183,7 +207,22 @@ pub struct Blockchain {
 
     block0: HeaderHash,
 
-    rewards_report_all: bool,
+    rewards_report: RewardsReport,
+
+    /// where to take snapshots of the ledger state at epoch boundaries
+    snapshots: Option<Snapshots>,
+}
+
+/// what is kept of the rewards distributed at epoch boundaries
+#[derive(Clone, Default)]
+pub struct RewardsReport {
+    /// keep the rewards received by every account in memory, not only the
+    /// rewards of the stake pools
+    pub report_all: bool,
+    /// where to dump the rewards distributions
+    pub dump: Option<RewardsDump>,
+    /// where to index the rewards received by every account
+    pub index: Option<RewardsIndex>,
 }
 
 pub enum PreCheckedHeader {
@@ -230,6 +269,9 @@ pub struct PostCheckedHeader {
     time_frame: Arc<TimeFrame>,
     previous_epoch_state: Option<Arc<Ref>>,
     epoch_rewards_info: Option<Arc<EpochRewardsInfo>>,
+    /// the ledger the epoch's leadership was built from, set only on the
+    /// first block of an epoch
+    epoch_state: Option<Arc<Ledger>>,
 }
 
 impl PostCheckedHeader {
@@ -243,6 +285,17 @@ pub enum AppliedBlock {
     Existing(Arc<Ref>),
 }
 
+/// the outcome of `Blockchain::verify_storage`
+pub struct StorageVerification {
+    /// the number of blocks validated after the block0
+    pub verified: u64,
+    /// the last valid block, the block0 if no other block could be validated
+    pub last_valid: Arc<Ref>,
+    /// the first invalid or missing block and the reason it was rejected,
+    /// `None` if every block up to the `Head` is valid
+    pub first_invalid: Option<(HeaderHash, Error)>,
+}
+
 impl AppliedBlock {
     pub fn cached_ref(&self) -> Arc<Ref> {
         match self {
@@ -270,7 +323,8 @@ impl Blockchain {
         block0: HeaderHash,
         storage: Storage,
         cache_capacity: usize,
-        rewards_report_all: bool,
+        rewards_report: RewardsReport,
+        snapshots: Option<Snapshots>,
     ) -> Self {
         Blockchain {
             branches: Branches::new(),
@@ -278,7 +332,8 @@ impl Blockchain {
             ledgers: Multiverse::new(),
             storage,
             block0,
-            rewards_report_all,
+            rewards_report,
+            snapshots,
         }
     }
 
@@ -362,6 +417,169 @@ impl Blockchain {
         }
     }
 
+    /// get the `Ref` of the given block of the storage. If the block is no
+    /// longer in the cache, its `Ref` is rebuilt by applying the blocks that
+    /// follow its closest ancestor still in the cache, its closest ancestor
+    /// with a ledger snapshot or the block0, whichever comes first.
+    ///
+    /// The rebuilt states are not kept: they are not put in the cache, no
+    /// ledger snapshot is taken and no rewards are reported, see `replayer`.
+    ///
+    /// Returns `None` if the block is not in the storage, and fails with
+    /// `ErrorKind::ReplayTooDeep` if more than `MAX_REPLAY_DEPTH` blocks would
+    /// be applied.
+    pub async fn get_or_replay_ref(&self, header_hash: HeaderHash) -> Result<Option<Arc<Ref>>> {
+        self.replay_ref(header_hash, MAX_REPLAY_DEPTH).await
+    }
+
+    async fn replay_ref(
+        &self,
+        header_hash: HeaderHash,
+        max_depth: u32,
+    ) -> Result<Option<Arc<Ref>>> {
+        if let Some(reference) = self.get_ref(header_hash).await? {
+            return Ok(Some(reference));
+        }
+        let block = match self.storage.get(header_hash).await? {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+        let replayer = self.replayer();
+        if header_hash == self.block0 {
+            return replayer.create_block0_reference(&block).await.map(Some);
+        }
+
+        let snapshot = self
+            .closest_snapshot(header_hash, block.header.chain_length())
+            .await?;
+        if let Some((chain_length, snapshot_block)) = snapshot {
+            if snapshot_block == header_hash {
+                return self
+                    .replay_snapshot(&replayer, chain_length, snapshot_block)
+                    .await
+                    .map(Some);
+            }
+        }
+
+        let mut ancestor = block.header.block_parent_hash();
+        let mut depth = 1;
+        let mut reference = loop {
+            if depth > max_depth {
+                return Err(ErrorKind::ReplayTooDeep(header_hash, max_depth).into());
+            }
+            if let Some(reference) = self.ref_cache.get(ancestor).await {
+                break reference;
+            }
+            match snapshot {
+                Some((chain_length, snapshot_block)) if snapshot_block == ancestor => {
+                    break self
+                        .replay_snapshot(&replayer, chain_length, snapshot_block)
+                        .await?;
+                }
+                _ => {}
+            }
+            let block = self
+                .storage
+                .get(ancestor)
+                .await?
+                .ok_or(ErrorKind::MissingParentBlock(ancestor))?;
+            if ancestor == self.block0 {
+                break replayer.create_block0_reference(&block).await?;
+            }
+            ancestor = block.header.block_parent_hash();
+            depth += 1;
+        };
+
+        let mut block_stream = self
+            .storage
+            .stream_from_to(reference.hash(), header_hash)
+            .await
+            .map(Box::pin)
+            .map_err(|e| Error::with_chain(e, "Cannot iterate blocks to replay"))?;
+
+        while let Some(block) = block_stream.next().await {
+            let block = block
+                .map_err(|e| Error::with_chain(e, "Error while iterating blocks to replay"))?;
+            let post_checked_header = replayer
+                .post_check_header(
+                    block.header.clone(),
+                    reference,
+                    CheckHeaderProof::SkipFromStorage,
+                )
+                .await?;
+            let new_ledger = replayer.apply_block_dry_run(&post_checked_header, &block)?;
+            reference = replayer
+                .apply_block_finalize(post_checked_header, new_ledger)
+                .await;
+        }
+        Ok(Some(reference))
+    }
+
+    /// a blockchain over the same storage, to rebuild states without keeping
+    /// them: its cache and ledgers are its own, it takes no ledger snapshot
+    /// and reports no rewards
+    fn replayer(&self) -> Blockchain {
+        Blockchain::new(
+            self.block0,
+            self.storage.clone(),
+            1,
+            RewardsReport::default(),
+            None,
+        )
+    }
+
+    /// the most recent ledger snapshot of the given block or of one of its
+    /// ancestors
+    async fn closest_snapshot(
+        &self,
+        header_hash: HeaderHash,
+        chain_length: ChainLength,
+    ) -> Result<Option<(ChainLength, HeaderHash)>> {
+        let snapshots = match &self.snapshots {
+            Some(snapshots) => snapshots.clone(),
+            None => return Ok(None),
+        };
+        let available = tokio::task::spawn_blocking(move || snapshots.list())
+            .await
+            .map_err(|e| Error::with_chain(e, "Cannot list the ledger snapshots"))?
+            .map_err(|e| Error::with_chain(e, "Cannot list the ledger snapshots"))?;
+
+        for (snapshot_length, block) in available {
+            if snapshot_length > chain_length {
+                continue;
+            }
+            if self
+                .storage
+                .is_ancestor(block, header_hash)
+                .await?
+                .is_some()
+            {
+                return Ok(Some((snapshot_length, block)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// load the given ledger snapshot in the `replayer`
+    async fn replay_snapshot(
+        &self,
+        replayer: &Blockchain,
+        chain_length: ChainLength,
+        block: HeaderHash,
+    ) -> Result<Arc<Ref>> {
+        let snapshots = self
+            .snapshots
+            .clone()
+            .ok_or_else(|| Error::from("The ledger snapshots are not enabled"))?;
+        let block0 = self.block0;
+        let snapshot =
+            tokio::task::spawn_blocking(move || snapshots.read(&block0, chain_length, &block))
+                .await
+                .map_err(|e| Error::with_chain(e, "Cannot read the ledger snapshot"))?
+                .map_err(|e| Error::with_chain(e, "Cannot read the ledger snapshot"))?;
+        replayer.load_snapshot(snapshot).await
+    }
+
     /// load the header's parent `Ref`.
     async fn load_header_parent(&self, header: Header, force: bool) -> Result<PreCheckedHeader> {
         let block_id = header.hash();
@@ -424,7 +642,6 @@ impl Blockchain {
         check_header_proof: CheckHeaderProof,
     ) -> Result<PostCheckedHeader> {
         let current_date = header.block_date();
-        let rewards_report_all = self.rewards_report_all;
 
         let (
             parent_ledger_state,
@@ -433,7 +650,8 @@ impl Blockchain {
             epoch_rewards_info,
             time_frame,
             previous_epoch_state,
-        ) = new_epoch_leadership_from(current_date.epoch, parent, rewards_report_all);
+            epoch_state,
+        ) = new_epoch_leadership_from(current_date.epoch, parent, &self.rewards_report);
 
         if check_header_proof == CheckHeaderProof::Enabled {
             match epoch_leadership_schedule.verify(&header) {
@@ -452,6 +670,7 @@ impl Blockchain {
             parent_ledger_state,
             time_frame,
             previous_epoch_state,
+            epoch_state,
         })
     }
 
@@ -486,18 +705,72 @@ impl Blockchain {
         let epoch_ledger_parameters = post_checked_header.epoch_ledger_parameters;
         let time_frame = post_checked_header.time_frame;
         let previous_epoch_state = post_checked_header.previous_epoch_state;
+        let epoch_state = post_checked_header.epoch_state;
 
-        self.create_and_store_reference(
-            block_id,
-            header,
-            new_ledger,
-            time_frame,
-            epoch_leadership_schedule,
-            epoch_rewards_info,
-            epoch_ledger_parameters,
-            previous_epoch_state,
-        )
-        .await
+        let reference = self
+            .create_and_store_reference(
+                block_id,
+                header,
+                new_ledger,
+                time_frame,
+                epoch_leadership_schedule,
+                epoch_rewards_info,
+                epoch_ledger_parameters,
+                previous_epoch_state,
+            )
+            .await;
+
+        if let Some(epoch_state) = epoch_state {
+            self.take_snapshot(&reference, epoch_state);
+        }
+
+        reference
+    }
+
+    /// write, in the background, a snapshot of the ledger state of the first
+    /// block of an epoch
+    fn take_snapshot(&self, reference: &Ref, epoch_state: Arc<Ledger>) {
+        if self.snapshots.is_none() {
+            return;
+        }
+        let previous_epoch_ledger = match reference.last_ref_previous_epoch() {
+            Some(previous_epoch) => previous_epoch.ledger(),
+            None => return,
+        };
+        self.write_snapshot(Snapshot::new(
+            reference.header(),
+            reference.ledger(),
+            epoch_state,
+            previous_epoch_ledger,
+        ));
+    }
+
+    /// write the snapshot in the background, if the snapshots are enabled
+    fn write_snapshot(&self, snapshot: Snapshot) {
+        let snapshots = match &self.snapshots {
+            Some(snapshots) => snapshots.clone(),
+            None => return,
+        };
+        let block0 = self.block0;
+
+        tokio::task::spawn_blocking(move || {
+            let logger = snapshots.logger();
+            match snapshots.write(&block0, &snapshot) {
+                Ok(true) => info!(
+                    logger,
+                    "ledger snapshot taken";
+                    "block" => %snapshot.block,
+                    "epoch" => snapshot.epoch,
+                ),
+                Ok(false) => {}
+                Err(e) => warn!(
+                    logger,
+                    "cannot take a snapshot of the ledger";
+                    "block" => %snapshot.block,
+                    "reason" => %e,
+                ),
+            }
+        });
     }
 
     /// Apply the block on the blockchain from a post checked header
@@ -542,26 +815,20 @@ impl Blockchain {
     /// * the block0 does build an invalid `Ledger`: `ErrorKind::Block0InitialLedgerError`;
     ///
     async fn apply_block0(&self, block0: &Block) -> Result<Branch> {
-        let block0_id = block0.header.hash();
-        let block0_date = block0.header.block_date();
-
         let mut branches = self.branches.clone();
 
-        let time_frame = {
-            use crate::blockcfg::Block0DataSource as _;
+        let b = Branch::new(self.create_block0_reference(block0).await?);
+        branches.add(b.clone()).await;
+        Ok(b)
+    }
 
-            let start_time = block0
-                .start_time()
-                .map_err(|err| Error::with_chain(err, ErrorKind::Block0InitialLedgerError))?;
-            let slot_duration = block0
-                .slot_duration()
-                .map_err(|err| Error::with_chain(err, ErrorKind::Block0InitialLedgerError))?;
+    /// create and store the reference of the block0, without adding it to
+    /// the branches
+    async fn create_block0_reference(&self, block0: &Block) -> Result<Arc<Ref>> {
+        let block0_id = block0.header.hash();
+        let block0_date = block0.header.block_date();
 
-            TimeFrame::new(
-                chain_time::Timeline::new(start_time),
-                chain_time::SlotDuration::from_secs(slot_duration.as_secs() as u32),
-            )
-        };
+        let time_frame = block0_time_frame(block0)?;
 
         // we lift the creation of the ledger in the future type
         // this allow chaining of the operation and lifting the error handling
@@ -571,7 +838,7 @@ impl Blockchain {
         let block0_leadership = Leadership::new(block0_date.epoch, &block0_ledger);
         let ledger_parameters = block0_leadership.ledger_parameters().clone();
 
-        let b = self
+        Ok(self
             .create_and_store_reference(
                 block0_id,
                 block0.header.clone(),
@@ -582,10 +849,7 @@ impl Blockchain {
                 Arc::new(ledger_parameters),
                 None,
             )
-            .await;
-        let b = Branch::new(b);
-        branches.add(b.clone()).await;
-        Ok(b)
+            .await)
     }
 
     /// function to do the initial application of the block0 in the `Blockchain` and its
@@ -635,6 +899,9 @@ impl Blockchain {
     /// from the block0 to the `Head` of the storage (the last known block which
     /// made consensus).
     ///
+    /// If ledger snapshots are enabled, the state is rebuilt from the most recent
+    /// snapshot on the way to the `Head` and only the blocks after it are applied.
+    ///
     /// The Future will returns a branch pointing to the `Head`.
     ///
     /// # Errors
@@ -669,16 +936,19 @@ impl Blockchain {
             return Err(ErrorKind::NoTag(MAIN_BRANCH_TAG.to_owned()).into());
         };
 
-        let block0_branch = self.apply_block0(&block0).await?;
+        let (start_branch, start_hash) = match self.load_from_snapshot(head_hash, logger).await? {
+            Some(loaded) => loaded,
+            None => (self.apply_block0(&block0).await?, block0_id),
+        };
 
         let mut block_stream = self
             .storage
-            .stream_from_to(block0_id, head_hash)
+            .stream_from_to(start_hash, head_hash)
             .await
             .map(Box::pin)
             .map_err(|e| Error::with_chain(e, "Cannot iterate blocks from block0 to HEAD"))?;
 
-        let mut branch = block0_branch;
+        let mut branch = start_branch;
         let mut count = 0u64;
 
         let mut block_processing = std::time::Duration::from_secs(0);
@@ -753,62 +1023,508 @@ impl Blockchain {
         Ok(branch)
     }
 
-    pub async fn get_checkpoints(&self, branch: &Branch) -> Checkpoints {
-        Checkpoints::new_from(branch.get_ref().await)
+    /// check the blocks of the storage from the block0 to the `Head`, fully
+    /// validating the link of every block to its parent and the ledger
+    /// transition it makes. The validation stops at the first invalid or
+    /// missing block.
+    ///
+    /// The blocks are followed from the block0 by chain length, preferring
+    /// the ancestors of the `Head`. If the ancestry of the `Head` is broken,
+    /// the blocks below the break are followed on the first fork found.
+    ///
+    /// # Errors
+    ///
+    /// The resulted future may fail if
+    ///
+    /// * the block0 is not already in the storage: `ErrorKind::Block0NotAlreadyInStorage`;
+    /// * the block0 does build a valid `Ledger`: `ErrorKind::Block0InitialLedgerError`;
+    /// * other errors while interacting with the storage (IO errors)
+    ///
+    pub async fn verify_storage(
+        &self,
+        block0: Block,
+        logger: &Logger,
+    ) -> Result<StorageVerification> {
+        let block0_id = block0.header.hash();
+        let already_exist = self
+            .storage
+            .block_exists(block0_id)
+            .await
+            .map_err(|e| Error::with_chain(e, "Cannot check if block0 is in storage"))?;
+
+        if !already_exist {
+            return Err(ErrorKind::Block0NotAlreadyInStorage.into());
+        }
+
+        let head_hash = self
+            .storage
+            .get_tag(MAIN_BRANCH_TAG.to_owned())
+            .await
+            .map_err(|e| Error::with_chain(e, "Cannot get hash of the HEAD tag"))?
+            .ok_or_else(|| ErrorKind::NoTag(MAIN_BRANCH_TAG.to_owned()))?;
+
+        // the ancestors of the head that can be found in the storage
+        let mut head_ancestry = HashSet::new();
+        let mut missing = None;
+        let mut hash = head_hash;
+        while hash != block0_id {
+            match self.storage.get(hash).await? {
+                Some(block) => {
+                    head_ancestry.insert(hash);
+                    hash = block.header.block_parent_hash();
+                }
+                None => {
+                    missing = Some(hash);
+                    break;
+                }
+            }
+        }
+
+        let mut last_valid = self.apply_block0(&block0).await?.get_ref().await;
+        let mut verified = 0u64;
+
+        while last_valid.hash() != head_hash {
+            let chain_length: u32 = last_valid.chain_length().into();
+            let children: Vec<Block> = self
+                .storage
+                .get_blocks_by_chain_length(chain_length as u64 + 1)
+                .await?
+                .into_iter()
+                .filter(|block| block.header.block_parent_hash() == last_valid.hash())
+                .collect();
+            let next = children
+                .iter()
+                .position(|block| head_ancestry.contains(&block.header.hash()))
+                .or_else(|| if children.is_empty() { None } else { Some(0) });
+
+            let block = match next {
+                Some(index) => children.into_iter().nth(index).unwrap(),
+                None => {
+                    let missing = missing.unwrap_or(head_hash);
+                    return Ok(StorageVerification {
+                        verified,
+                        last_valid,
+                        first_invalid: Some((
+                            missing,
+                            ErrorKind::MissingParentBlock(missing).into(),
+                        )),
+                    });
+                }
+            };
+            let block_id = block.header.hash();
+
+            match self.verify_block(Arc::clone(&last_valid), block).await {
+                Ok(new_ref) => last_valid = new_ref,
+                Err(e) => {
+                    return Ok(StorageVerification {
+                        verified,
+                        last_valid,
+                        first_invalid: Some((block_id, e)),
+                    })
+                }
+            }
+
+            verified += 1;
+            const PROCESS_LOGGING_DISTANCE: u64 = 2500;
+            if verified % PROCESS_LOGGING_DISTANCE == 0 {
+                info!(
+                    logger,
+                    "verifying storage, currently at {}",
+                    last_valid.header().description(),
+                );
+            }
+        }
+
+        Ok(StorageVerification {
+            verified,
+            last_valid,
+            first_invalid: None,
+        })
     }
-}
 
-fn write_reward_info(
-    epoch: Epoch,
-    parent_hash: HeaderHash,
-    rewards_info: &EpochRewardsInfo,
-) -> std::io::Result<()> {
-    use std::{
-        env::var,
-        fs::rename,
-        fs::File,
-        io::{BufWriter, Write},
-        path::PathBuf,
-    };
-
-    if let Ok(directory) = var("JORMUNGANDR_REWARD_DUMP_DIRECTORY") {
-        let directory = PathBuf::from(directory);
-
-        std::fs::create_dir_all(&directory)?;
-
-        let filepath = format!("reward-info-{}-{}", epoch, parent_hash);
-        let filepath = directory.join(filepath);
-        let filepath_tmp = format!("tmp.reward-info-{}-{}", epoch, parent_hash);
-        let filepath_tmp = directory.join(filepath_tmp);
+    /// validate the block on top of its parent and create its `Ref`
+    async fn verify_block(&self, parent: Arc<Ref>, block: Block) -> Result<Arc<Ref>> {
+        let header = block.header.clone();
+
+        if !block.is_consistent() {
+            return Err(ErrorKind::BlockHeaderVerificationFailed(
+                "the block content does not match the header".to_owned(),
+            )
+            .into());
+        }
+        pre_verify_link(&header, parent.header())
+            .map_err(|e| ErrorKind::BlockHeaderVerificationFailed(e.to_string()))?;
+
+        let post_checked_header = self
+            .post_check_header(header, parent, CheckHeaderProof::Enabled)
+            .await?;
+        let new_ledger = self.apply_block_dry_run(&post_checked_header, &block)?;
+        Ok(self
+            .apply_block_finalize(post_checked_header, new_ledger)
+            .await)
+    }
+
+    /// rebuild the state of the blockchain from the most recent ledger snapshot
+    /// of a block between the block0 and `head`.
+    ///
+    /// Snapshots that cannot be used are skipped. Returns the branch pointing
+    /// to the block of the snapshot and its hash, or `None` if no snapshot
+    /// could be used.
+    async fn load_from_snapshot(
+        &self,
+        head: HeaderHash,
+        logger: &Logger,
+    ) -> Result<Option<(Branch, HeaderHash)>> {
+        let snapshots = match &self.snapshots {
+            Some(snapshots) => snapshots,
+            None => return Ok(None),
+        };
+        let available = match snapshots.list() {
+            Ok(available) => available,
+            Err(e) => {
+                warn!(logger, "cannot list the ledger snapshots"; "reason" => %e);
+                return Ok(None);
+            }
+        };
+
+        for (chain_length, block_id) in available {
+            let on_main_branch = self
+                .storage
+                .find_closest_ancestor(vec![block_id], head)
+                .await
+                .map_err(|e| Error::with_chain(e, "Cannot check if the snapshot is before HEAD"))?
+                .is_some();
+            if !on_main_branch {
+                debug!(
+                    logger,
+                    "ignoring ledger snapshot of a block not on the HEAD branch";
+                    "block" => %block_id,
+                );
+                continue;
+            }
+
+            let snapshot = {
+                let snapshots = snapshots.clone();
+                let block0_id = self.block0;
+                tokio::task::spawn_blocking(move || {
+                    snapshots.read(&block0_id, chain_length, &block_id)
+                })
+                .await
+            };
+            let snapshot = match snapshot {
+                Ok(Ok(snapshot)) => snapshot,
+                Err(e) => {
+                    warn!(
+                        logger,
+                        "the read of the ledger snapshot was aborted";
+                        "block" => %block_id,
+                        "reason" => %e,
+                    );
+                    continue;
+                }
+                Ok(Err(e)) => {
+                    warn!(
+                        logger,
+                        "cannot read the ledger snapshot";
+                        "block" => %block_id,
+                        "reason" => %e,
+                    );
+                    continue;
+                }
+            };
+
+            let reference = match self.load_snapshot(snapshot).await {
+                Ok(reference) => reference,
+                Err(e) => {
+                    warn!(
+                        logger,
+                        "cannot load the ledger snapshot";
+                        "block" => %block_id,
+                        "reason" => %e,
+                    );
+                    continue;
+                }
+            };
 
+            info!(
+                logger,
+                "resuming from the ledger snapshot at {}",
+                reference.header().description()
+            );
+
+            let branch = Branch::new(reference);
+            self.branches.clone().add(branch.clone()).await;
+            return Ok(Some((branch, block_id)));
+        }
+
+        Ok(None)
+    }
+
+    /// create the `Ref` of the block of the given ledger snapshot. The block
+    /// and its parent need to be in the storage already.
+    ///
+    /// # Errors
+    ///
+    /// The resulted future may fail if
+    ///
+    /// * the block or its parent is not in the storage;
+    /// * the block is not the first block of the epoch of the snapshot:
+    ///   `ErrorKind::LedgerSnapshotMismatch`;
+    /// * other errors while interacting with the storage (IO errors)
+    ///
+    pub async fn load_snapshot(&self, snapshot: Snapshot) -> Result<Arc<Ref>> {
+        let block0 = self
+            .storage
+            .get(self.block0)
+            .await?
+            .ok_or(StorageError::BlockNotFound)?;
+        let header = self
+            .storage
+            .get(snapshot.block)
+            .await?
+            .ok_or(StorageError::BlockNotFound)?
+            .header;
+        let parent_hash = header.block_parent_hash();
+        let parent_header = self
+            .storage
+            .get(parent_hash)
+            .await?
+            .ok_or_else(|| ErrorKind::MissingParentBlock(parent_hash))?
+            .header;
+
+        if header.block_date().epoch != snapshot.epoch
+            || parent_header.block_date().epoch >= snapshot.epoch
         {
-            let file = File::create(&filepath_tmp)?;
-            let mut buf = BufWriter::new(file);
-            write!(&mut buf, "type,identifier,received,distributed\r\n")?;
-            write!(&mut buf, "drawn,,,{}\r\n", rewards_info.drawn.0)?;
-            write!(&mut buf, "fees,,,{}\r\n", rewards_info.fees.0)?;
-            write!(&mut buf, "treasury,,{},\r\n", rewards_info.treasury.0)?;
-
-            for (pool_id, (taxed, distr)) in rewards_info.stake_pools.iter() {
-                write!(&mut buf, "pool,{},{},{}\r\n", pool_id, taxed.0, distr.0)?;
+            return Err(ErrorKind::LedgerSnapshotMismatch(snapshot.block).into());
+        }
+
+        let time_frame = Arc::new(block0_time_frame(&block0)?);
+        let leadership = Arc::new(Leadership::new(snapshot.epoch, &snapshot.epoch_state));
+        let ledger_parameters = Arc::new(leadership.ledger_parameters().clone());
+
+        // the last block of the previous epoch is only needed to compute the
+        // leadership of the next epoch. It is not put in the cache as we do not
+        // have the leadership of its own epoch.
+        let previous_epoch_ledger = self
+            .ledgers
+            .insert(
+                parent_header.chain_length(),
+                parent_hash,
+                (*snapshot.previous_epoch_ledger).clone(),
+            )
+            .await;
+        let previous_epoch_state = Arc::new(Ref::new(
+            previous_epoch_ledger,
+            Arc::clone(&time_frame),
+            Arc::clone(&leadership),
+            Arc::clone(&ledger_parameters),
+            None,
+            parent_header,
+            None,
+        ));
+
+        Ok(self
+            .create_and_store_reference(
+                snapshot.block,
+                header,
+                (*snapshot.ledger).clone(),
+                time_frame,
+                leadership,
+                None, // the rewards info is not part of the snapshot
+                ledger_parameters,
+                Some(previous_epoch_state),
+            )
+            .await)
+    }
+
+    /// load a ledger snapshot received from another node, see `load_snapshot`,
+    /// and keep it along the snapshots taken by this node.
+    pub async fn import_snapshot(&self, snapshot: Snapshot) -> Result<Arc<Ref>> {
+        let reference = self.load_snapshot(snapshot.clone()).await?;
+        self.write_snapshot(snapshot);
+        Ok(reference)
+    }
+
+    pub fn snapshots(&self) -> Option<&Snapshots> {
+        self.snapshots.as_ref()
+    }
+
+    pub fn rewards_dump(&self) -> Option<&RewardsDump> {
+        self.rewards_report.dump.as_ref()
+    }
+
+    pub fn rewards_index(&self) -> Option<&RewardsIndex> {
+        self.rewards_report.index.as_ref()
+    }
+
+    /// remove the branches that fell too far behind the given tip to ever be
+    /// selected, then release the `Ref`s and ledger states that only these
+    /// branches were using. Returns the tips of the removed branches.
+    ///
+    /// A branch is stale once its tip is more than `epoch_stability_depth`
+    /// blocks behind the tip, or more than `max_lag` blocks if it is lower.
+    pub async fn gc_branches(&self, tip: &Ref, max_lag: Option<u32>) -> Result<Vec<Arc<Ref>>> {
+        let epoch_stability_depth = tip.epoch_ledger_parameters().epoch_stability_depth;
+        let max_lag = max_lag.map_or(epoch_stability_depth, |max_lag| {
+            max_lag.min(epoch_stability_depth)
+        });
+
+        let removed = self.branches.clone().remove_stale(tip, max_lag).await;
+
+        for branch_tip in removed.iter() {
+            // forget the blocks of the stale branch up to the block where it
+            // forked from the tip's branch
+            let mut hash = branch_tip.hash();
+            while self.storage.is_ancestor(hash, tip.hash()).await?.is_none() {
+                match self.ref_cache.remove(&hash).await {
+                    Some(reference) => hash = reference.block_parent_hash(),
+                    None => break,
+                }
+            }
+        }
+
+        if !removed.is_empty() {
+            self.ledgers.purge().await;
+        }
+
+        Ok(removed)
+    }
+
+    /// report the rewards distributed at the epoch boundaries the tip moved
+    /// across, going from `old_tip` to `new_tip`, to the rewards dump and
+    /// index. The boundaries already on the branch of the old tip were
+    /// reported when it moved across them, the candidate forks are never
+    /// reported.
+    ///
+    /// The reports are written on the blocking thread pool, the errors are
+    /// logged.
+    pub async fn record_rewards(&self, old_tip: &Ref, new_tip: Arc<Ref>, logger: &Logger) {
+        if self.rewards_report.dump.is_none() && self.rewards_report.index.is_none() {
+            return;
+        }
+
+        let old_epoch = old_tip.block_date().epoch;
+        let mut boundaries = Vec::new();
+        let mut current = new_tip;
+        while let Some(parent) = current.last_ref_previous_epoch().cloned() {
+            let epoch = current.block_date().epoch;
+            if epoch <= old_epoch {
+                match self
+                    .storage
+                    .is_ancestor(parent.hash(), old_tip.hash())
+                    .await
+                {
+                    Ok(Some(_)) => break,
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!(
+                            logger,
+                            "cannot find the rewards to report";
+                            "reason" => %e,
+                        );
+                        return;
+                    }
+                }
             }
+            // the rewards info is carried over from the previous epoch if no
+            // rewards were distributed
+            match (current.epoch_rewards_info(), parent.epoch_rewards_info()) {
+                (Some(rewards_info), Some(previous)) if Arc::ptr_eq(rewards_info, previous) => {}
+                (Some(rewards_info), _) => {
+                    boundaries.push((epoch, parent.hash(), Arc::clone(rewards_info)))
+                }
+                (None, _) => {}
+            }
+            current = parent;
+        }
+        if boundaries.is_empty() {
+            return;
+        }
 
-            for (account_id, received) in rewards_info.accounts.iter() {
-                write!(&mut buf, "account,{},{},\r\n", account_id, received.0)?;
+        let rewards_report = self.rewards_report.clone();
+        let recorded = tokio::task::spawn_blocking(move || {
+            for (epoch, parent, rewards_info) in boundaries.iter().rev() {
+                rewards_report.record(*epoch, parent, rewards_info);
             }
+        })
+        .await;
+        if let Err(e) = recorded {
+            error!(logger, "the report of the rewards was aborted"; "reason" => %e);
+        }
+    }
 
-            buf.flush()?;
+    pub async fn get_checkpoints(&self, branch: &Branch) -> Checkpoints {
+        Checkpoints::new_from(branch.get_ref().await)
+    }
+}
+
+/// the time frame of the blockchain, as set in the block0
+fn block0_time_frame(block0: &Block) -> Result<TimeFrame> {
+    use crate::blockcfg::Block0DataSource as _;
+
+    let start_time = block0
+        .start_time()
+        .map_err(|err| Error::with_chain(err, ErrorKind::Block0InitialLedgerError))?;
+    let slot_duration = block0
+        .slot_duration()
+        .map_err(|err| Error::with_chain(err, ErrorKind::Block0InitialLedgerError))?;
+
+    Ok(TimeFrame::new(
+        chain_time::Timeline::new(start_time),
+        chain_time::SlotDuration::from_secs(slot_duration.as_secs() as u32),
+    ))
+}
+
+impl RewardsReport {
+    /// the rewards of the accounts are needed to index them
+    fn parameters(&self) -> RewardsInfoParameters {
+        if self.report_all || self.index.is_some() {
+            RewardsInfoParameters::report_all()
+        } else {
+            RewardsInfoParameters::default()
         }
+    }
 
-        rename(filepath_tmp, filepath)?;
+    /// write the rewards distributed at the beginning of `epoch` to the dump
+    /// and the index. The errors are logged, they do not prevent the epoch
+    /// from starting. This blocks on the file system.
+    fn record(&self, epoch: Epoch, parent: &HeaderHash, rewards_info: &EpochRewardsInfo) {
+        if let Some(dump) = &self.dump {
+            if let Err(err) = dump.write(epoch, parent, rewards_info) {
+                error!(
+                    dump.logger(),
+                    "cannot write the rewards dump";
+                    "epoch" => epoch,
+                    "parent" => %parent,
+                    "reason" => %err,
+                );
+            }
+        }
+        if let Some(index) = &self.index {
+            if let Err(err) = index.write(epoch, parent, rewards_info) {
+                error!(
+                    index.logger(),
+                    "cannot index the rewards of the accounts";
+                    "epoch" => epoch,
+                    "parent" => %parent,
+                    "reason" => %err,
+                );
+            }
+        }
     }
-    Ok(())
 }
 
+/// compute the leadership state of the given epoch following the `parent` block.
+///
+/// The last element is the ledger the leadership was built from, it is only set
+/// when `epoch` is a new epoch compared to the `parent`'s. The rewards
+/// distributed at the beginning of a new epoch are kept as needed by
+/// `rewards_report`, they are only reported once a block of the epoch becomes
+/// the tip, see `Blockchain::record_rewards`.
 pub fn new_epoch_leadership_from(
     epoch: Epoch,
     parent: Arc<Ref>,
-    rewards_report_all: bool,
+    rewards_report: &RewardsReport,
 ) -> (
     Arc<Ledger>,
     Arc<Leadership>,
@@ -816,6 +1532,7 @@ pub fn new_epoch_leadership_from(
     Option<Arc<EpochRewardsInfo>>,
     Arc<TimeFrame>,
     Option<Arc<Ref>>,
+    Option<Arc<Ledger>>,
 ) {
     let parent_ledger_state = parent.ledger();
     let parent_epoch_leadership_schedule = parent.epoch_leadership_schedule().clone();
@@ -834,22 +1551,13 @@ pub fn new_epoch_leadership_from(
         // 1. distribute the rewards (if any) This will give us the transition state
         let (transition_state, epoch_rewards_info) =
             if let Some(distribution) = parent.epoch_leadership_schedule().stake_distribution() {
-                let reward_info_dist = if rewards_report_all {
-                    RewardsInfoParameters::report_all()
-                } else {
-                    RewardsInfoParameters::default()
-                };
-
                 let (ledger, rewards_info) = parent_ledger_state
                     .distribute_rewards(
                         distribution,
                         &parent.epoch_ledger_parameters(),
-                        reward_info_dist,
+                        rewards_report.parameters(),
                     )
                     .expect("Distribution of rewards will not overflow");
-                if let Err(err) = write_reward_info(epoch, parent.hash(), &rewards_info) {
-                    panic!("Error while storing the reward dump, err {}", err)
-                }
                 (Arc::new(ledger), Some(Arc::new(rewards_info)))
             } else {
                 (parent_ledger_state.clone(), parent_epoch_rewards_info)
@@ -879,6 +1587,7 @@ pub fn new_epoch_leadership_from(
             epoch_rewards_info,
             parent_time_frame,
             previous_epoch_state,
+            Some(epoch_state),
         )
     } else {
         (
@@ -888,6 +1597,259 @@ pub fn new_epoch_leadership_from(
             parent_epoch_rewards_info,
             parent_time_frame,
             parent.last_ref_previous_epoch().map(Arc::clone),
+            None,
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::test_chain::{apply_all, logger, TestChain, SLOTS_PER_EPOCH};
+    use crate::utils::test_dir::TestDir;
+
+    const CACHE_CAPACITY: usize = 64;
+
+    /// load the block0 and apply a branch of `length` blocks on top of it,
+    /// returns the headers of the branch
+    async fn main_branch(chain: &TestChain, blockchain: &Blockchain, length: usize) -> Vec<Header> {
+        blockchain
+            .load_from_block0(chain.block0.clone())
+            .await
+            .unwrap();
+        let blocks = chain.branch(&chain.block0.header, length, 0);
+        let headers = blocks.iter().map(|block| block.header.clone()).collect();
+        apply_all(blockchain, blocks).await;
+        headers
+    }
+
+    #[tokio::test]
+    async fn replay_from_the_cache() {
+        let chain = TestChain::new("chain-replay-cache", 10);
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        let headers = main_branch(&chain, &blockchain, 5).await;
+        let target = headers[3].hash();
+
+        // forget the block and its parent, the grandparent stays in the cache
+        blockchain.ref_cache.remove(&target).await;
+        blockchain.ref_cache.remove(&headers[2].hash()).await;
+
+        let reference = blockchain.replay_ref(target, 2).await.unwrap().unwrap();
+        assert_eq!(reference.hash(), target);
+        assert_eq!(reference.chain_length(), headers[3].chain_length());
+        // the rebuilt state is not kept
+        assert!(blockchain.ref_cache.get(target).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn replay_from_block0() {
+        let chain = TestChain::new("chain-replay-block0", 10);
+        let headers = main_branch(&chain, &chain.blockchain(CACHE_CAPACITY, None), 5).await;
+
+        // a blockchain over the same storage, with nothing in its cache
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        let block0_hash = chain.block0.header.hash();
+        let block0 = blockchain
+            .get_or_replay_ref(block0_hash)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(block0.hash(), block0_hash);
+
+        let reference = blockchain
+            .get_or_replay_ref(headers[4].hash())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reference.hash(), headers[4].hash());
+        assert_eq!(reference.chain_length(), headers[4].chain_length());
+
+        let unknown = HeaderHash::hash_bytes(&[0]);
+        assert!(blockchain
+            .get_or_replay_ref(unknown)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn replay_from_a_snapshot() {
+        let dir = TestDir::new("chain-replay-snapshot-ledgers");
+        let chain = TestChain::new("chain-replay-snapshot", 10);
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        // the branch ends on the third block of the second epoch
+        let headers = main_branch(&chain, &blockchain, SLOTS_PER_EPOCH as usize + 2).await;
+        let first_of_epoch = &headers[SLOTS_PER_EPOCH as usize - 1];
+        let last = &headers[SLOTS_PER_EPOCH as usize + 1];
+        assert_eq!(first_of_epoch.block_date().epoch, 1);
+        assert_eq!(first_of_epoch.block_date().slot_id, 0);
+
+        // the snapshot the blockchain would take of the first block of the
+        // epoch, written synchronously
+        let reference = blockchain
+            .get_ref(first_of_epoch.hash())
+            .await
+            .unwrap()
+            .unwrap();
+        let previous_epoch_ledger = reference.last_ref_previous_epoch().unwrap().ledger();
+        let snapshot = Snapshot::new(
+            reference.header(),
+            reference.ledger(),
+            Arc::clone(&previous_epoch_ledger),
+            previous_epoch_ledger,
+        );
+        let snapshots = Snapshots::new(dir.path().to_owned(), 2, logger());
+        assert!(snapshots
+            .write(&chain.block0.header.hash(), &snapshot)
+            .unwrap());
+
+        let blockchain = chain.blockchain(CACHE_CAPACITY, Some(snapshots));
+        let reference = blockchain
+            .get_or_replay_ref(first_of_epoch.hash())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reference.hash(), first_of_epoch.hash());
+
+        // two blocks from the snapshot, instead of twelve from the block0
+        let reference = blockchain
+            .replay_ref(last.hash(), 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reference.hash(), last.hash());
+        assert_eq!(reference.chain_length(), last.chain_length());
+
+        // the blocks before the snapshot are still replayed from the block0
+        let reference = blockchain
+            .get_or_replay_ref(headers[4].hash())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reference.hash(), headers[4].hash());
+    }
+
+    #[tokio::test]
+    async fn replay_depth_is_bounded() {
+        let chain = TestChain::new("chain-replay-depth", 10);
+        let headers = main_branch(&chain, &chain.blockchain(CACHE_CAPACITY, None), 5).await;
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        let target = headers[4].hash();
+
+        // the four ancestors of the block and the block0
+        let error = blockchain
+            .replay_ref(target, 4)
+            .await
+            .err()
+            .expect("the block is too deep to be replayed");
+        match error.kind() {
+            ErrorKind::ReplayTooDeep(hash, max) => {
+                assert_eq!(*hash, target);
+                assert_eq!(*max, 4);
+            }
+            _ => panic!("unexpected error: {}", error),
+        }
+
+        assert!(blockchain.replay_ref(target, 5).await.unwrap().is_some());
+    }
+
+    /// apply a block forking from `parent` and add its branch
+    async fn fork(chain: &TestChain, blockchain: &Blockchain, parent: &Header) -> Arc<Ref> {
+        let reference = apply_all(blockchain, chain.branch(parent, 1, 1)).await;
+        blockchain
+            .branches()
+            .clone()
+            .apply_or_create(Arc::clone(&reference))
+            .await;
+        reference
+    }
+
+    fn sorted_hashes(references: Vec<Arc<Ref>>) -> Vec<HeaderHash> {
+        let mut hashes: Vec<_> = references
+            .iter()
+            .map(|reference| reference.hash())
+            .collect();
+        hashes.sort();
+        hashes
+    }
+
+    #[tokio::test]
+    async fn stale_branches_are_removed() {
+        let chain = TestChain::new("chain-gc-stale", 3);
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        let mut main = blockchain
+            .load_from_block0(chain.block0.clone())
+            .await
+            .unwrap();
+        let blocks = chain.branch(&chain.block0.header, 8, 0);
+        let headers: Vec<Header> = blocks.iter().map(|block| block.header.clone()).collect();
+        let tip = apply_all(&blockchain, blocks).await;
+        main.update_ref(Arc::clone(&tip)).await;
+
+        // two blocks forking from the block0, six blocks behind the tip
+        let stale_blocks = chain.branch(&chain.block0.header, 2, 1);
+        let stale_hashes: Vec<_> = stale_blocks
+            .iter()
+            .map(|block| block.header.hash())
+            .collect();
+        let stale = apply_all(&blockchain, stale_blocks).await;
+        blockchain
+            .branches()
+            .clone()
+            .apply_or_create(Arc::clone(&stale))
+            .await;
+        // one block behind the tip
+        let recent = fork(&chain, &blockchain, &headers[5]).await;
+
+        let removed = blockchain.gc_branches(&tip, None).await.unwrap();
+        assert_eq!(sorted_hashes(removed), vec![stale.hash()]);
+        assert_eq!(
+            sorted_hashes(blockchain.branches().branches().await),
+            sorted_hashes(vec![tip, recent]),
+        );
+
+        // the states of the stale branch are released, down to the block0
+        // the branches share
+        for hash in stale_hashes {
+            assert!(blockchain.ref_cache.get(hash).await.is_none());
+        }
+        assert!(blockchain
+            .ref_cache
+            .get(chain.block0.header.hash())
+            .await
+            .is_some());
+        for header in headers {
+            assert!(blockchain.ref_cache.get(header.hash()).await.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn max_lag_is_clamped_to_the_epoch_stability_depth() {
+        let chain = TestChain::new("chain-gc-max-lag", 3);
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        let mut main = blockchain
+            .load_from_block0(chain.block0.clone())
+            .await
+            .unwrap();
+        let blocks = chain.branch(&chain.block0.header, 8, 0);
+        let headers: Vec<Header> = blocks.iter().map(|block| block.header.clone()).collect();
+        let tip = apply_all(&blockchain, blocks).await;
+        main.update_ref(Arc::clone(&tip)).await;
+
+        // two and four blocks behind the tip
+        let behind_2 = fork(&chain, &blockchain, &headers[4]).await;
+        let behind_4 = fork(&chain, &blockchain, &headers[2]).await;
+
+        // a larger lag than the epoch stability depth is not allowed
+        let removed = blockchain.gc_branches(&tip, Some(10)).await.unwrap();
+        assert_eq!(sorted_hashes(removed), vec![behind_4.hash()]);
+
+        // a lower one is
+        let removed = blockchain.gc_branches(&tip, Some(1)).await.unwrap();
+        assert_eq!(sorted_hashes(removed), vec![behind_2.hash()]);
+        assert_eq!(
+            sorted_hashes(blockchain.branches().branches().await),
+            vec![tip.hash()],
+        );
+    }
+}
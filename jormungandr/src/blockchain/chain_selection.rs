@@ -1,9 +1,13 @@
-use crate::blockchain::{Ref, Storage};
+use crate::blockchain::{Ref, Storage, StorageError};
+use chain_core::property::Block as _;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Hash)]
 pub enum ComparisonResult {
     PreferCurrent,
     PreferCandidate,
+    /// the candidate would have been preferred but it forks from the current
+    /// branch more than `epoch_stability_depth` blocks before the current tip
+    RejectDeepFork,
 }
 
 /// chose which of the two Ref is the most interesting to keep as a branch
@@ -13,18 +17,24 @@ pub enum ComparisonResult {
 /// on the same block date is to a requirement to call this function as it will still
 /// work to make a choice as to which of these two Ref is the right choice.
 ///
-pub fn compare_against(storage: &Storage, current: &Ref, candidate: &Ref) -> ComparisonResult {
+pub async fn compare_against(
+    storage: &Storage,
+    current: &Ref,
+    candidate: &Ref,
+) -> Result<ComparisonResult, StorageError> {
     let epoch_stability_depth = current.epoch_ledger_parameters().epoch_stability_depth;
 
-    let rollback_possible =
-        check_rollback_up_to(epoch_stability_depth, storage, current, candidate);
+    if is_in_future(candidate) || current.chain_length() >= candidate.chain_length() {
+        return Ok(ComparisonResult::PreferCurrent);
+    }
 
-    let not_in_future = !is_in_future(candidate);
+    let rollback_possible =
+        check_rollback_up_to(epoch_stability_depth, storage, current, candidate).await?;
 
-    if rollback_possible && not_in_future && current.chain_length() < candidate.chain_length() {
-        ComparisonResult::PreferCandidate
+    if rollback_possible {
+        Ok(ComparisonResult::PreferCandidate)
     } else {
-        ComparisonResult::PreferCurrent
+        Ok(ComparisonResult::RejectDeepFork)
     }
 }
 
@@ -34,11 +44,77 @@ fn is_in_future(node: &Ref) -> bool {
     node.elapsed().is_err()
 }
 
-fn check_rollback_up_to(
-    _epoch_stability_depth: u32,
-    _: &Storage,
-    _ref1: &Ref,
-    _ref2: &Ref,
-) -> bool {
-    true
+/// returns `true` if switching from `current` to `candidate` does not roll
+/// back more than `epoch_stability_depth` blocks of the current branch, i.e.
+/// the block of the current branch `epoch_stability_depth` blocks before
+/// the current tip is also an ancestor of the candidate.
+async fn check_rollback_up_to(
+    epoch_stability_depth: u32,
+    storage: &Storage,
+    current: &Ref,
+    candidate: &Ref,
+) -> Result<bool, StorageError> {
+    // the candidate is on top of the current branch, nothing is rolled back
+    if candidate.block_parent_hash() == current.hash()
+        || storage
+            .is_ancestor(current.hash(), candidate.hash())
+            .await?
+            .is_some()
+    {
+        return Ok(true);
+    }
+
+    let stable_chain_length =
+        match stable_chain_length(current.chain_length().into(), epoch_stability_depth) {
+            Some(stable_chain_length) => stable_chain_length,
+            None => return Ok(true),
+        };
+
+    let stable_candidates = storage
+        .get_blocks_by_chain_length(stable_chain_length as u64)
+        .await?
+        .into_iter()
+        .map(|block| block.id())
+        .collect();
+    let stable = storage
+        .find_closest_ancestor(stable_candidates, current.hash())
+        .await?;
+
+    match stable {
+        Some(stable) => Ok(storage
+            .is_ancestor(stable.header_hash, candidate.hash())
+            .await?
+            .is_some()),
+        // the current branch cannot be followed back in the storage, do not
+        // take the risk of rolling back stable blocks
+        None => Ok(false),
+    }
+}
+
+/// the chain length of the most recent block of a branch that can no longer
+/// be rolled back, `None` if the branch is too short to have one
+fn stable_chain_length(current_chain_length: u32, epoch_stability_depth: u32) -> Option<u32> {
+    if current_chain_length <= epoch_stability_depth {
+        None
+    } else {
+        Some(current_chain_length - epoch_stability_depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_branches_have_no_stable_block() {
+        assert_eq!(stable_chain_length(0, 10), None);
+        assert_eq!(stable_chain_length(10, 10), None);
+    }
+
+    #[test]
+    fn stable_block_is_stability_depth_behind_the_tip() {
+        assert_eq!(stable_chain_length(11, 10), Some(1));
+        assert_eq!(stable_chain_length(2500, 2160), Some(340));
+        assert_eq!(stable_chain_length(5, 0), Some(5));
+    }
 }
@@ -7,7 +7,12 @@ mod multiverse;
 mod process;
 mod reference;
 mod reference_cache;
+mod rewards_dump;
+mod rewards_index;
+mod snapshot;
 mod storage;
+#[cfg(test)]
+pub mod test_chain;
 mod tip;
 
 // Constants
@@ -27,14 +32,18 @@ mod chunk_sizes {
 pub use self::{
     branch::Branch,
     chain::{
-        new_epoch_leadership_from, Blockchain, CheckHeaderProof, Error, ErrorKind,
-        PreCheckedHeader, MAIN_BRANCH_TAG,
+        new_epoch_leadership_from, pre_verify_link, Blockchain, CheckHeaderProof, Error, ErrorKind,
+        HeaderChainVerifyError, PreCheckedHeader, RewardsReport, StorageVerification,
+        MAIN_BRANCH_TAG,
     },
     chain_selection::{compare_against, ComparisonResult},
     checkpoints::Checkpoints,
     multiverse::Multiverse,
     process::{process_new_ref, Process},
     reference::Ref,
+    rewards_dump::{Error as RewardsDumpError, Format as RewardsDumpFormat, RewardsDump},
+    rewards_index::{AccountReward, Error as RewardsIndexError, RewardsIndex},
+    snapshot::{Error as SnapshotError, Snapshot, Snapshots},
     storage::{Error as StorageError, Storage},
     tip::Tip,
 };
@@ -7,9 +7,10 @@ use super::{
 use crate::{
     blockcfg::{Block, FragmentId, Header, HeaderHash},
     blockchain::Checkpoints,
+    events::{Event, Events},
     intercom::{self, BlockMsg, ExplorerMsg, NetworkMsg, PropagateMsg, TransactionMsg},
     log,
-    network::p2p::Address,
+    network::p2p::{Address, Behavior},
     stats_counter::StatsCounter,
     utils::{
         async_msg::{self, MessageBox, MessageQueue},
@@ -23,9 +24,13 @@ use chain_core::property::{Block as _, Fragment as _, HasHeader as _, Header as
 use jormungandr_lib::interfaces::FragmentStatus;
 
 use futures::prelude::*;
+use lru::LruCache;
 use slog::Logger;
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 type PullHeadersScheduler = FireForgetScheduler<HeaderHash, Address, Checkpoints>;
 type GetNextBlockScheduler = FireForgetScheduler<HeaderHash, Address, ()>;
@@ -44,6 +49,10 @@ const PULL_HEADERS_SCHEDULER_CONFIG: FireForgetSchedulerConfig = FireForgetSched
     timeout: Duration::from_millis(500),
 };
 
+/// number of announced blocks whose first announcer is remembered until the
+/// block is applied
+const MAX_ANNOUNCERS: usize = 1_024;
+
 const GET_NEXT_BLOCK_SCHEDULER_CONFIG: FireForgetSchedulerConfig = FireForgetSchedulerConfig {
     max_running: 16,
     max_running_same_task: 2,
@@ -58,7 +67,9 @@ pub struct Process {
     pub network_msgbox: MessageBox<NetworkMsg>,
     pub fragment_msgbox: MessageBox<TransactionMsg>,
     pub explorer_msgbox: Option<MessageBox<ExplorerMsg>>,
+    pub events: Events,
     pub garbage_collection_interval: Duration,
+    pub branch_max_lag: Option<u32>,
 }
 
 impl Process {
@@ -68,14 +79,17 @@ impl Process {
         mut input: MessageQueue<BlockMsg>,
     ) {
         self.start_branch_reprocessing(&service_info);
+        self.start_branch_garbage_collection(&service_info);
         let pull_headers_scheduler = self.spawn_pull_headers_scheduler(&service_info);
         let get_next_block_scheduler = self.spawn_get_next_block_scheduler(&service_info);
+        let announcers = Announcers::new();
         while let Some(msg) = input.next().await {
             self.handle_input(
                 &service_info,
                 msg,
                 &pull_headers_scheduler,
                 &get_next_block_scheduler,
+                &announcers,
             );
         }
     }
@@ -86,11 +100,13 @@ impl Process {
         input: BlockMsg,
         pull_headers_scheduler: &PullHeadersScheduler,
         get_next_block_scheduler: &GetNextBlockScheduler,
+        announcers: &Announcers,
     ) {
         let blockchain = self.blockchain.clone();
         let blockchain_tip = self.blockchain_tip.clone();
         let network_msg_box = self.network_msgbox.clone();
         let explorer_msg_box = self.explorer_msgbox.clone();
+        let events = self.events.clone();
         let tx_msg_box = self.fragment_msgbox.clone();
         let stats_counter = self.stats_counter.clone();
 
@@ -113,6 +129,7 @@ impl Process {
                         tx_msg_box,
                         network_msg_box,
                         explorer_msg_box,
+                        events,
                         block,
                         stats_counter,
                     ),
@@ -135,8 +152,10 @@ impl Process {
                         blockchain_tip,
                         header,
                         node_id,
+                        network_msg_box,
                         pull_headers_scheduler.clone(),
                         get_next_block_scheduler.clone(),
+                        announcers.clone(),
                         logger.clone(),
                     ),
                 )
@@ -156,7 +175,9 @@ impl Process {
                         tx_msg_box,
                         network_msg_box,
                         explorer_msg_box,
+                        events,
                         get_next_block_scheduler,
+                        announcers.clone(),
                         handle,
                         stats_counter,
                         logger,
@@ -187,12 +208,35 @@ impl Process {
     fn start_branch_reprocessing(&self, info: &TokioServiceInfo) {
         let tip = self.blockchain_tip.clone();
         let blockchain = self.blockchain.clone();
+        let events = self.events.clone();
         let logger = info.logger().clone();
 
         info.run_periodic_fallible(
             "branch reprocessing",
             BRANCH_REPROCESSING_INTERVAL,
-            move || reprocess_tip(logger.clone(), blockchain.clone(), tip.clone()),
+            move || {
+                reprocess_tip(
+                    logger.clone(),
+                    blockchain.clone(),
+                    tip.clone(),
+                    events.clone(),
+                )
+            },
+        )
+    }
+
+    fn start_branch_garbage_collection(&self, info: &TokioServiceInfo) {
+        let tip = self.blockchain_tip.clone();
+        let blockchain = self.blockchain.clone();
+        let max_lag = self.branch_max_lag;
+        let logger = info.logger().clone();
+
+        info.run_periodic_fallible(
+            "branch garbage collection",
+            self.garbage_collection_interval,
+            move || {
+                collect_stale_branches(logger.clone(), blockchain.clone(), tip.clone(), max_lag)
+            },
         )
     }
 
@@ -263,7 +307,12 @@ fn try_request_fragment_removal(
 /// this function will re-process the tip against the different branches
 /// this is because a branch may have become more interesting with time
 /// moving forward and branches may have been dismissed
-async fn reprocess_tip(logger: Logger, mut blockchain: Blockchain, tip: Tip) -> Result<(), Error> {
+async fn reprocess_tip(
+    logger: Logger,
+    mut blockchain: Blockchain,
+    tip: Tip,
+    events: Events,
+) -> Result<(), Error> {
     let branches: Vec<Arc<Ref>> = blockchain.branches().branches().await;
 
     let tip_as_ref = tip.get_ref().await;
@@ -274,7 +323,36 @@ async fn reprocess_tip(logger: Logger, mut blockchain: Blockchain, tip: Tip) ->
         .collect::<Vec<_>>();
 
     for other in others {
-        process_new_ref(&logger, &mut blockchain, tip.clone(), Arc::clone(other)).await?
+        process_new_ref_and_notify(
+            &logger,
+            &mut blockchain,
+            tip.clone(),
+            Arc::clone(other),
+            &events,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// remove the branches that are too far behind the tip to ever be selected
+async fn collect_stale_branches(
+    logger: Logger,
+    blockchain: Blockchain,
+    tip: Tip,
+    max_lag: Option<u32>,
+) -> Result<(), Error> {
+    let tip_ref = tip.get_ref().await;
+    let removed = blockchain.gc_branches(&tip_ref, max_lag).await?;
+
+    for branch_tip in removed {
+        info!(
+            logger,
+            "removed stale branch {} | current-tip {}",
+            branch_tip.header().description(),
+            tip_ref.header().description(),
+        );
     }
 
     Ok(())
@@ -289,16 +367,24 @@ async fn reprocess_tip(logger: Logger, mut blockchain: Blockchain, tip: Tip) ->
 /// If the current tip is not the one being updated we will then trigger
 /// chain selection after updating that other branch as it may be possible that
 /// this branch just became more interesting for the current consensus algorithm.
+///
+/// A candidate forking from the current branch deeper than the epoch stability
+/// depth is never selected, the returned `ComparisonResult` tells the caller
+/// when this happens.
 pub async fn process_new_ref(
     logger: &Logger,
     blockchain: &mut Blockchain,
     mut tip: Tip,
     candidate: Arc<Ref>,
-) -> Result<(), Error> {
+) -> Result<ComparisonResult, Error> {
     let candidate_hash = candidate.hash();
     let tip_ref = tip.get_ref().await;
 
-    match chain_selection::compare_against(blockchain.storage(), &tip_ref, &candidate) {
+    let comparison = chain_selection::compare_against(blockchain.storage(), &tip_ref, &candidate)
+        .await
+        .map_err(|e| Error::with_chain(e, "Cannot compare the candidate against the tip"))?;
+
+    match comparison {
         ComparisonResult::PreferCurrent => {
             info!(
                 logger,
@@ -322,7 +408,7 @@ pub async fn process_new_ref(
                     .await
                     .map_err(|e| Error::with_chain(e, "Cannot update the main storage's tip"))?;
 
-                tip.update_ref(candidate).await;
+                tip.update_ref(Arc::clone(&candidate)).await;
             } else {
                 info!(
                     logger,
@@ -337,13 +423,46 @@ pub async fn process_new_ref(
                     .await
                     .map_err(|e| Error::with_chain(e, "Cannot update the main storage's tip"))?;
 
-                let branch = blockchain.branches_mut().apply_or_create(candidate).await;
+                let branch = blockchain
+                    .branches_mut()
+                    .apply_or_create(Arc::clone(&candidate))
+                    .await;
                 tip.swap(branch).await;
             }
+
+            blockchain.record_rewards(&tip_ref, candidate, logger).await;
+        }
+        ComparisonResult::RejectDeepFork => {
+            info!(
+                logger,
+                "rejecting candidate {} forking deeper than the stability depth | current-tip {}",
+                candidate.header().description(),
+                tip_ref.header().description(),
+            );
         }
     }
 
-    Ok(())
+    Ok(comparison)
+}
+
+/// same as `process_new_ref`, notifying the change of tip if the candidate
+/// became the new tip
+async fn process_new_ref_and_notify(
+    logger: &Logger,
+    blockchain: &mut Blockchain,
+    tip: Tip,
+    candidate: Arc<Ref>,
+    events: &Events,
+) -> Result<ComparisonResult, Error> {
+    let tip_hash = tip.get_ref().await.hash();
+
+    let comparison = process_new_ref(logger, blockchain, tip.clone(), candidate).await?;
+
+    let new_tip = tip.get_ref().await;
+    if new_tip.hash() != tip_hash {
+        events.notify(Event::TipChanged(new_tip.header().clone()));
+    }
+    Ok(comparison)
 }
 
 async fn process_and_propagate_new_ref(
@@ -352,12 +471,23 @@ async fn process_and_propagate_new_ref(
     tip: Tip,
     new_block_ref: Arc<Ref>,
     mut network_msg_box: MessageBox<NetworkMsg>,
+    events: &Events,
+    stats_counter: &StatsCounter,
 ) -> Result<(), Error> {
     let header = new_block_ref.header().clone();
     let hash = header.hash();
     debug!(logger, "processing the new block and propagating");
 
-    process_new_ref(logger, blockchain, tip, new_block_ref).await?;
+    let comparison =
+        process_new_ref_and_notify(logger, blockchain, tip, new_block_ref, events).await?;
+    if comparison == ComparisonResult::RejectDeepFork {
+        warn!(
+            logger,
+            "block forks deeper than the epoch stability depth, it will not be selected";
+            "hash" => %hash,
+        );
+        stats_counter.add_rejected_deep_fork_cnt(1);
+    }
 
     debug!(logger, "propagating block to the network");
     network_msg_box
@@ -374,11 +504,13 @@ async fn process_leadership_block(
     mut tx_msg_box: MessageBox<TransactionMsg>,
     network_msg_box: MessageBox<NetworkMsg>,
     explorer_msg_box: Option<MessageBox<ExplorerMsg>>,
+    events: Events,
     block: Block,
     stats_counter: StatsCounter,
 ) -> Result<(), Error> {
     let new_block_ref =
         process_leadership_block_inner(&logger, &mut blockchain, block.clone()).await?;
+    events.notify(Event::NewBlock(new_block_ref.header().clone()));
 
     let fragments = block.fragments().map(|f| f.id()).collect();
 
@@ -392,6 +524,8 @@ async fn process_leadership_block(
         blockchain_tip,
         Arc::clone(&new_block_ref),
         network_msg_box,
+        &events,
+        &stats_counter,
     )
     .await?;
 
@@ -448,14 +582,27 @@ async fn process_block_announcement(
     blockchain_tip: Tip,
     header: Header,
     node_id: Address,
+    mut network_msg_box: MessageBox<NetworkMsg>,
     mut pull_headers_scheduler: PullHeadersScheduler,
     mut get_next_block_scheduler: GetNextBlockScheduler,
+    announcers: Announcers,
     logger: Logger,
 ) -> Result<(), Error> {
-    let pre_checked = blockchain
-        .pre_check_header(header, false)
-        .await
-        .map_err(|err| Error::with_chain(err, "cannot process block announcement"))?;
+    let pre_checked = match blockchain.pre_check_header(header, false).await {
+        Ok(pre_checked) => pre_checked,
+        Err(err) => {
+            if let ErrorKind::BlockHeaderVerificationFailed(_) = err.kind() {
+                report_peer(
+                    &mut network_msg_box,
+                    node_id,
+                    Behavior::InvalidHeader,
+                    &logger,
+                )
+                .await;
+            }
+            return Err(Error::with_chain(err, "cannot process block announcement"));
+        }
+    };
     match pre_checked {
         PreCheckedHeader::AlreadyPresent { .. } => {
             debug!(logger, "block is already present");
@@ -475,14 +622,31 @@ async fn process_block_announcement(
                 });
             Ok(())
         }
-        PreCheckedHeader::HeaderWithCache {
-            header,
-            parent_ref: _,
-        } => {
+        PreCheckedHeader::HeaderWithCache { header, parent_ref } => {
+            let post_checked = blockchain
+                .post_check_header(header, parent_ref, CheckHeaderProof::Enabled)
+                .await;
+            let header = match post_checked {
+                Ok(post_checked) => post_checked.header().clone(),
+                Err(err) => {
+                    if let ErrorKind::BlockHeaderVerificationFailed(_) = err.kind() {
+                        report_peer(
+                            &mut network_msg_box,
+                            node_id,
+                            Behavior::InvalidHeader,
+                            &logger,
+                        )
+                        .await;
+                    }
+                    return Err(Error::with_chain(err, "cannot process block announcement"));
+                }
+            };
             debug!(
                 logger,
                 "Announced block has a locally stored parent, fetch it"
             );
+            // the announcer is rewarded once the block is applied
+            announcers.record(header.hash(), node_id.clone());
             get_next_block_scheduler
                 .schedule(header.id(), node_id, ())
                 .unwrap_or_else(move |err| {
@@ -496,13 +660,78 @@ async fn process_block_announcement(
     }
 }
 
+/// the first peer to announce each of the blocks being fetched
+#[derive(Clone)]
+struct Announcers(Arc<Mutex<LruCache<HeaderHash, Address>>>);
+
+impl Announcers {
+    fn new() -> Self {
+        Announcers(Arc::new(Mutex::new(LruCache::new(MAX_ANNOUNCERS))))
+    }
+
+    fn record(&self, header_hash: HeaderHash, node_id: Address) {
+        let mut announcers = self.0.lock().unwrap();
+        if !announcers.contains(&header_hash) {
+            announcers.put(header_hash, node_id);
+        }
+    }
+
+    fn take(&self, header_hash: &HeaderHash) -> Option<Address> {
+        self.0.lock().unwrap().pop(header_hash)
+    }
+}
+
+/// reward the first peer to announce an applied block, and reward it
+/// again if the block was received within its slot
+async fn reward_announcer(
+    announcers: &Announcers,
+    block_ref: &Ref,
+    network_msg_box: &mut MessageBox<NetworkMsg>,
+    logger: &Logger,
+) {
+    let node_id = match announcers.take(&block_ref.hash()) {
+        Some(node_id) => node_id,
+        None => return,
+    };
+    let slot_duration = Duration::from_secs(block_ref.time_frame().slot_duration());
+    let within_slot = block_ref
+        .elapsed()
+        .map_or(false, |elapsed| elapsed < slot_duration);
+    report_peer(
+        network_msg_box,
+        node_id.clone(),
+        Behavior::BlockAnnounced(block_ref.hash()),
+        logger,
+    )
+    .await;
+    if within_slot {
+        report_peer(network_msg_box, node_id, Behavior::LowLatency, logger).await;
+    }
+}
+
+async fn report_peer(
+    network_msg_box: &mut MessageBox<NetworkMsg>,
+    node_id: Address,
+    behavior: Behavior,
+    logger: &Logger,
+) {
+    network_msg_box
+        .send(NetworkMsg::ReportPeer(node_id, behavior))
+        .await
+        .unwrap_or_else(
+            |err| error!(logger, "cannot report peer to the network task"; "reason" => %err),
+        );
+}
+
 async fn process_network_blocks(
     mut blockchain: Blockchain,
     blockchain_tip: Tip,
     mut tx_msg_box: MessageBox<TransactionMsg>,
-    network_msg_box: MessageBox<NetworkMsg>,
+    mut network_msg_box: MessageBox<NetworkMsg>,
     mut explorer_msg_box: Option<MessageBox<ExplorerMsg>>,
+    events: Events,
     mut get_next_block_scheduler: GetNextBlockScheduler,
+    announcers: Announcers,
     handle: intercom::RequestStreamHandle<Block, ()>,
     stats_counter: StatsCounter,
     logger: Logger,
@@ -516,11 +745,13 @@ async fn process_network_blocks(
         match maybe_block {
             Some(block) => {
                 latest_block = Some(Arc::new(block.clone()));
+                let block_id = block.id();
                 let res = process_network_block(
                     &mut blockchain,
                     block.clone(),
                     &mut tx_msg_box,
                     explorer_msg_box.as_mut(),
+                    &events,
                     &mut get_next_block_scheduler,
                     &logger,
                 )
@@ -528,6 +759,7 @@ async fn process_network_blocks(
                 match res {
                     Ok(Some(r)) => {
                         stats_counter.add_block_recv_cnt(1);
+                        reward_announcer(&announcers, &r, &mut network_msg_box, &logger).await;
                         stream = stream_tail;
                         candidate = Some(r);
                     }
@@ -541,6 +773,17 @@ async fn process_network_blocks(
                             "validation of an incoming block failed";
                             "reason" => ?e,
                         );
+                        if let ErrorKind::BlockHeaderVerificationFailed(_) = e.kind() {
+                            if let Some(node_id) = announcers.take(&block_id) {
+                                report_peer(
+                                    &mut network_msg_box,
+                                    node_id,
+                                    Behavior::InvalidHeader,
+                                    &logger,
+                                )
+                                .await;
+                            }
+                        }
                         reply.reply_error(network_block_error_into_reply(e));
                         break candidate;
                     }
@@ -561,6 +804,8 @@ async fn process_network_blocks(
                 blockchain_tip,
                 Arc::clone(&new_block_ref),
                 network_msg_box,
+                &events,
+                &stats_counter,
             )
             .await?;
 
@@ -582,6 +827,7 @@ async fn process_network_block(
     block: Block,
     tx_msg_box: &mut MessageBox<TransactionMsg>,
     explorer_msg_box: Option<&mut MessageBox<ExplorerMsg>>,
+    events: &Events,
     get_next_block_scheduler: &mut GetNextBlockScheduler,
     logger: &Logger,
 ) -> Result<Option<Arc<Ref>>, chain::Error> {
@@ -621,6 +867,7 @@ async fn process_network_block(
                 block,
                 tx_msg_box,
                 explorer_msg_box,
+                events,
                 logger,
             )
             .await;
@@ -635,6 +882,7 @@ async fn check_and_apply_block(
     block: Block,
     tx_msg_box: &mut MessageBox<TransactionMsg>,
     explorer_msg_box: Option<&mut MessageBox<ExplorerMsg>>,
+    events: &Events,
     logger: &Logger,
 ) -> Result<Option<Arc<Ref>>, chain::Error> {
     let explorer_enabled = explorer_msg_box.is_some();
@@ -671,6 +919,7 @@ async fn check_and_apply_block(
         try_request_fragment_removal(tx_msg_box, fragment_ids, header).unwrap_or_else(
             |err| error!(logger, "cannot remove fragments from pool" ; "reason" => %err),
         );
+        events.notify(Event::NewBlock(header.clone()));
         if let Some(msg_box) = explorer_msg_box {
             msg_box
                 .try_send(ExplorerMsg::NewBlock(block_for_explorer.take().unwrap()))
@@ -53,4 +53,15 @@ impl RefCache {
         let mut guard = self.inner.lock().await;
         guard.get(&key).map(Arc::clone)
     }
+
+    /// remove a [`Ref`] from the cache, returning it if it was present
+    ///
+    /// # Errors
+    ///
+    /// No error possible yet
+    ///
+    pub async fn remove(&self, key: &HeaderHash) -> Option<Arc<Ref>> {
+        let mut guard = self.inner.lock().await;
+        guard.pop(key)
+    }
 }
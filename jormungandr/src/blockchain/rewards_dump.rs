@@ -0,0 +1,491 @@
+//! dumps of the rewards distributed at the beginning of every epoch.
+//!
+//! A file is written in the dump directory for every distribution. It is named
+//! after the epoch and the parent of the first block of the epoch, as the same
+//! epoch may start on more than one branch. Every entry of the file is one line
+//! of the distribution report:
+//!
+//! * `drawn`: the rewards drawn from the reward pot, as `distributed`;
+//! * `fees`: the fees collected during the previous epoch, as `distributed`;
+//! * `treasury`: the part of the rewards sent to the treasury, as `received`;
+//! * `pool`: for every stake pool, the rewards taken by the pool as tax,
+//!   as `received`, and the rewards left to its delegators, as `distributed`;
+//! * `account`: for every account, the rewards it has `received`.
+//!
+//! The entries are written either as CSV, after a header line, or as JSON
+//! objects, one per line, in a file with the `.jsonl` extension.
+
+use crate::blockcfg::{Epoch, EpochRewardsInfo as EpochRewardsInfoStd, HeaderHash};
+use jormungandr_lib::{
+    crypto::{account::Identifier, hash::Hash},
+    interfaces::{EpochRewardsInfo, Value},
+};
+use serde::{Deserialize, Serialize};
+use slog::Logger;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+use thiserror::Error;
+
+const DUMP_FILE_PREFIX: &str = "reward-info-";
+const DUMP_TMP_FILE_PREFIX: &str = "tmp.reward-info-";
+const JSON_LINES_EXTENSION: &str = ".jsonl";
+const CSV_HEADER: &str = "type,identifier,received,distributed";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot access the rewards dump")]
+    Io(#[from] io::Error),
+    #[error("cannot encode or decode the rewards dump")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid entry on line {0} of the rewards dump")]
+    InvalidEntry(usize),
+}
+
+/// the format of the rewards dump files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EntryType {
+    Drawn,
+    Fees,
+    Treasury,
+    Pool,
+    Account,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    #[serde(rename = "type")]
+    entry_type: EntryType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    identifier: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    received: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    distributed: Option<u64>,
+}
+
+/// the directory where the rewards distributions are dumped
+#[derive(Clone)]
+pub struct RewardsDump {
+    dir: PathBuf,
+    format: Format,
+    logger: Logger,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Csv
+    }
+}
+
+impl Format {
+    fn file_name(self, epoch: Epoch, parent: &HeaderHash) -> String {
+        match self {
+            Format::Csv => format!("{}{}-{}", DUMP_FILE_PREFIX, epoch, parent),
+            Format::JsonLines => format!(
+                "{}{}-{}{}",
+                DUMP_FILE_PREFIX, epoch, parent, JSON_LINES_EXTENSION
+            ),
+        }
+    }
+}
+
+impl EntryType {
+    fn as_str(self) -> &'static str {
+        match self {
+            EntryType::Drawn => "drawn",
+            EntryType::Fees => "fees",
+            EntryType::Treasury => "treasury",
+            EntryType::Pool => "pool",
+            EntryType::Account => "account",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "drawn" => Some(EntryType::Drawn),
+            "fees" => Some(EntryType::Fees),
+            "treasury" => Some(EntryType::Treasury),
+            "pool" => Some(EntryType::Pool),
+            "account" => Some(EntryType::Account),
+            _ => None,
+        }
+    }
+}
+
+impl Entry {
+    fn new(
+        entry_type: EntryType,
+        identifier: Option<String>,
+        received: Option<Value>,
+        distributed: Option<Value>,
+    ) -> Self {
+        Entry {
+            entry_type,
+            identifier,
+            received: received.map(Into::into),
+            distributed: distributed.map(Into::into),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        fn field<T: ToString>(value: &Option<T>) -> String {
+            value.as_ref().map(ToString::to_string).unwrap_or_default()
+        }
+
+        format!(
+            "{},{},{},{}",
+            self.entry_type.as_str(),
+            field(&self.identifier),
+            field(&self.received),
+            field(&self.distributed),
+        )
+    }
+
+    fn from_csv(line: &str) -> Option<Self> {
+        fn field<T: std::str::FromStr>(value: &str) -> Option<Option<T>> {
+            if value.is_empty() {
+                Some(None)
+            } else {
+                value.parse().ok().map(Some)
+            }
+        }
+
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() != 4 {
+            return None;
+        }
+        Some(Entry {
+            entry_type: EntryType::parse(fields[0])?,
+            identifier: field(fields[1])?,
+            received: field(fields[2])?,
+            distributed: field(fields[3])?,
+        })
+    }
+}
+
+fn to_entries(info: &EpochRewardsInfo) -> Vec<Entry> {
+    let mut entries = vec![
+        Entry::new(EntryType::Drawn, None, None, Some(info.drawn())),
+        Entry::new(EntryType::Fees, None, None, Some(info.fees())),
+        Entry::new(EntryType::Treasury, None, Some(info.treasury()), None),
+    ];
+    entries.extend(info.stake_pools().iter().map(|(pool_id, (taxed, distr))| {
+        Entry::new(
+            EntryType::Pool,
+            Some(pool_id.to_string()),
+            Some(*taxed),
+            Some(*distr),
+        )
+    }));
+    entries.extend(info.accounts().iter().map(|(account_id, received)| {
+        Entry::new(
+            EntryType::Account,
+            Some(account_id.to_hex()),
+            Some(*received),
+            None,
+        )
+    }));
+    entries
+}
+
+fn from_entries(
+    epoch: Epoch,
+    entries: impl IntoIterator<Item = Result<Entry, Error>>,
+) -> Result<EpochRewardsInfo, Error> {
+    let zero = Value::from(0);
+    let (mut drawn, mut fees, mut treasury) = (zero, zero, zero);
+    let mut stake_pools = BTreeMap::new();
+    let mut accounts = BTreeMap::new();
+
+    // the entries are numbered from the line after the CSV header, which is
+    // also the first line of a JSON lines file
+    for (index, entry) in entries.into_iter().enumerate() {
+        let entry = entry?;
+        let invalid = || Error::InvalidEntry(index + 1);
+        let value = |value: Option<u64>| value.map(Value::from).ok_or_else(invalid);
+
+        match entry.entry_type {
+            EntryType::Drawn => drawn = value(entry.distributed)?,
+            EntryType::Fees => fees = value(entry.distributed)?,
+            EntryType::Treasury => treasury = value(entry.received)?,
+            EntryType::Pool => {
+                let pool_id = entry
+                    .identifier
+                    .and_then(|id| id.parse::<Hash>().ok())
+                    .ok_or_else(invalid)?;
+                let taxed = value(entry.received)?;
+                let distr = value(entry.distributed)?;
+                stake_pools.insert(pool_id, (taxed, distr));
+            }
+            EntryType::Account => {
+                let account_id = entry
+                    .identifier
+                    .and_then(|id| Identifier::from_hex(&id).ok())
+                    .ok_or_else(invalid)?;
+                accounts.insert(account_id, value(entry.received)?);
+            }
+        }
+    }
+
+    Ok(EpochRewardsInfo::new(
+        epoch,
+        drawn,
+        fees,
+        treasury,
+        stake_pools,
+        accounts,
+    ))
+}
+
+impl RewardsDump {
+    pub fn new(dir: PathBuf, format: Format, logger: Logger) -> Self {
+        RewardsDump {
+            dir,
+            format,
+            logger,
+        }
+    }
+
+    pub fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    /// write the rewards distributed at the beginning of `epoch`, on top of
+    /// the `parent` block. An existing dump of the same distribution is
+    /// replaced.
+    pub fn write(
+        &self,
+        epoch: Epoch,
+        parent: &HeaderHash,
+        rewards_info: &EpochRewardsInfoStd,
+    ) -> Result<(), Error> {
+        self.write_info(parent, &EpochRewardsInfo::from(epoch, rewards_info))
+    }
+
+    fn write_info(&self, parent: &HeaderHash, info: &EpochRewardsInfo) -> Result<(), Error> {
+        let epoch = info.epoch();
+        fs::create_dir_all(&self.dir)?;
+        let file_name = self.format.file_name(epoch, parent);
+        let path = self.dir.join(&file_name);
+        let tmp_path = self
+            .dir
+            .join(format!("{}{}", DUMP_TMP_FILE_PREFIX, &file_name));
+
+        let entries = to_entries(info);
+        {
+            let mut file = BufWriter::new(File::create(&tmp_path)?);
+            match self.format {
+                Format::Csv => {
+                    write!(&mut file, "{}\r\n", CSV_HEADER)?;
+                    for entry in entries {
+                        write!(&mut file, "{}\r\n", entry.to_csv())?;
+                    }
+                }
+                Format::JsonLines => {
+                    for entry in entries {
+                        serde_json::to_writer(&mut file, &entry)?;
+                        writeln!(&mut file)?;
+                    }
+                }
+            }
+            file.flush()?;
+        }
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// list the parents of the blocks starting `epoch` for which a rewards
+    /// distribution has been dumped, in any format
+    pub fn list(&self, epoch: Epoch) -> Result<Vec<HeaderHash>, Error> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let prefix = format!("{}{}-", DUMP_FILE_PREFIX, epoch);
+        let mut parents = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let file_name = entry?.file_name();
+            let parsed = file_name
+                .to_str()
+                .filter(|name| name.starts_with(&prefix))
+                .map(|name| &name[prefix.len()..])
+                .map(|name| name.trim_end_matches(JSON_LINES_EXTENSION))
+                .and_then(|name| name.parse::<Hash>().ok());
+            if let Some(parent) = parsed {
+                let parent = parent.into_hash();
+                if !parents.contains(&parent) {
+                    parents.push(parent);
+                }
+            }
+        }
+        Ok(parents)
+    }
+
+    /// read the rewards distributed at the beginning of `epoch` on top of the
+    /// `parent` block, returns `None` if they were not dumped.
+    pub fn read(
+        &self,
+        epoch: Epoch,
+        parent: &HeaderHash,
+    ) -> Result<Option<EpochRewardsInfo>, Error> {
+        for format in &[self.format, Format::Csv, Format::JsonLines] {
+            let file = match File::open(self.dir.join(format.file_name(epoch, parent))) {
+                Ok(file) => file,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let lines = BufReader::new(file).lines();
+            let info = match format {
+                Format::Csv => from_entries(
+                    epoch,
+                    lines.skip(1).enumerate().map(|(index, line)| {
+                        Entry::from_csv(&line?).ok_or(Error::InvalidEntry(index + 1))
+                    }),
+                ),
+                Format::JsonLines => {
+                    from_entries(epoch, lines.map(|line| Ok(serde_json::from_str(&line?)?)))
+                }
+            }?;
+            return Ok(Some(info));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use std::str::FromStr;
+
+    fn rewards_dump(dir: &TestDir, format: Format) -> RewardsDump {
+        let logger = Logger::root(slog::Discard, slog::o!());
+        RewardsDump::new(dir.path().to_owned(), format, logger)
+    }
+
+    fn parent(n: u8) -> HeaderHash {
+        HeaderHash::hash_bytes(&[n])
+    }
+
+    fn rewards_info(epoch: Epoch) -> EpochRewardsInfo {
+        let mut stake_pools = BTreeMap::new();
+        stake_pools.insert(
+            Hash::from_str(&"11".repeat(32)).unwrap(),
+            (Value::from(10), Value::from(90)),
+        );
+        let mut accounts = BTreeMap::new();
+        accounts.insert(
+            Identifier::from_hex(&"22".repeat(32)).unwrap(),
+            Value::from(45),
+        );
+        accounts.insert(
+            Identifier::from_hex(&"33".repeat(32)).unwrap(),
+            Value::from(45),
+        );
+        EpochRewardsInfo::new(
+            epoch,
+            Value::from(100),
+            Value::from(20),
+            Value::from(20),
+            stake_pools,
+            accounts,
+        )
+    }
+
+    fn round_trip(format: Format) {
+        let dir = TestDir::new(&format!("rewards-dump-{:?}", format));
+        let dump = rewards_dump(&dir, format);
+        let info = rewards_info(3);
+        dump.write_info(&parent(1), &info).unwrap();
+
+        assert_eq!(dump.read(3, &parent(1)).unwrap(), Some(info));
+        assert_eq!(dump.read(3, &parent(2)).unwrap(), None);
+        assert_eq!(dump.read(4, &parent(1)).unwrap(), None);
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        round_trip(Format::Csv)
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        round_trip(Format::JsonLines)
+    }
+
+    #[test]
+    fn read_falls_back_to_the_other_format() {
+        let dir = TestDir::new("rewards-dump-fallback");
+        let info = rewards_info(3);
+        rewards_dump(&dir, Format::JsonLines)
+            .write_info(&parent(1), &info)
+            .unwrap();
+
+        let dump = rewards_dump(&dir, Format::Csv);
+        assert_eq!(dump.read(3, &parent(1)).unwrap(), Some(info));
+    }
+
+    #[test]
+    fn list_missing_directory() {
+        let dir = TestDir::new("rewards-dump-missing");
+        assert!(rewards_dump(&dir, Format::Csv).list(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn list_parses_the_file_names() {
+        let dir = TestDir::new("rewards-dump-list");
+        let csv = rewards_dump(&dir, Format::Csv);
+        let json_lines = rewards_dump(&dir, Format::JsonLines);
+        csv.write_info(&parent(1), &rewards_info(3)).unwrap();
+        json_lines.write_info(&parent(1), &rewards_info(3)).unwrap();
+        json_lines.write_info(&parent(2), &rewards_info(3)).unwrap();
+        csv.write_info(&parent(3), &rewards_info(4)).unwrap();
+        csv.write_info(&parent(4), &rewards_info(13)).unwrap();
+        fs::write(
+            dir.path().join(format!("{}3-not-a-hash", DUMP_FILE_PREFIX)),
+            "",
+        )
+        .unwrap();
+        fs::write(
+            dir.path()
+                .join(format!("{}3-{}", DUMP_TMP_FILE_PREFIX, parent(5))),
+            "",
+        )
+        .unwrap();
+
+        let mut parents = csv.list(3).unwrap();
+        parents.sort();
+        assert_eq!(parents, vec![parent(1), parent(2)]);
+        assert_eq!(csv.list(1).unwrap(), Vec::<HeaderHash>::new());
+        assert_eq!(csv.list(4).unwrap(), vec![parent(3)]);
+    }
+
+    #[test]
+    fn read_rejects_invalid_entries() {
+        let dir = TestDir::new("rewards-dump-invalid");
+        let dump = rewards_dump(&dir, Format::Csv);
+        fs::create_dir_all(dir.path()).unwrap();
+        fs::write(
+            dir.path().join(Format::Csv.file_name(3, &parent(1))),
+            format!("{}\r\ndrawn,,,100\r\npool,not-a-hash,10,90\r\n", CSV_HEADER),
+        )
+        .unwrap();
+
+        match dump.read(3, &parent(1)) {
+            Err(Error::InvalidEntry(2)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
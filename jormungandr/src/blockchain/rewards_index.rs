@@ -0,0 +1,247 @@
+//! index of the rewards received by every account, epoch after epoch.
+//!
+//! The index keeps one file per account, named after the account's public key,
+//! with one line per rewards distribution the account received rewards from:
+//! the epoch, the parent of the first block of the epoch and the value
+//! received. As the same epoch may start on more than one branch, the readers
+//! select the distributions of the branch they are interested in from the
+//! parent block.
+//!
+//! A marker file is written once all the accounts of a distribution have been
+//! indexed, so the same distribution is not indexed twice.
+
+use crate::blockcfg::{Epoch, EpochRewardsInfo, HeaderHash};
+use jormungandr_lib::{
+    crypto::{account::Identifier, hash::Hash},
+    interfaces::Value,
+};
+use slog::Logger;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+};
+use thiserror::Error;
+
+const ACCOUNT_FILE_PREFIX: &str = "account-";
+const DISTRIBUTION_FILE_PREFIX: &str = "distribution-";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot access the rewards index")]
+    Io(#[from] io::Error),
+    #[error("invalid entry on line {0} of the rewards index")]
+    InvalidEntry(usize),
+}
+
+/// the rewards received by an account from one distribution
+#[derive(Debug, Clone)]
+pub struct AccountReward {
+    pub epoch: Epoch,
+    /// the parent of the first block of the epoch
+    pub parent: HeaderHash,
+    pub received: Value,
+}
+
+/// the directory of the index of the rewards received by the accounts
+#[derive(Clone)]
+pub struct RewardsIndex {
+    dir: PathBuf,
+    logger: Logger,
+}
+
+impl AccountReward {
+    fn to_line(&self) -> String {
+        format!("{},{},{}\n", self.epoch, self.parent, self.received)
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split(',');
+        let epoch = fields.next()?.parse().ok()?;
+        let parent = fields.next()?.parse::<Hash>().ok()?.into_hash();
+        let received = fields.next()?.parse().ok()?;
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(AccountReward {
+            epoch,
+            parent,
+            received,
+        })
+    }
+}
+
+impl RewardsIndex {
+    pub fn new(dir: PathBuf, logger: Logger) -> Self {
+        RewardsIndex { dir, logger }
+    }
+
+    pub fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    fn account_path(&self, account: &Identifier) -> PathBuf {
+        self.dir
+            .join(format!("{}{}", ACCOUNT_FILE_PREFIX, account.to_hex()))
+    }
+
+    fn distribution_path(&self, epoch: Epoch, parent: &HeaderHash) -> PathBuf {
+        self.dir
+            .join(format!("{}{}-{}", DISTRIBUTION_FILE_PREFIX, epoch, parent))
+    }
+
+    /// index the rewards received by the accounts at the beginning of
+    /// `epoch`, on top of the `parent` block. Returns `false` if the
+    /// distribution was already indexed.
+    pub fn write(
+        &self,
+        epoch: Epoch,
+        parent: &HeaderHash,
+        rewards_info: &EpochRewardsInfo,
+    ) -> Result<bool, Error> {
+        let distribution_path = self.distribution_path(epoch, parent);
+        if distribution_path.exists() {
+            return Ok(false);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        for (account, received) in rewards_info.accounts.iter() {
+            let account = Identifier::from(account.clone());
+            let reward = AccountReward {
+                epoch,
+                parent: *parent,
+                received: (*received).into(),
+            };
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.account_path(&account))?
+                .write_all(reward.to_line().as_bytes())?;
+        }
+        File::create(distribution_path)?;
+        Ok(true)
+    }
+
+    /// the rewards received by the account from every indexed distribution,
+    /// in the order they were indexed. A distribution interrupted before its
+    /// marker was written may be listed twice.
+    pub fn read(&self, account: &Identifier) -> Result<Vec<AccountReward>, Error> {
+        let file = match File::open(self.account_path(account)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        BufReader::new(file)
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                AccountReward::from_line(&line?).ok_or(Error::InvalidEntry(index + 1))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+    use chain_impl_mockchain::value::Value as ChainValue;
+
+    fn rewards_index(dir: &TestDir) -> RewardsIndex {
+        let logger = Logger::root(slog::Discard, slog::o!());
+        RewardsIndex::new(dir.path().to_owned(), logger)
+    }
+
+    fn account(n: u8) -> Identifier {
+        Identifier::from_hex(&format!("{:02x}", n).repeat(32)).unwrap()
+    }
+
+    fn parent(n: u8) -> HeaderHash {
+        HeaderHash::hash_bytes(&[n])
+    }
+
+    fn rewards_info(accounts: &[(u8, u64)]) -> EpochRewardsInfo {
+        let mut info = EpochRewardsInfo::new();
+        for (n, received) in accounts {
+            info.accounts
+                .insert(account(*n).to_inner(), ChainValue(*received));
+        }
+        info
+    }
+
+    fn received(rewards: Vec<AccountReward>) -> Vec<(Epoch, HeaderHash, u64)> {
+        rewards
+            .into_iter()
+            .map(|reward| (reward.epoch, reward.parent, reward.received.into()))
+            .collect()
+    }
+
+    #[test]
+    fn rewards_are_indexed_per_account() {
+        let dir = TestDir::new("rewards-index-accounts");
+        let index = rewards_index(&dir);
+        assert!(index
+            .write(1, &parent(1), &rewards_info(&[(1, 10), (2, 20)]))
+            .unwrap());
+        assert!(index
+            .write(2, &parent(2), &rewards_info(&[(1, 5)]))
+            .unwrap());
+
+        assert_eq!(
+            received(index.read(&account(1)).unwrap()),
+            vec![(1, parent(1), 10), (2, parent(2), 5)]
+        );
+        assert_eq!(
+            received(index.read(&account(2)).unwrap()),
+            vec![(1, parent(1), 20)]
+        );
+        assert!(index.read(&account(3)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn distribution_is_indexed_once() {
+        let dir = TestDir::new("rewards-index-once");
+        let index = rewards_index(&dir);
+        let info = rewards_info(&[(1, 10)]);
+        assert!(index.write(1, &parent(1), &info).unwrap());
+        assert!(!index.write(1, &parent(1), &info).unwrap());
+
+        // the same epoch starting on another branch is another distribution
+        assert!(index.write(1, &parent(2), &info).unwrap());
+        assert_eq!(
+            received(index.read(&account(1)).unwrap()),
+            vec![(1, parent(1), 10), (1, parent(2), 10)]
+        );
+    }
+
+    #[test]
+    fn read_without_index() {
+        let dir = TestDir::new("rewards-index-missing");
+        let index = RewardsIndex::new(
+            dir.path().join("missing"),
+            Logger::root(slog::Discard, slog::o!()),
+        );
+        assert!(index.read(&account(1)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_entry_is_reported() {
+        let dir = TestDir::new("rewards-index-invalid");
+        let index = rewards_index(&dir);
+        index
+            .write(1, &parent(1), &rewards_info(&[(1, 10)]))
+            .unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(index.account_path(&account(1)))
+            .unwrap()
+            .write_all(b"1,not-a-hash,10\n")
+            .unwrap();
+
+        match index.read(&account(1)) {
+            Err(Error::InvalidEntry(2)) => (),
+            other => panic!("unexpected read result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! snapshots of the ledger state taken at epoch boundaries, so the node does
+//! not have to apply every block from the block0 when it restarts.
+//!
+//! A snapshot is taken on the first block of every epoch. It contains:
+//!
+//! * the ledger state after this block;
+//! * the ledger state the epoch's `Leadership` was built from, the
+//!   `Leadership` and the `LedgerParameters` of the epoch are rebuilt from it;
+//! * the ledger state at the end of the previous epoch, needed to compute the
+//!   leadership of the next epoch.
+//!
+//! The `TimeFrame` is the one of the block0, it does not change during the
+//! lifetime of the blockchain and is rebuilt from the block0. The rewards
+//! distributed at the beginning of the epoch are not part of the snapshot.
+
+use crate::blockcfg::{ChainLength, Epoch, Header, HeaderHash, Ledger};
+use chain_core::property::{Deserialize as _, Serialize as _};
+use jormungandr_lib::crypto::hash::Hash;
+use serde::{Deserialize, Serialize};
+use slog::Logger;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use thiserror::Error;
+
+const SNAPSHOT_VERSION: u32 = 1;
+const SNAPSHOT_FILE_PREFIX: &str = "ledger-snapshot-";
+const SNAPSHOT_TMP_FILE_PREFIX: &str = "tmp.ledger-snapshot-";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot access the ledger snapshot")]
+    Io(#[from] io::Error),
+    #[error("cannot encode or decode the ledger snapshot")]
+    Encoding(#[from] bincode::Error),
+    #[error("unsupported ledger snapshot version {0}")]
+    UnsupportedVersion(u32),
+    #[error("the ledger snapshot was taken on a blockchain with a different block0")]
+    Block0Mismatch,
+    #[error("the ledger snapshot is not the one of block {0}")]
+    BlockMismatch(HeaderHash),
+}
+
+/// the ledger states needed to rebuild the `Ref` of the first block of an
+/// epoch.
+#[derive(Clone)]
+pub struct Snapshot {
+    pub block: HeaderHash,
+    pub chain_length: ChainLength,
+    pub epoch: Epoch,
+    pub ledger: Arc<Ledger>,
+    pub epoch_state: Arc<Ledger>,
+    pub previous_epoch_ledger: Arc<Ledger>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+    version: u32,
+    block0: Hash,
+    block: Hash,
+    chain_length: u32,
+    epoch: Epoch,
+    ledger: Vec<u8>,
+    epoch_state: Vec<u8>,
+    previous_epoch_ledger: Vec<u8>,
+}
+
+/// the directory where the snapshots of the ledger are kept.
+#[derive(Clone)]
+pub struct Snapshots {
+    dir: PathBuf,
+    keep: usize,
+    logger: Logger,
+}
+
+impl Snapshot {
+    pub fn new(
+        header: &Header,
+        ledger: Arc<Ledger>,
+        epoch_state: Arc<Ledger>,
+        previous_epoch_ledger: Arc<Ledger>,
+    ) -> Self {
+        Snapshot {
+            block: header.hash(),
+            chain_length: header.chain_length(),
+            epoch: header.block_date().epoch,
+            ledger,
+            epoch_state,
+            previous_epoch_ledger,
+        }
+    }
+
+    /// decode a snapshot, as written by `Snapshots::write`, taken on the
+    /// blockchain of the given block0
+    pub fn decode<R: Read>(block0: &HeaderHash, reader: R) -> Result<Self, Error> {
+        Self::from_file(block0, None, bincode::deserialize_from(reader)?)
+    }
+
+    /// decode the snapshot of the given block, see `decode`. The block is
+    /// checked before the ledger states are decoded.
+    pub fn decode_of<R: Read>(
+        block0: &HeaderHash,
+        block: &HeaderHash,
+        reader: R,
+    ) -> Result<Self, Error> {
+        Self::from_file(block0, Some(block), bincode::deserialize_from(reader)?)
+    }
+
+    fn from_file(
+        block0: &HeaderHash,
+        block: Option<&HeaderHash>,
+        content: SnapshotFile,
+    ) -> Result<Self, Error> {
+        if content.version != SNAPSHOT_VERSION {
+            return Err(Error::UnsupportedVersion(content.version));
+        }
+        if content.block0.into_hash() != *block0 {
+            return Err(Error::Block0Mismatch);
+        }
+        match block {
+            Some(block) if content.block.into_hash() != *block => {
+                return Err(Error::BlockMismatch(*block))
+            }
+            _ => {}
+        }
+
+        Ok(Snapshot {
+            block: content.block.into_hash(),
+            chain_length: ChainLength::from(content.chain_length),
+            epoch: content.epoch,
+            ledger: Arc::new(Ledger::deserialize(content.ledger.as_slice())?),
+            epoch_state: Arc::new(Ledger::deserialize(content.epoch_state.as_slice())?),
+            previous_epoch_ledger: Arc::new(Ledger::deserialize(
+                content.previous_epoch_ledger.as_slice(),
+            )?),
+        })
+    }
+}
+
+impl Snapshots {
+    /// keep at most `keep` snapshots in the given directory
+    pub fn new(dir: PathBuf, keep: usize, logger: Logger) -> Self {
+        Snapshots { dir, keep, logger }
+    }
+
+    pub fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    fn file_name(chain_length: ChainLength, block: &HeaderHash) -> String {
+        format!("{}{}-{}", SNAPSHOT_FILE_PREFIX, chain_length, block)
+    }
+
+    /// list the snapshots available in the directory, the most recent first
+    pub fn list(&self) -> Result<Vec<(ChainLength, HeaderHash)>, Error> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let file_name = entry?.file_name();
+            let parsed = file_name
+                .to_str()
+                .filter(|name| name.starts_with(SNAPSHOT_FILE_PREFIX))
+                .map(|name| &name[SNAPSHOT_FILE_PREFIX.len()..])
+                .and_then(|name| {
+                    let mut parts = name.splitn(2, '-');
+                    let chain_length = parts.next()?.parse::<u32>().ok()?;
+                    let block = parts.next()?.parse::<Hash>().ok()?;
+                    Some((ChainLength::from(chain_length), block.into_hash()))
+                });
+            if let Some(snapshot) = parsed {
+                snapshots.push(snapshot);
+            }
+        }
+        snapshots.sort_by(|a, b| b.0.cmp(&a.0));
+        Ok(snapshots)
+    }
+
+    /// write the snapshot in the directory, then remove the oldest snapshots.
+    ///
+    /// Nothing is written if the snapshot already exists or if there are
+    /// already enough snapshots of more recent blocks. Returns `true` if the
+    /// snapshot was written.
+    pub fn write(&self, block0: &HeaderHash, snapshot: &Snapshot) -> Result<bool, Error> {
+        let existing = self.list()?;
+        let more_recent = existing
+            .iter()
+            .filter(|(chain_length, _)| *chain_length >= snapshot.chain_length)
+            .count();
+        if existing.iter().any(|(_, block)| *block == snapshot.block) || more_recent >= self.keep {
+            return Ok(false);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let file_name = Self::file_name(snapshot.chain_length, &snapshot.block);
+        let path = self.dir.join(&file_name);
+        let tmp_path = self
+            .dir
+            .join(format!("{}{}", SNAPSHOT_TMP_FILE_PREFIX, &file_name));
+
+        let content = SnapshotFile {
+            version: SNAPSHOT_VERSION,
+            block0: (*block0).into(),
+            block: snapshot.block.into(),
+            chain_length: snapshot.chain_length.into(),
+            epoch: snapshot.epoch,
+            ledger: snapshot.ledger.serialize_as_vec()?,
+            epoch_state: snapshot.epoch_state.serialize_as_vec()?,
+            previous_epoch_ledger: snapshot.previous_epoch_ledger.serialize_as_vec()?,
+        };
+        {
+            let mut file = BufWriter::new(File::create(&tmp_path)?);
+            bincode::serialize_into(&mut file, &content)?;
+            file.flush()?;
+            file.get_ref().sync_all()?;
+        }
+        fs::rename(&tmp_path, &path)?;
+
+        self.prune()?;
+        Ok(true)
+    }
+
+    /// read the snapshot of the given block
+    pub fn read(
+        &self,
+        block0: &HeaderHash,
+        chain_length: ChainLength,
+        block: &HeaderHash,
+    ) -> Result<Snapshot, Error> {
+        let path = self.dir.join(Self::file_name(chain_length, block));
+        let snapshot = Snapshot::decode(block0, BufReader::new(File::open(path)?))?;
+
+        if snapshot.block != *block || snapshot.chain_length != chain_length {
+            return Err(Error::BlockMismatch(*block));
+        }
+        Ok(snapshot)
+    }
+
+    /// read the content of the snapshot of the given block, as it is stored,
+    /// returns `None` if there is no snapshot of this block.
+    pub fn read_raw(&self, block: &HeaderHash) -> Result<Option<Vec<u8>>, Error> {
+        let found = self.list()?.into_iter().find(|(_, id)| id == block);
+        match found {
+            Some((chain_length, block)) => {
+                let path = self.dir.join(Self::file_name(chain_length, &block));
+                Ok(Some(fs::read(path)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// remove the snapshots beyond the `keep` most recent ones
+    fn prune(&self) -> Result<(), Error> {
+        for (chain_length, block) in self.list()?.into_iter().skip(self.keep) {
+            match fs::remove_file(self.dir.join(Self::file_name(chain_length, &block))) {
+                Ok(()) => {}
+                // the snapshot may be removed concurrently by another write
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+
+    fn block(n: u8) -> HeaderHash {
+        HeaderHash::hash_bytes(&[n])
+    }
+
+    fn snapshots(dir: &TestDir, keep: usize) -> Snapshots {
+        let logger = Logger::root(slog::Discard, o!());
+        Snapshots::new(dir.path().to_owned(), keep, logger)
+    }
+
+    /// create an empty snapshot file, only its name matters to `list`
+    fn touch(snapshots: &Snapshots, chain_length: u32, block: &HeaderHash) {
+        fs::create_dir_all(&snapshots.dir).unwrap();
+        let file_name = Snapshots::file_name(ChainLength::from(chain_length), block);
+        File::create(snapshots.dir.join(file_name)).unwrap();
+    }
+
+    fn encode(version: u32, block0: &HeaderHash) -> Vec<u8> {
+        bincode::serialize(&SnapshotFile {
+            version,
+            block0: (*block0).into(),
+            block: block(1).into(),
+            chain_length: 1,
+            epoch: 0,
+            ledger: Vec::new(),
+            epoch_state: Vec::new(),
+            previous_epoch_ledger: Vec::new(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn list_is_empty_without_directory() {
+        let dir = TestDir::new("snapshots-missing");
+        assert!(snapshots(&dir, 2).list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn list_most_recent_first_and_skip_other_files() {
+        let dir = TestDir::new("snapshots-list");
+        let snapshots = snapshots(&dir, 2);
+        touch(&snapshots, 10, &block(10));
+        touch(&snapshots, 30, &block(30));
+        touch(&snapshots, 20, &block(20));
+        File::create(snapshots.dir.join("blocks.sqlite")).unwrap();
+        File::create(
+            snapshots
+                .dir
+                .join(format!("{}30-{}", SNAPSHOT_TMP_FILE_PREFIX, block(30))),
+        )
+        .unwrap();
+        File::create(
+            snapshots
+                .dir
+                .join(format!("{}garbage", SNAPSHOT_FILE_PREFIX)),
+        )
+        .unwrap();
+
+        let listed = snapshots.list().unwrap();
+        assert_eq!(
+            listed,
+            vec![
+                (ChainLength::from(30), block(30)),
+                (ChainLength::from(20), block(20)),
+                (ChainLength::from(10), block(10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn prune_keeps_the_most_recent_snapshots() {
+        let dir = TestDir::new("snapshots-prune");
+        let snapshots = snapshots(&dir, 2);
+        for n in 1..=4 {
+            touch(&snapshots, n as u32, &block(n));
+        }
+        snapshots.prune().unwrap();
+
+        let listed = snapshots.list().unwrap();
+        assert_eq!(
+            listed,
+            vec![
+                (ChainLength::from(4), block(4)),
+                (ChainLength::from(3), block(3)),
+            ]
+        );
+        assert_eq!(snapshots.read_raw(&block(1)).unwrap(), None);
+        assert_eq!(snapshots.read_raw(&block(4)).unwrap(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn decode_rejects_other_versions() {
+        let block0 = block(0);
+        let encoded = encode(SNAPSHOT_VERSION + 1, &block0);
+        match Snapshot::decode(&block0, encoded.as_slice()) {
+            Err(Error::UnsupportedVersion(version)) => assert_eq!(version, SNAPSHOT_VERSION + 1),
+            _ => panic!("a snapshot of another version should be rejected"),
+        }
+    }
+
+    #[test]
+    fn decode_rejects_other_blockchains() {
+        let encoded = encode(SNAPSHOT_VERSION, &block(0));
+        match Snapshot::decode(&block(99), encoded.as_slice()) {
+            Err(Error::Block0Mismatch) => {}
+            _ => panic!("a snapshot of another blockchain should be rejected"),
+        }
+    }
+
+    #[test]
+    fn decode_of_rejects_other_blocks() {
+        let block0 = block(0);
+        let encoded = encode(SNAPSHOT_VERSION, &block0);
+        match Snapshot::decode_of(&block0, &block(2), encoded.as_slice()) {
+            Err(Error::BlockMismatch(expected)) => assert_eq!(expected, block(2)),
+            _ => panic!("the snapshot of another block should be rejected"),
+        }
+    }
+}
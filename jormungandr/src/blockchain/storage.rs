@@ -333,6 +333,26 @@ impl Storage {
         Ok(())
     }
 
+    /// Return values:
+    /// - `Ok(Some(distance))` - `ancestor` is an ancestor of `descendant`, or
+    ///   the same block
+    /// - `Ok(None)` - `ancestor` is not an ancestor of `descendant`, or one of
+    ///   them was not found
+    /// - `Err(_)` - some other storage error
+    pub async fn is_ancestor(
+        &self,
+        ancestor: HeaderHash,
+        descendant: HeaderHash,
+    ) -> Result<Option<u64>, Error> {
+        self.run(
+            move |connection| match connection.is_ancestor(&ancestor, &descendant) {
+                Err(StorageError::BlockNotFound) => Ok(None),
+                r => r,
+            },
+        )
+        .await
+    }
+
     pub async fn find_closest_ancestor(
         &self,
         checkpoints: Vec<HeaderHash>,
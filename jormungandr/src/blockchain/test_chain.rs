@@ -0,0 +1,170 @@
+//! a blockchain of empty blocks signed by a single BFT leader, kept in a
+//! temporary storage, for the tests working on the states of the blocks
+
+use crate::{
+    blockcfg::{
+        Block, BlockDate, BlockVersion, ChainLength, Contents, ContentsBuilder, Header,
+        HeaderBuilderNew,
+    },
+    blockchain::{Blockchain, CheckHeaderProof, Ref, RewardsReport, Snapshots, Storage},
+    start_up::open_storage,
+    utils::test_dir::TestDir,
+};
+use chain_addr::Discrimination;
+use chain_crypto::{Ed25519, SecretKey};
+use chain_impl_mockchain::{chaintypes::ConsensusVersion, fee::LinearFee, key::BftLeaderId};
+use jormungandr_lib::interfaces::{
+    Block0Configuration, BlockchainConfiguration, ConsensusLeaderId, EpochStabilityDepth,
+    NumberOfSlotsPerEpoch,
+};
+use jormungandr_lib::time::SecondsSinceUnixEpoch;
+use slog::Logger;
+use std::sync::Arc;
+
+pub const SLOTS_PER_EPOCH: u32 = 10;
+
+/// the start of the chain, far enough in the past for the blocks not to be
+/// in the future
+const BLOCK0_DATE: u64 = 1_577_836_800;
+
+pub struct TestChain {
+    leader: SecretKey<Ed25519>,
+    pub block0: Block,
+    pub storage: Storage,
+    _dir: TestDir,
+}
+
+pub fn logger() -> Logger {
+    Logger::root(slog::Discard, o!())
+}
+
+impl TestChain {
+    /// a chain of the given epoch stability depth with an empty storage in
+    /// the test directory `name`
+    pub fn new(name: &str, epoch_stability_depth: u32) -> Self {
+        let leader = SecretKey::generate(rand_core::OsRng);
+
+        let mut blockchain_configuration = BlockchainConfiguration::new(
+            Discrimination::Test,
+            ConsensusVersion::Bft,
+            LinearFee::new(0, 0, 0),
+        );
+        blockchain_configuration.block0_date = SecondsSinceUnixEpoch::from_secs(BLOCK0_DATE);
+        blockchain_configuration.consensus_leader_ids =
+            vec![ConsensusLeaderId::from(leader.to_public())];
+        blockchain_configuration.slots_per_epoch =
+            NumberOfSlotsPerEpoch::new(SLOTS_PER_EPOCH).unwrap();
+        blockchain_configuration.epoch_stability_depth =
+            EpochStabilityDepth::from(epoch_stability_depth);
+        let block0 = Block0Configuration {
+            blockchain_configuration,
+            initial: Vec::new(),
+        }
+        .to_block();
+
+        let dir = TestDir::new(name);
+        TestChain {
+            leader,
+            block0,
+            storage: open_storage(dir.path(), &logger()).unwrap(),
+            _dir: dir,
+        }
+    }
+
+    /// the same chain with another empty storage, as kept by another node,
+    /// in the test directory `name`
+    pub fn with_empty_storage(&self, name: &str) -> Self {
+        let dir = TestDir::new(name);
+        TestChain {
+            leader: self.leader.clone(),
+            block0: self.block0.clone(),
+            storage: open_storage(dir.path(), &logger()).unwrap(),
+            _dir: dir,
+        }
+    }
+
+    /// a blockchain over the storage of the chain, with its own cache
+    pub fn blockchain(&self, cache_capacity: usize, snapshots: Option<Snapshots>) -> Blockchain {
+        Blockchain::new(
+            self.block0.header.hash(),
+            self.storage.clone(),
+            cache_capacity,
+            RewardsReport::default(),
+            snapshots,
+        )
+    }
+
+    /// an empty block on top of `parent`, `skip` slots after the next slot
+    pub fn block_after(&self, parent: &Header, skip: u32) -> Block {
+        self.signed_block_after(parent, skip, &self.leader)
+    }
+
+    /// a block on top of `parent` signed by a node that is not a leader
+    pub fn forged_block_after(&self, parent: &Header) -> Block {
+        self.signed_block_after(parent, 0, &SecretKey::generate(rand_core::OsRng))
+    }
+
+    /// `length` blocks, each one on top of the previous one, starting on top
+    /// of `parent`. The first block is `skip` slots after the next slot, so
+    /// forks starting on the same parent differ.
+    pub fn branch(&self, parent: &Header, length: usize, skip: u32) -> Vec<Block> {
+        let mut blocks: Vec<Block> = Vec::with_capacity(length);
+        for _ in 0..length {
+            let block = match blocks.last() {
+                Some(previous) => self.block_after(&previous.header, 0),
+                None => self.block_after(parent, skip),
+            };
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    fn signed_block_after(&self, parent: &Header, skip: u32, key: &SecretKey<Ed25519>) -> Block {
+        let parent_date = parent.block_date();
+        let slot = parent_date.epoch * SLOTS_PER_EPOCH + parent_date.slot_id + 1 + skip;
+        let date = BlockDate {
+            epoch: slot / SLOTS_PER_EPOCH,
+            slot_id: slot % SLOTS_PER_EPOCH,
+        };
+        let chain_length = ChainLength::from(u32::from(parent.chain_length()) + 1);
+
+        let contents: Contents = ContentsBuilder::new().into();
+        let builder = HeaderBuilderNew::new(BlockVersion::Ed25519Signed, &contents)
+            .set_parent(&parent.hash(), chain_length)
+            .set_date(date)
+            .into_bft_builder()
+            .expect("Valid Header Builder")
+            .set_consensus_data(&BftLeaderId::from(self.leader.to_public()));
+        let signature = key.sign_slice(builder.get_authenticated_data());
+        let header = builder.set_signature(signature.into()).generalize();
+        Block { header, contents }
+    }
+}
+
+/// check the block against its parent, which has to be in the cache, apply it
+/// and store it
+pub async fn apply(blockchain: &Blockchain, block: Block) -> Arc<Ref> {
+    let parent = blockchain
+        .get_ref(block.header.block_parent_hash())
+        .await
+        .unwrap()
+        .expect("the parent is in the cache");
+    let post_checked = blockchain
+        .post_check_header(block.header.clone(), parent, CheckHeaderProof::Enabled)
+        .await
+        .unwrap();
+    blockchain
+        .apply_and_store_block(post_checked, block)
+        .await
+        .unwrap()
+        .cached_ref()
+}
+
+/// apply and store the blocks, in order, returns the `Ref` of the last one
+pub async fn apply_all(blockchain: &Blockchain, blocks: Vec<Block>) -> Arc<Ref> {
+    let mut last = None;
+    for block in blocks {
+        last = Some(apply(blockchain, block).await);
+    }
+    last.expect("at least one block to apply")
+}
@@ -0,0 +1,43 @@
+//! notifications of what is happening in the node, broadcast to whoever
+//! subscribed to them (for example the clients of the REST events stream).
+
+use crate::blockcfg::Header;
+use jormungandr_lib::interfaces::{FragmentLog, LeadershipLog};
+use tokio::sync::broadcast;
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// a new block has been applied and stored
+    NewBlock(Header),
+    /// the tip of the blockchain moved to the given block
+    TipChanged(Header),
+    /// the log of a fragment has been added or its status changed
+    FragmentLog(FragmentLog),
+    /// a leadership log has been added or updated
+    LeadershipLog(LeadershipLog),
+}
+
+/// handle to broadcast events to the subscribers.
+///
+/// Subscribers that do not keep up miss the oldest events once more than
+/// the capacity of the channel are waiting to be received.
+#[derive(Clone)]
+pub struct Events {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Events {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Events { sender }
+    }
+
+    pub fn notify(&self, event: Event) {
+        // an error only means there is no subscriber at the moment
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
@@ -2,7 +2,11 @@ use crate::{
     blockcfg::{Value, ValueError},
     fragment::{Fragment, FragmentId},
 };
-use std::time::SystemTime;
+use chain_impl_mockchain::{
+    fee::{FeeAlgorithm, LinearFee},
+    transaction::{Payload, Transaction},
+};
+use std::{cmp::Ordering, time::SystemTime};
 
 pub struct PoolEntry {
     // reference of the fragment stored in the pool
@@ -29,21 +33,17 @@ pub struct PoolEntry {
 }
 
 impl PoolEntry {
-    pub fn new(fragment: &Fragment) -> Self {
+    pub fn new(fragment: &Fragment, fees: &LinearFee, received_at: SystemTime) -> Self {
         let raw = fragment.to_raw();
         let fragment_size = raw.size_bytes_plus_size();
         let fragment_ref = raw.id();
-        // TODO: the fragment fee is not yet computed. Yet we should
-        // have an explicit fee in the message. So we need to be able
-        // to extract this information without the need to compute the
-        // fee from the ledger's fee settings.
-        let fragment_fee = Value::zero();
+        let fragment_fee = fragment_fee(fragment, fees);
 
         PoolEntry {
             fragment_ref,
             fragment_fee,
             fragment_size,
-            received_at: SystemTime::now(),
+            received_at,
 
             // when this entry is added in the pool, it has no
             // descendant
@@ -74,6 +74,14 @@ impl PoolEntry {
         &self.received_at
     }
     #[inline]
+    pub fn ancestors_fee(&self) -> &Value {
+        &self.ancestors_fee
+    }
+    #[inline]
+    pub fn ancestors_size(&self) -> &usize {
+        &self.ancestors_size
+    }
+    #[inline]
     pub fn with_descendants_fee(&self) -> Result<Value, ValueError> {
         self.descendants_fee + self.fragment_fee
     }
@@ -89,4 +97,144 @@ impl PoolEntry {
     pub fn with_ancestors_size(&self) -> usize {
         self.ancestors_size + self.fragment_size
     }
+
+    #[cfg(test)]
+    pub(super) fn with_fee(
+        fragment_ref: FragmentId,
+        fragment_fee: u64,
+        fragment_size: usize,
+        received_at: SystemTime,
+    ) -> Self {
+        PoolEntry {
+            fragment_ref,
+            fragment_fee: Value(fragment_fee),
+            fragment_size,
+            received_at,
+            descendants_fee: Value::zero(),
+            descendants_size: 0,
+            ancestors_fee: Value::zero(),
+            ancestors_size: 0,
+        }
+    }
+
+    /// set the accumulated fee and size of the pending fragments this
+    /// entry depends upon
+    pub fn set_ancestors(&mut self, fee: Value, size: usize) {
+        self.ancestors_fee = fee;
+        self.ancestors_size = size;
+    }
+
+    /// set the accumulated fee and size of the pending fragments
+    /// depending on this entry
+    pub fn set_descendants(&mut self, fee: Value, size: usize) {
+        self.descendants_fee = fee;
+        self.descendants_size = size;
+    }
+
+    /// compare the fee per byte paid by this entry and its ancestors with
+    /// the one paid by `other` and its ancestors. The comparison is done
+    /// without loss of precision.
+    pub fn cmp_package_fee_rate(&self, other: &Self) -> Ordering {
+        let fee = |entry: &Self| {
+            entry
+                .with_ancestors_fee()
+                .map_or(u128::from(u64::MAX), |fee| u128::from(fee.0))
+        };
+        let lhs = fee(self) * other.with_ancestors_size() as u128;
+        let rhs = fee(other) * self.with_ancestors_size() as u128;
+        lhs.cmp(&rhs)
+    }
+}
+
+/// compute the fee the ledger will require for the given fragment
+/// given the fee settings `fees`.
+///
+/// Fragments that are not transactions (and are not accepted in the
+/// mempool anyway) are considered to not pay any fee.
+pub fn fragment_fee(fragment: &Fragment, fees: &LinearFee) -> Value {
+    match fragment {
+        Fragment::Initial(_) => Value::zero(),
+        Fragment::OldUtxoDeclaration(_) => Value::zero(),
+        Fragment::Transaction(ref tx) => transaction_fee(tx, fees),
+        Fragment::StakeDelegation(ref tx) => transaction_fee(tx, fees),
+        Fragment::OwnerStakeDelegation(ref tx) => transaction_fee(tx, fees),
+        Fragment::PoolRegistration(ref tx) => transaction_fee(tx, fees),
+        Fragment::PoolRetirement(ref tx) => transaction_fee(tx, fees),
+        Fragment::PoolUpdate(ref tx) => transaction_fee(tx, fees),
+        Fragment::UpdateProposal(_) => Value::zero(),
+        Fragment::UpdateVote(_) => Value::zero(),
+        Fragment::VotePlan(ref tx) => transaction_fee(tx, fees),
+        Fragment::VoteCast(ref tx) => transaction_fee(tx, fees),
+        Fragment::VoteTally(ref tx) => transaction_fee(tx, fees),
+    }
+}
+
+fn transaction_fee<P: Payload>(tx: &Transaction<P>, fees: &LinearFee) -> Value {
+    let tx = tx.as_slice();
+    fees.calculate(
+        tx.payload().into_certificate_slice(),
+        tx.nb_inputs(),
+        tx.nb_outputs(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(n: u8, fee: u64, size: usize) -> PoolEntry {
+        PoolEntry::with_fee(FragmentId::hash_bytes(&[n]), fee, size, SystemTime::now())
+    }
+
+    #[test]
+    fn higher_fee_per_byte_ranks_higher() {
+        let cheap = entry(0, 100, 200);
+        let expensive = entry(1, 100, 100);
+        assert_eq!(expensive.cmp_package_fee_rate(&cheap), Ordering::Greater);
+        assert_eq!(cheap.cmp_package_fee_rate(&expensive), Ordering::Less);
+    }
+
+    #[test]
+    fn equal_rates_compare_equal_without_rounding() {
+        // 1/3 of a unit per byte, a ratio with no exact binary representation
+        let small = entry(0, 1, 3);
+        let large = entry(1, 333_333, 999_999);
+        assert_eq!(small.cmp_package_fee_rate(&large), Ordering::Equal);
+        assert_eq!(large.cmp_package_fee_rate(&small), Ordering::Equal);
+    }
+
+    #[test]
+    fn package_includes_the_ancestors() {
+        // a child paying little on its own, spending from a parent paying a
+        // lot: the package pays 5 per byte
+        let mut child = entry(0, 100, 100);
+        child.set_ancestors(Value(900), 100);
+        let standalone = entry(1, 400, 100);
+        assert_eq!(child.cmp_package_fee_rate(&standalone), Ordering::Greater);
+
+        // the parent paying nothing drags its child down
+        let mut child = entry(2, 900, 100);
+        child.set_ancestors(Value(0), 300);
+        assert_eq!(child.cmp_package_fee_rate(&standalone), Ordering::Less);
+    }
+
+    #[test]
+    fn descendants_are_not_part_of_the_package() {
+        let mut parent = entry(0, 100, 100);
+        parent.set_descendants(Value(10_000), 100);
+        let standalone = entry(1, 200, 100);
+        assert_eq!(parent.cmp_package_fee_rate(&standalone), Ordering::Less);
+    }
+
+    #[test]
+    fn overflowing_package_fee_is_saturated() {
+        let mut overflowing = entry(0, u64::MAX, 100);
+        overflowing.set_ancestors(Value(1), 100);
+        // just below the saturated fee over the same size
+        let standalone = entry(1, u64::MAX / 2, 100);
+        assert_eq!(
+            overflowing.cmp_package_fee_rate(&standalone),
+            Ordering::Greater
+        );
+    }
 }
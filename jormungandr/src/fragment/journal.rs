@@ -0,0 +1,280 @@
+//! on-disk journal of the mempool and of the fragment logs.
+//!
+//! Every fragment admitted in the mempool and every change of a fragment log
+//! is appended to the journal as a JSON line. On startup the journal is read
+//! back so the pending fragments can be checked again against the ledger and
+//! put back in the mempool. The journal is then rewritten with only the
+//! current state of the mempool and of the logs.
+
+use crate::fragment::Fragment;
+use chain_core::property::{Deserialize as _, Serialize as _};
+use jormungandr_lib::{
+    crypto::hash::Hash,
+    interfaces::{FragmentLog, FragmentOrigin},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+const JOURNAL_FILE_NAME: &str = "mempool.journal";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot access the mempool journal")]
+    Io(#[from] io::Error),
+    #[error("cannot encode an entry of the mempool journal")]
+    Encode(#[from] serde_json::Error),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entry {
+    /// a fragment admitted in the mempool, hex encoded
+    Fragment {
+        origin: FragmentOrigin,
+        fragment: String,
+    },
+    /// the latest version of a fragment log
+    Log(FragmentLog),
+}
+
+pub struct Journal {
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// number of entries appended since the journal was last rewritten
+    appended: usize,
+}
+
+impl Entry {
+    pub fn fragment(origin: FragmentOrigin, fragment: &Fragment) -> Result<Self, Error> {
+        let bytes = fragment.serialize_as_vec()?;
+        Ok(Entry::Fragment {
+            origin,
+            fragment: hex::encode(bytes),
+        })
+    }
+
+    /// decode the fragment of the entry, returns `None` if the entry is not
+    /// a fragment or if it cannot be decoded
+    pub fn to_fragment(&self) -> Option<(FragmentOrigin, Fragment)> {
+        match self {
+            Entry::Fragment { origin, fragment } => {
+                let bytes = hex::decode(fragment).ok()?;
+                let fragment = Fragment::deserialize(bytes.as_slice()).ok()?;
+                Some((*origin, fragment))
+            }
+            Entry::Log(_) => None,
+        }
+    }
+}
+
+impl Journal {
+    /// open the journal in the given storage directory, creating it if it
+    /// does not exist yet.
+    ///
+    /// Returns the journal along with the entries it already contains and
+    /// the number of entries that could not be read (for example if the node
+    /// was stopped while an entry was being written).
+    pub fn open(dir: &Path) -> Result<(Self, Vec<Entry>, usize), Error> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(JOURNAL_FILE_NAME);
+
+        let mut entries = Vec::new();
+        let mut invalid = 0;
+        if path.exists() {
+            let reader = BufReader::new(File::open(&path)?);
+            for line in reader.lines() {
+                let line = line?;
+                if line.is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(&line) {
+                    Ok(entry) => entries.push(entry),
+                    Err(_) => invalid += 1,
+                }
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let journal = Journal {
+            path,
+            file: BufWriter::new(file),
+            appended: entries.len(),
+        };
+        Ok((journal, entries, invalid))
+    }
+
+    /// number of entries appended since the journal was last rewritten
+    pub fn appended(&self) -> usize {
+        self.appended
+    }
+
+    pub fn append(&mut self, entries: impl IntoIterator<Item = Entry>) -> Result<(), Error> {
+        for entry in entries {
+            serde_json::to_writer(&mut self.file, &entry)?;
+            self.file.write_all(b"\n")?;
+            self.appended += 1;
+        }
+        self.file.flush()?;
+        Ok(())
+    }
+
+    /// replace the content of the journal with the given entries.
+    ///
+    /// The entries are written in a temporary file first, which then replaces
+    /// the journal so a crash while rewriting does not lose the journal.
+    pub fn rewrite(&mut self, entries: impl IntoIterator<Item = Entry>) -> Result<(), Error> {
+        let tmp_path = self.path.with_extension("journal.tmp");
+        {
+            let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+            for entry in entries {
+                serde_json::to_writer(&mut tmp, &entry)?;
+                tmp.write_all(b"\n")?;
+            }
+            tmp.flush()?;
+            tmp.get_ref().sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+
+        let file = OpenOptions::new().append(true).open(&self.path)?;
+        self.file = BufWriter::new(file);
+        self.appended = 0;
+        Ok(())
+    }
+}
+
+/// split the entries read back from the journal into the latest version of
+/// each fragment log, in the order the fragments were first logged, and the
+/// fragment entries
+pub fn replay(entries: Vec<Entry>) -> (Vec<FragmentLog>, Vec<Entry>) {
+    let mut fragments = Vec::new();
+    let mut logs: Vec<FragmentLog> = Vec::new();
+    let mut logs_index: HashMap<Hash, usize> = HashMap::new();
+    for entry in entries {
+        match entry {
+            Entry::Log(log) => match logs_index.get(log.fragment_id()) {
+                Some(index) => logs[*index] = log,
+                None => {
+                    logs_index.insert(*log.fragment_id(), logs.len());
+                    logs.push(log);
+                }
+            },
+            entry => fragments.push(entry),
+        }
+    }
+    (logs, fragments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fragment::FragmentId, utils::test_dir::TestDir};
+    use jormungandr_lib::interfaces::FragmentStatus;
+
+    fn log(n: u8) -> FragmentLog {
+        FragmentLog::new(FragmentId::hash_bytes(&[n]), FragmentOrigin::Rest)
+    }
+
+    fn log_ids(entries: &[Entry]) -> Vec<FragmentId> {
+        entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Log(log) => Some(log.fragment_id().clone().into_hash()),
+                Entry::Fragment { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn appended_entries_are_read_back_in_order() {
+        let dir = TestDir::new("mempool-journal-append");
+        let (mut journal, entries, invalid) = Journal::open(dir.path()).unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(invalid, 0);
+
+        journal.append(vec![Entry::Log(log(0))]).unwrap();
+        let mut rejected = log(1);
+        rejected.modify(FragmentStatus::Rejected {
+            reason: "invalid".to_owned(),
+        });
+        journal
+            .append(vec![Entry::Log(log(1)), Entry::Log(rejected)])
+            .unwrap();
+        assert_eq!(journal.appended(), 3);
+        drop(journal);
+
+        let (journal, entries, invalid) = Journal::open(dir.path()).unwrap();
+        assert_eq!(invalid, 0);
+        assert_eq!(journal.appended(), 3);
+        let ids = vec![0, 1, 1]
+            .into_iter()
+            .map(|n| FragmentId::hash_bytes(&[n]));
+        assert_eq!(log_ids(&entries), ids.collect::<Vec<_>>());
+        match entries.last() {
+            Some(Entry::Log(log)) => assert!(log.is_rejected()),
+            _ => panic!("the last entry should be the rejected log"),
+        }
+    }
+
+    #[test]
+    fn truncated_entries_are_skipped() {
+        let dir = TestDir::new("mempool-journal-truncated");
+        let (mut journal, _, _) = Journal::open(dir.path()).unwrap();
+        journal.append(vec![Entry::Log(log(0))]).unwrap();
+        drop(journal);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(JOURNAL_FILE_NAME))
+            .unwrap();
+        file.write_all(b"{\"log\":{\"fragm").unwrap();
+        drop(file);
+
+        let (_, entries, invalid) = Journal::open(dir.path()).unwrap();
+        assert_eq!(invalid, 1);
+        assert_eq!(log_ids(&entries), vec![FragmentId::hash_bytes(&[0])]);
+    }
+
+    #[test]
+    fn rewrite_replaces_the_journal() {
+        let dir = TestDir::new("mempool-journal-rewrite");
+        let (mut journal, _, _) = Journal::open(dir.path()).unwrap();
+        journal
+            .append(vec![Entry::Log(log(0)), Entry::Log(log(1))])
+            .unwrap();
+        journal.rewrite(vec![Entry::Log(log(2))]).unwrap();
+        assert_eq!(journal.appended(), 0);
+        journal.append(vec![Entry::Log(log(3))]).unwrap();
+        drop(journal);
+
+        let (_, entries, _) = Journal::open(dir.path()).unwrap();
+        assert_eq!(
+            log_ids(&entries),
+            vec![FragmentId::hash_bytes(&[2]), FragmentId::hash_bytes(&[3])]
+        );
+    }
+
+    #[test]
+    fn replay_keeps_the_latest_version_of_each_log() {
+        let mut rejected = log(0);
+        rejected.modify(FragmentStatus::Rejected {
+            reason: "invalid".to_owned(),
+        });
+        let received_at = *log(0).received_at();
+        let (logs, fragments) = replay(vec![
+            Entry::Log(log(0)),
+            Entry::Log(log(1)),
+            Entry::Log(rejected),
+        ]);
+        assert!(fragments.is_empty());
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0].fragment_id(), log(0).fragment_id());
+        assert!(logs[0].is_rejected());
+        assert!(*logs[0].received_at() <= received_at);
+        assert!(logs[1].is_pending());
+    }
+}
@@ -1,33 +1,80 @@
-use crate::fragment::FragmentId;
+use crate::{
+    events::{Event, Events},
+    fragment::FragmentId,
+};
 use jormungandr_lib::{
     crypto::hash::Hash,
     interfaces::{FragmentLog, FragmentOrigin, FragmentStatus},
 };
 use lru::LruCache;
+use std::{collections::HashSet, time::SystemTime};
 
 pub struct Logs {
     entries: LruCache<Hash, FragmentLog>,
+    /// identifiers of the logs modified since the last call to
+    /// `take_changes`, only tracked if enabled with `track_changes`
+    changes: Option<HashSet<Hash>>,
+    events: Events,
 }
 
 impl Logs {
-    pub fn new(max_entries: usize) -> Self {
+    pub fn new(max_entries: usize, events: Events) -> Self {
         Logs {
             entries: LruCache::new(max_entries),
+            changes: None,
+            events,
         }
     }
 
-    pub fn exists(&self, fragment_id: FragmentId) -> bool {
-        let fragment_id: Hash = fragment_id.into();
-        self.entries.contains(&fragment_id)
+    /// start keeping track of the logs being inserted or modified
+    pub fn track_changes(&mut self) {
+        if self.changes.is_none() {
+            self.changes = Some(HashSet::new());
+        }
     }
 
-    pub fn exist_all(&self, fragment_ids: impl IntoIterator<Item = FragmentId>) -> Vec<bool> {
-        fragment_ids
+    /// the logs inserted or modified since the last call, if the changes
+    /// are tracked
+    pub fn take_changes(&mut self) -> Vec<FragmentLog> {
+        let changes = match self.changes.as_mut() {
+            Some(changes) => std::mem::take(changes),
+            None => return Vec::new(),
+        };
+        changes
             .into_iter()
-            .map(|fragment_id| self.exists(fragment_id))
+            .filter_map(|fragment_id| self.entries.peek(&fragment_id).cloned())
             .collect()
     }
 
+    fn changed(&mut self, fragment_id: Hash) {
+        if let Some(changes) = self.changes.as_mut() {
+            changes.insert(fragment_id);
+        }
+        if let Some(log) = self.entries.peek(&fragment_id) {
+            self.events.notify(Event::FragmentLog(log.clone()));
+        }
+    }
+
+    pub fn get(&self, fragment_id: &Hash) -> Option<&FragmentLog> {
+        self.entries.peek(fragment_id)
+    }
+
+    /// the logs of the given fragments, the fragments without a log are
+    /// skipped
+    pub fn get_all<'a>(
+        &'a self,
+        fragment_ids: impl IntoIterator<Item = FragmentId> + 'a,
+    ) -> impl Iterator<Item = &'a FragmentLog> + 'a {
+        fragment_ids
+            .into_iter()
+            .filter_map(move |fragment_id| self.get(&fragment_id.into()))
+    }
+
+    pub fn exists(&self, fragment_id: FragmentId) -> bool {
+        let fragment_id: Hash = fragment_id.into();
+        self.entries.contains(&fragment_id)
+    }
+
     /// Returns true if fragment was registered
     pub fn insert(&mut self, log: FragmentLog) -> bool {
         let fragment_id = *log.fragment_id();
@@ -35,6 +82,7 @@ impl Logs {
             false
         } else {
             self.entries.put(fragment_id, log);
+            self.changed(fragment_id);
             true
         }
     }
@@ -59,12 +107,13 @@ impl Logs {
                 // we can mark the status of the transaction so newly received transaction
                 // be stored.
 
-                self.entries.put(
-                    fragment_id,
-                    FragmentLog::new(fragment_id.clone().into_hash(), FragmentOrigin::Network),
-                );
+                let mut log =
+                    FragmentLog::new(fragment_id.clone().into_hash(), FragmentOrigin::Network);
+                log.modify(status);
+                self.entries.put(fragment_id, log);
             }
         }
+        self.changed(fragment_id);
     }
 
     pub fn modify_all(
@@ -77,7 +126,75 @@ impl Logs {
         }
     }
 
+    /// mark the logs of the fragments received before `deadline` and still
+    /// pending as expired. Returns the number of logs that expired.
+    pub fn expire_pending_received_before(&mut self, deadline: SystemTime) -> usize {
+        let mut expired = Vec::new();
+        for (fragment_id, log) in self.entries.iter_mut() {
+            if log.is_pending() && log.received_at().as_ref() < &deadline {
+                log.modify(FragmentStatus::Expired);
+                expired.push(*fragment_id);
+            }
+        }
+        let count = expired.len();
+        for fragment_id in expired {
+            self.changed(fragment_id);
+        }
+        count
+    }
+
     pub fn logs<'a>(&'a self) -> impl Iterator<Item = &'a FragmentLog> {
         self.entries.iter().map(|(_, v)| v)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn id(n: u8) -> FragmentId {
+        FragmentId::hash_bytes(&[n])
+    }
+
+    fn log(n: u8) -> FragmentLog {
+        FragmentLog::new(id(n), FragmentOrigin::Rest)
+    }
+
+    #[test]
+    fn only_pending_logs_expire() {
+        let mut logs = Logs::new(10, Events::new(1));
+        logs.insert_all(vec![log(0), log(1), log(2)]);
+        logs.modify(
+            id(1),
+            FragmentStatus::Rejected {
+                reason: String::new(),
+            },
+        );
+
+        assert_eq!(
+            logs.expire_pending_received_before(SystemTime::UNIX_EPOCH),
+            0
+        );
+        let deadline = SystemTime::now() + Duration::from_secs(1);
+        assert_eq!(logs.expire_pending_received_before(deadline), 2);
+        assert!(logs.get(&id(0).into()).unwrap().is_expired());
+        assert!(logs.get(&id(1).into()).unwrap().is_rejected());
+        assert!(logs.get(&id(2).into()).unwrap().is_expired());
+        assert_eq!(logs.expire_pending_received_before(deadline), 0);
+    }
+
+    #[test]
+    fn expired_logs_are_tracked_as_changes() {
+        let mut logs = Logs::new(10, Events::new(1));
+        logs.track_changes();
+        logs.insert(log(0));
+        assert_eq!(logs.take_changes().len(), 1);
+
+        let deadline = SystemTime::now() + Duration::from_secs(1);
+        logs.expire_pending_received_before(deadline);
+        let changes = logs.take_changes();
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].is_expired());
+    }
+}
@@ -1,12 +1,15 @@
 mod entry;
+mod journal;
 mod logs;
+mod orphans;
 mod pool;
 mod process;
 pub mod selection;
 
 pub use self::entry::PoolEntry;
+pub use self::journal::{Entry as JournalEntry, Error as JournalError, Journal};
 pub use self::logs::Logs;
-pub use self::pool::Pool;
+pub use self::pool::{is_rejected_as_invalid, Pool};
 pub use self::process::Process;
 
 pub use crate::blockcfg::{Fragment, FragmentId};
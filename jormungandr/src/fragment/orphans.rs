@@ -0,0 +1,272 @@
+//! fragments received before the pending fragments they depend upon.
+//!
+//! Such a fragment cannot be checked against the ledger yet, it is held here
+//! until the fragments it waits for are admitted in the mempool, or until it
+//! is dropped for waiting too long or to make room for other orphans.
+
+use crate::fragment::FragmentId;
+use linked_hash_map::LinkedHashMap;
+use std::{collections::HashMap, time::SystemTime};
+
+pub type AccountKey = [u8; 32];
+
+/// a fragment an orphan is waiting for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dependency {
+    /// the fragment using the given spending counter of the account
+    Counter(AccountKey, u32),
+    /// the transaction whose outputs are spent
+    Transaction(FragmentId),
+}
+
+struct Entry<T> {
+    item: T,
+    received_at: SystemTime,
+    missing: Vec<Dependency>,
+}
+
+pub struct Orphans<T> {
+    /// the orphans, from the oldest to the most recently received one
+    entries: LinkedHashMap<FragmentId, Entry<T>>,
+    /// the orphans waiting for a given dependency
+    waiting: HashMap<Dependency, Vec<FragmentId>>,
+    max_entries: usize,
+}
+
+impl<T> Orphans<T> {
+    pub fn new(max_entries: usize) -> Self {
+        Orphans {
+            entries: LinkedHashMap::new(),
+            waiting: HashMap::new(),
+            max_entries,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// hold a fragment until the fragments it depends upon are admitted.
+    ///
+    /// If there are already too many orphans, the oldest one is dropped to
+    /// make room and returned.
+    pub fn insert(
+        &mut self,
+        fragment_id: FragmentId,
+        item: T,
+        received_at: SystemTime,
+        missing: Vec<Dependency>,
+    ) -> Option<(FragmentId, T)> {
+        if self.max_entries == 0 {
+            return Some((fragment_id, item));
+        }
+        let dropped = if self.entries.len() >= self.max_entries {
+            self.pop_oldest()
+        } else {
+            None
+        };
+        for dependency in missing.iter() {
+            self.waiting
+                .entry(*dependency)
+                .or_default()
+                .push(fragment_id);
+        }
+        let entry = Entry {
+            item,
+            received_at,
+            missing,
+        };
+        if let Some(previous) = self.entries.insert(fragment_id, entry) {
+            self.unindex(&fragment_id, &previous.missing);
+        }
+        dropped
+    }
+
+    /// whether an orphan is already waiting for the dependency
+    pub fn is_waiting_for(&self, dependency: &Dependency) -> bool {
+        self.waiting.contains_key(dependency)
+    }
+
+    pub fn remove(&mut self, fragment_id: &FragmentId) -> Option<T> {
+        let entry = self.entries.remove(fragment_id)?;
+        self.unindex(fragment_id, &entry.missing);
+        Some(entry.item)
+    }
+
+    /// remove the orphans waiting for any of the given dependencies, from the
+    /// oldest to the most recently received
+    pub fn take_waiting_for(
+        &mut self,
+        provided: &[Dependency],
+    ) -> Vec<(FragmentId, T, SystemTime)> {
+        let mut ids: Vec<FragmentId> = provided
+            .iter()
+            .filter_map(|dependency| self.waiting.get(dependency))
+            .flatten()
+            .cloned()
+            .collect();
+        ids.sort_by_key(|id| self.position(id));
+        ids.dedup();
+        ids.into_iter()
+            .filter_map(|id| {
+                let entry = self.entries.remove(&id)?;
+                self.unindex(&id, &entry.missing);
+                Some((id, entry.item, entry.received_at))
+            })
+            .collect()
+    }
+
+    /// remove every orphan, from the oldest to the most recently received
+    pub fn take_all(&mut self) -> Vec<(FragmentId, T, SystemTime)> {
+        self.waiting.clear();
+        let mut taken = Vec::with_capacity(self.entries.len());
+        while let Some((id, entry)) = self.entries.pop_front() {
+            taken.push((id, entry.item, entry.received_at));
+        }
+        taken
+    }
+
+    /// remove the orphans received before `deadline`, along with the
+    /// dependencies they are still waiting for
+    pub fn take_received_before(
+        &mut self,
+        deadline: SystemTime,
+    ) -> Vec<(FragmentId, T, Vec<Dependency>)> {
+        let expired: Vec<FragmentId> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.received_at < deadline)
+            .map(|(id, _)| *id)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|id| {
+                let entry = self.entries.remove(&id)?;
+                self.unindex(&id, &entry.missing);
+                Some((id, entry.item, entry.missing))
+            })
+            .collect()
+    }
+
+    /// the orphans, from the oldest to the most recently received
+    pub fn iter(&self) -> impl Iterator<Item = (&FragmentId, &T)> {
+        self.entries.iter().map(|(id, entry)| (id, &entry.item))
+    }
+
+    fn pop_oldest(&mut self) -> Option<(FragmentId, T)> {
+        let (id, entry) = self.entries.pop_front()?;
+        self.unindex(&id, &entry.missing);
+        Some((id, entry.item))
+    }
+
+    fn position(&self, fragment_id: &FragmentId) -> usize {
+        self.entries
+            .keys()
+            .position(|id| id == fragment_id)
+            .unwrap_or(usize::MAX)
+    }
+
+    fn unindex(&mut self, fragment_id: &FragmentId, missing: &[Dependency]) {
+        for dependency in missing {
+            if let Some(ids) = self.waiting.get_mut(dependency) {
+                ids.retain(|id| id != fragment_id);
+                if ids.is_empty() {
+                    self.waiting.remove(dependency);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn id(n: u8) -> FragmentId {
+        FragmentId::hash_bytes(&[n])
+    }
+
+    fn counter(account: u8, counter: u32) -> Dependency {
+        Dependency::Counter([account; 32], counter)
+    }
+
+    #[test]
+    fn orphans_are_released_by_their_dependency() {
+        let now = SystemTime::now();
+        let mut orphans = Orphans::new(10);
+        assert!(orphans.insert(id(2), 2, now, vec![counter(0, 1)]).is_none());
+        assert!(orphans.insert(id(3), 3, now, vec![counter(0, 2)]).is_none());
+
+        assert!(orphans.take_waiting_for(&[counter(1, 1)]).is_empty());
+        let released = orphans.take_waiting_for(&[counter(0, 1)]);
+        assert_eq!(released, vec![(id(2), 2, now)]);
+        assert_eq!(orphans.len(), 1);
+        assert!(orphans.iter().any(|(held, _)| *held == id(3)));
+
+        let released = orphans.take_waiting_for(&[counter(0, 2)]);
+        assert_eq!(released, vec![(id(3), 3, now)]);
+        assert_eq!(orphans.len(), 0);
+    }
+
+    #[test]
+    fn orphans_waiting_for_several_dependencies_are_released_once() {
+        let now = SystemTime::now();
+        let mut orphans = Orphans::new(10);
+        orphans.insert(id(1), 1, now, vec![counter(0, 1), counter(1, 4)]);
+        orphans.insert(id(2), 2, now, vec![counter(1, 4)]);
+
+        let released = orphans.take_waiting_for(&[counter(1, 4), counter(0, 1)]);
+        assert_eq!(released, vec![(id(1), 1, now), (id(2), 2, now)]);
+        assert!(orphans.take_waiting_for(&[counter(0, 1)]).is_empty());
+    }
+
+    #[test]
+    fn oldest_orphan_is_dropped_when_full() {
+        let now = SystemTime::now();
+        let mut orphans = Orphans::new(2);
+        assert!(orphans.insert(id(1), 1, now, vec![counter(0, 0)]).is_none());
+        assert!(orphans.insert(id(2), 2, now, vec![counter(0, 1)]).is_none());
+        assert_eq!(
+            orphans.insert(id(3), 3, now, vec![counter(0, 2)]),
+            Some((id(1), 1))
+        );
+        assert_eq!(orphans.len(), 2);
+        // the dropped orphan is not released anymore
+        assert!(!orphans.is_waiting_for(&counter(0, 0)));
+        assert!(orphans.take_waiting_for(&[counter(0, 0)]).is_empty());
+    }
+
+    #[test]
+    fn orphans_spending_unseen_transactions_are_released_by_them() {
+        let now = SystemTime::now();
+        let mut orphans = Orphans::new(10);
+        let parent = Dependency::Transaction(id(1));
+        orphans.insert(id(2), 2, now, vec![parent]);
+        orphans.insert(id(3), 3, now, vec![parent, counter(0, 1)]);
+        assert!(orphans.is_waiting_for(&parent));
+
+        let released = orphans.take_waiting_for(&[parent]);
+        assert_eq!(released, vec![(id(2), 2, now), (id(3), 3, now)]);
+        assert!(!orphans.is_waiting_for(&parent));
+        assert!(!orphans.is_waiting_for(&counter(0, 1)));
+    }
+
+    #[test]
+    fn old_orphans_are_taken_with_their_missing_dependencies() {
+        let now = SystemTime::now();
+        let old = now - Duration::from_secs(600);
+        let mut orphans = Orphans::new(10);
+        orphans.insert(id(1), 1, old, vec![counter(0, 0)]);
+        orphans.insert(id(2), 2, now, vec![counter(0, 1)]);
+
+        let expired = orphans.take_received_before(now - Duration::from_secs(60));
+        assert_eq!(expired, vec![(id(1), 1, vec![counter(0, 0)])]);
+        assert_eq!(orphans.len(), 1);
+        assert!(orphans.take_waiting_for(&[counter(0, 0)]).is_empty());
+    }
+}
@@ -1,30 +1,138 @@
 use crate::{
-    blockcfg::{BlockDate, Ledger, LedgerParameters},
+    blockcfg::{BlockDate, Ledger, LedgerParameters, Value},
+    blockchain::Tip,
     fragment::{
-        selection::{FragmentSelectionAlgorithm, FragmentSelectionAlgorithmParams, OldestFirst},
-        Fragment, FragmentId, Logs,
+        journal,
+        orphans::{Dependency, Orphans},
+        selection::{
+            FeeRate, FragmentSelectionAlgorithm, FragmentSelectionAlgorithmParams, OldestFirst,
+        },
+        Fragment, FragmentId, Journal, JournalEntry, Logs,
     },
     intercom::{NetworkMsg, PropagateMsg},
+    network::p2p::{Address, Behavior},
     utils::async_msg::MessageBox,
 };
 use chain_core::property::Fragment as _;
 use chain_impl_mockchain::{fragment::Contents, transaction::Transaction};
 use futures::sink::SinkExt;
-use jormungandr_lib::interfaces::{FragmentLog, FragmentOrigin, FragmentStatus};
+use internal::Admission;
+use jormungandr_lib::interfaces::{
+    FragmentLog, FragmentOrigin, FragmentStatus, FragmentSubmission, FragmentSubmissionStatus,
+    MempoolStats, PendingFragment, PendingFragments,
+};
 use slog::Logger;
+use std::{
+    collections::HashSet,
+    time::{Duration, SystemTime},
+};
+
+/// number of entries appended to the journal after which it is rewritten
+/// with only the current state of the pool and of the logs
+const JOURNAL_COMPACTION_THRESHOLD: usize = 100_000;
+
+/// maximum number of fragments held until the pending fragments they depend
+/// upon are received
+const MAX_ORPHANS: usize = 1024;
+
+/// time after which a fragment still waiting for the pending fragments it
+/// depends upon is dropped
+const ORPHAN_TTL: Duration = Duration::from_secs(120);
+
+/// maximum number of pending fragments sent to a peer asking for all of
+/// them, or for a list of fragments
+const MAX_FRAGMENTS_SERVED: usize = 1024;
+
+/// maximum number of fragments from a peer held until the transactions they
+/// spend from, which this node has not seen, are fetched from the peer
+const MAX_HELD_SPENDING_UNSEEN_PER_PEER: usize = 16;
+
+/// reasons of the rejections not caused by the fragment being invalid
+const REJECTED_ALREADY_RECEIVED: &str = "fragment already received";
+const REJECTED_TOO_MANY_SPENDING_UNSEEN: &str =
+    "too many fragments from this peer are waiting for transactions this node has not seen";
+
+/// reason of the rejection of the fragments evicted to make room for others
+const REJECTED_POOL_FULL: &str = "evicted to make room in the full mempool";
+
+/// reasons of the rejections of fragments that were held
+const REJECTED_TOO_MANY_ORPHANS: &str =
+    "dropped to make room for other fragments waiting for the fragments they depend upon";
+const REJECTED_MISSING_DEPENDENCIES: &str =
+    "the pending fragments it depends upon were not received in time";
+
+/// reasons of the fragments being held
+const HELD_MISSING_COUNTERS: &str =
+    "held until the fragments using the preceding spending counters are received";
+const HELD_UNSEEN_INPUTS: &str =
+    "held until the transactions it spends from are received from the peer";
+
+/// whether the fragment was rejected for failing validation, rather than
+/// for being already received
+pub fn is_rejected_as_invalid(submission: &FragmentSubmission) -> bool {
+    match submission.status() {
+        FragmentSubmissionStatus::Accepted | FragmentSubmissionStatus::Held { .. } => false,
+        FragmentSubmissionStatus::Rejected { reason } => {
+            reason != REJECTED_ALREADY_RECEIVED && reason != REJECTED_TOO_MANY_SPENDING_UNSEEN
+        }
+    }
+}
+
+/// a fragment held until the fragments it depends upon are received
+struct Held {
+    origin: FragmentOrigin,
+    /// the peer the fragment was received from, if it came from the network
+    sender: Option<Address>,
+    fragment: Fragment,
+    /// whether the fragment is in the journal already, a held fragment is
+    /// only written there once it is admitted in the pool
+    journaled: bool,
+    /// whether the fragment waits for transactions this node has not seen
+    spends_unseen: bool,
+}
 
 pub struct Pool {
     logs: Logs,
     pool: internal::Pool,
+    /// fragments received before the pending fragments they depend upon
+    orphans: Orphans<Held>,
+    /// peers that sent held fragments found invalid, or whose inputs were
+    /// never received, not reported yet
+    misbehaving: Vec<Address>,
     network_msg_box: MessageBox<NetworkMsg>,
+    tip: Tip,
+    journal: Option<Journal>,
+    /// fragments admitted in the pool and not yet written in the journal
+    journal_fragments: Vec<JournalEntry>,
+}
+
+/// the ledger the fragments are checked against
+struct Validation<'a> {
+    ledger: &'a Ledger,
+    ledger_params: &'a LedgerParameters,
+    block_date: BlockDate,
 }
 
 impl Pool {
-    pub fn new(max_entries: usize, logs: Logs, network_msg_box: MessageBox<NetworkMsg>) -> Self {
+    pub fn new(
+        max_entries: usize,
+        mut logs: Logs,
+        network_msg_box: MessageBox<NetworkMsg>,
+        tip: Tip,
+        journal: Option<Journal>,
+    ) -> Self {
+        if journal.is_some() {
+            logs.track_changes();
+        }
         Pool {
             logs,
             pool: internal::Pool::new(max_entries),
+            orphans: Orphans::new(MAX_ORPHANS),
+            misbehaving: Vec::new(),
             network_msg_box,
+            tip,
+            journal,
+            journal_fragments: Vec::new(),
         }
     }
 
@@ -32,48 +140,666 @@ impl Pool {
         &mut self.logs
     }
 
-    /// Returns number of registered fragments
+    /// Check the fragments against the ledger of the current tip, register
+    /// and propagate the ones that could be applied. Fragments rejected by
+    /// the ledger are marked as such in the logs and are not propagated.
+    ///
+    /// Fragments using spending counters ahead of the pending fragments of
+    /// their account are held until the fragments using the counters in
+    /// between are received, they are checked and propagated then. The same
+    /// goes for the fragments received from `sender` the ledger only rejects
+    /// for spending the outputs of transactions this node has not seen,
+    /// those transactions are fetched from `sender`. The held fragments are
+    /// journaled once they are admitted.
+    ///
+    /// Returns the outcome of the admission of each fragment, in the order
+    /// the fragments were given
     pub async fn insert_and_propagate_all(
         &mut self,
         origin: FragmentOrigin,
-        mut fragments: Vec<Fragment>,
+        sender: Option<Address>,
+        fragments: Vec<Fragment>,
         logger: Logger,
-    ) -> Result<usize, ()> {
+    ) -> Result<Vec<FragmentSubmission>, ()> {
         debug!(logger, "received {} fragments", fragments.len(); "origin" => ?origin);
-        fragments.retain(is_fragment_valid);
+        let mut submissions = Vec::with_capacity(fragments.len());
         if fragments.is_empty() {
-            return Ok(0);
+            return Ok(submissions);
         }
-        let mut network_msg_box = self.network_msg_box.clone();
-        let fragment_ids = fragments.iter().map(Fragment::id).collect::<Vec<_>>();
-        let fragments_exist_in_logs = self.logs.exist_all(fragment_ids);
-        let new_fragments = fragments
-            .into_iter()
-            .zip(fragments_exist_in_logs)
-            .filter(|(_, exists_in_logs)| !exists_in_logs)
-            .map(|(fragment, _)| fragment);
-        let new_fragments = self.pool.insert_all(new_fragments);
-        let count = new_fragments.len();
-        let fragment_logs = new_fragments
+
+        let tip = self.tip.get_ref().await;
+        let ledger = tip.ledger();
+        let validation = Validation {
+            ledger: &ledger,
+            ledger_params: tip.epoch_ledger_parameters(),
+            block_date: tip.block_date(),
+        };
+
+        let mut accepted = Vec::new();
+        let mut unseen = HashSet::new();
+        for fragment in fragments {
+            let id = fragment.id();
+            if !is_fragment_valid(&fragment) {
+                submissions.push(FragmentSubmission::rejected(
+                    Some(id),
+                    "fragment is invalid or not accepted in the mempool".to_owned(),
+                ));
+                continue;
+            }
+            if self.logs.exists(id) {
+                submissions.push(FragmentSubmission::rejected(
+                    Some(id),
+                    REJECTED_ALREADY_RECEIVED.to_owned(),
+                ));
+                continue;
+            }
+            let received_at = SystemTime::now();
+            match self.pool.insert_valid(
+                &fragment,
+                validation.ledger,
+                validation.ledger_params,
+                validation.block_date,
+                received_at,
+            ) {
+                Ok(Admission::Accepted(evicted)) => {
+                    self.log_evicted(evicted, &logger);
+                    self.add_to_journal(origin, &fragment, &logger);
+                    self.logs.insert(FragmentLog::new(id, origin));
+                    submissions.push(FragmentSubmission::accepted(id));
+                    accepted.push(fragment);
+                    let provided = self.pool.provides(&id);
+                    accepted.extend(self.admit_orphans(provided, &validation, &logger));
+                }
+                Ok(Admission::AlreadyPending) => {
+                    submissions.push(FragmentSubmission::rejected(
+                        Some(id),
+                        REJECTED_ALREADY_RECEIVED.to_owned(),
+                    ));
+                }
+                Ok(Admission::Missing(missing)) => {
+                    debug!(
+                        logger,
+                        "fragment held until the fragments it depends upon are received";
+                        "fragment_id" => %id,
+                    );
+                    self.logs.insert(FragmentLog::new(id, origin));
+                    submissions.push(FragmentSubmission::held(
+                        id,
+                        HELD_MISSING_COUNTERS.to_owned(),
+                    ));
+                    let held = Held {
+                        origin,
+                        sender: sender.clone(),
+                        fragment,
+                        journaled: false,
+                        spends_unseen: false,
+                    };
+                    self.hold(id, held, received_at, missing);
+                }
+                Ok(Admission::UnknownInputs(reason)) => {
+                    let missing = self.unseen_inputs(&fragment);
+                    let peer = match &sender {
+                        Some(peer) if !missing.is_empty() => peer,
+                        _ => {
+                            submissions.push(self.reject(id, origin, reason, &logger));
+                            continue;
+                        }
+                    };
+                    if self.held_spending_unseen(peer) >= MAX_HELD_SPENDING_UNSEEN_PER_PEER {
+                        let reason = REJECTED_TOO_MANY_SPENDING_UNSEEN.to_owned();
+                        submissions.push(self.reject(id, origin, reason, &logger));
+                        continue;
+                    }
+                    // the fragment may spend the outputs of fragments this
+                    // node missed, those are fetched from the peer unless
+                    // another held fragment is already waiting for them
+                    debug!(
+                        logger,
+                        "fragment held until the fragments it spends from are received";
+                        "fragment_id" => %id,
+                        "reason" => %reason,
+                    );
+                    unseen.extend(missing.iter().filter(|parent| {
+                        !self
+                            .orphans
+                            .is_waiting_for(&Dependency::Transaction(**parent))
+                    }));
+                    self.logs.insert(FragmentLog::new(id, origin));
+                    submissions.push(FragmentSubmission::held(id, HELD_UNSEEN_INPUTS.to_owned()));
+                    let held = Held {
+                        origin,
+                        sender: sender.clone(),
+                        fragment,
+                        journaled: false,
+                        spends_unseen: true,
+                    };
+                    let missing = missing.into_iter().map(Dependency::Transaction).collect();
+                    self.hold(id, held, received_at, missing);
+                }
+                Err(reason) => {
+                    submissions.push(self.reject(id, origin, reason, &logger));
+                }
+            }
+        }
+        self.propagate(accepted, &logger).await?;
+        self.report_misbehaving(&logger).await?;
+        if let (Some(sender), false) = (sender, unseen.is_empty()) {
+            self.network_msg_box
+                .send(NetworkMsg::GetFragments(
+                    sender,
+                    unseen.into_iter().collect(),
+                ))
+                .await
+                .map_err(|e| error!(logger, "cannot fetch fragments from network: {}", e))?;
+        }
+        Ok(submissions)
+    }
+
+    /// mark the fragment as rejected in the logs
+    fn reject(
+        &mut self,
+        fragment_id: FragmentId,
+        origin: FragmentOrigin,
+        reason: String,
+        logger: &Logger,
+    ) -> FragmentSubmission {
+        debug!(logger, "fragment rejected"; "fragment_id" => %fragment_id, "reason" => %reason);
+        let mut log = FragmentLog::new(fragment_id, origin);
+        log.modify(FragmentStatus::Rejected {
+            reason: reason.clone(),
+        });
+        self.logs.insert(log);
+        FragmentSubmission::rejected(Some(fragment_id), reason)
+    }
+
+    /// number of the fragments from the peer held until the transactions
+    /// they spend from are received
+    fn held_spending_unseen(&self, peer: &Address) -> usize {
+        self.orphans
             .iter()
-            .map(move |fragment| FragmentLog::new(fragment.id(), origin))
-            .collect::<Vec<_>>();
-        for fragment in new_fragments.into_iter() {
+            .filter(|(_, held)| held.spends_unseen && held.sender.as_ref() == Some(peer))
+            .count()
+    }
+
+    async fn propagate(&mut self, fragments: Vec<Fragment>, logger: &Logger) -> Result<(), ()> {
+        for fragment in fragments.into_iter() {
             let fragment_msg = NetworkMsg::Propagate(PropagateMsg::Fragment(fragment));
-            network_msg_box
+            self.network_msg_box
                 .send(fragment_msg)
                 .await
                 .map_err(|e| error!(logger, "cannot propagate fragment to network: {}", e))?;
         }
-        self.logs.insert_all(fragment_logs);
-        Ok(count)
+        Ok(())
+    }
+
+    /// lower the reputation of the peers that sent held fragments found
+    /// invalid, or whose inputs were never received
+    async fn report_misbehaving(&mut self, logger: &Logger) -> Result<(), ()> {
+        for sender in std::mem::replace(&mut self.misbehaving, Vec::new()) {
+            self.network_msg_box
+                .send(NetworkMsg::ReportPeer(sender, Behavior::InvalidFragment))
+                .await
+                .map_err(|e| error!(logger, "cannot report peer to network: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// mark as rejected the fragments evicted to make room in the full pool
+    fn log_evicted(&mut self, evicted: Vec<FragmentId>, logger: &Logger) {
+        if evicted.is_empty() {
+            return;
+        }
+        debug!(
+            logger,
+            "{} fragments evicted from the full pool",
+            evicted.len()
+        );
+        self.logs.modify_all(
+            evicted,
+            FragmentStatus::Rejected {
+                reason: REJECTED_POOL_FULL.to_owned(),
+            },
+        );
+    }
+
+    /// hold a fragment until the pending fragments it depends upon are
+    /// received. The fragment dropped to make room for it, if any, is marked
+    /// as rejected.
+    fn hold(
+        &mut self,
+        fragment_id: FragmentId,
+        held: Held,
+        received_at: SystemTime,
+        missing: Vec<Dependency>,
+    ) {
+        let dropped = self.orphans.insert(fragment_id, held, received_at, missing);
+        if let Some((dropped, _)) = dropped {
+            self.logs.modify(
+                dropped,
+                FragmentStatus::Rejected {
+                    reason: REJECTED_TOO_MANY_ORPHANS.to_owned(),
+                },
+            );
+        }
+    }
+
+    /// check again a held fragment, or one read back from the journal. A
+    /// held fragment found invalid gets its sender reported, unless the
+    /// ledger only rejects it for spending the outputs of transactions this
+    /// node has still not seen. A held fragment is journaled once admitted.
+    ///
+    /// Returns what the fragment provides to the fragments waiting for it if
+    /// it was admitted in the pool.
+    fn readmit(
+        &mut self,
+        fragment_id: FragmentId,
+        held: Held,
+        received_at: SystemTime,
+        validation: &Validation,
+        logger: &Logger,
+    ) -> Option<Vec<Dependency>> {
+        match self.pool.insert_valid(
+            &held.fragment,
+            validation.ledger,
+            validation.ledger_params,
+            validation.block_date,
+            received_at,
+        ) {
+            Ok(Admission::Accepted(evicted)) => {
+                self.log_evicted(evicted, logger);
+                if !held.journaled {
+                    self.add_to_journal(held.origin, &held.fragment, logger);
+                }
+                Some(self.pool.provides(&fragment_id))
+            }
+            Ok(Admission::AlreadyPending) => None,
+            Ok(Admission::Missing(missing)) => {
+                let held = Held {
+                    spends_unseen: false,
+                    ..held
+                };
+                self.hold(fragment_id, held, received_at, missing);
+                None
+            }
+            Ok(Admission::UnknownInputs(_))
+                if held.sender.is_some() && !self.unseen_inputs(&held.fragment).is_empty() =>
+            {
+                let missing = self
+                    .unseen_inputs(&held.fragment)
+                    .into_iter()
+                    .map(Dependency::Transaction)
+                    .collect();
+                let held = Held {
+                    spends_unseen: true,
+                    ..held
+                };
+                self.hold(fragment_id, held, received_at, missing);
+                None
+            }
+            Ok(Admission::UnknownInputs(reason)) | Err(reason) => {
+                self.logs
+                    .modify(fragment_id, FragmentStatus::Rejected { reason });
+                self.misbehaving.extend(held.sender);
+                None
+            }
+        }
+    }
+
+    /// admit the fragments held until the given dependencies are pending,
+    /// then the ones held until those are pending, and so on.
+    ///
+    /// Returns the admitted fragments.
+    fn admit_orphans(
+        &mut self,
+        mut provided: Vec<Dependency>,
+        validation: &Validation,
+        logger: &Logger,
+    ) -> Vec<Fragment> {
+        let mut admitted = Vec::new();
+        while !provided.is_empty() {
+            let released = self.orphans.take_waiting_for(&provided);
+            provided = Vec::new();
+            for (id, held, received_at) in released {
+                let fragment = held.fragment.clone();
+                if let Some(more) = self.readmit(id, held, received_at, validation, logger) {
+                    debug!(
+                        logger,
+                        "held fragment admitted";
+                        "fragment_id" => %id,
+                    );
+                    provided.extend(more);
+                    admitted.push(fragment);
+                }
+            }
+        }
+        admitted
+    }
+
+    /// check again the held fragments against the ledger of the current tip,
+    /// as the blocks applied in the meantime may include the fragments they
+    /// were waiting for. The fragments held for longer than `ORPHAN_TTL` are
+    /// dropped, the peers that sent fragments spending the outputs of
+    /// transactions that were never received are reported.
+    pub async fn sweep_orphans(&mut self, logger: &Logger) -> Result<(), ()> {
+        if let Some(deadline) = SystemTime::now().checked_sub(ORPHAN_TTL) {
+            for (id, held, missing) in self.orphans.take_received_before(deadline) {
+                self.logs.modify(
+                    id,
+                    FragmentStatus::Rejected {
+                        reason: REJECTED_MISSING_DEPENDENCIES.to_owned(),
+                    },
+                );
+                let spends_unseen = missing
+                    .iter()
+                    .any(|dependency| matches!(dependency, Dependency::Transaction(_)));
+                if spends_unseen {
+                    self.misbehaving.extend(held.sender);
+                }
+            }
+        }
+        if self.orphans.is_empty() {
+            return self.report_misbehaving(logger).await;
+        }
+        debug!(
+            logger,
+            "checking {} held fragments again",
+            self.orphans.len()
+        );
+
+        let tip = self.tip.get_ref().await;
+        let ledger = tip.ledger();
+        let validation = Validation {
+            ledger: &ledger,
+            ledger_params: tip.epoch_ledger_parameters(),
+            block_date: tip.block_date(),
+        };
+
+        let mut admitted = Vec::new();
+        for (id, held, received_at) in self.orphans.take_all() {
+            let fragment = held.fragment.clone();
+            if let Some(provided) = self.readmit(id, held, received_at, &validation, logger) {
+                admitted.push(fragment);
+                admitted.extend(self.admit_orphans(provided, &validation, logger));
+            }
+        }
+        self.propagate(admitted, logger).await?;
+        self.report_misbehaving(logger).await
+    }
+
+    /// put back in the pool the pending fragments recorded in the journal,
+    /// checking them against the ledger of the current tip. The fragments
+    /// that became invalid are marked as rejected in the logs.
+    pub async fn restore(&mut self, entries: Vec<JournalEntry>, logger: &Logger) {
+        let (logs, fragments) = journal::replay(entries);
+        let fragments: Vec<(FragmentOrigin, Fragment)> = fragments
+            .iter()
+            .filter_map(|entry| {
+                let fragment = entry.to_fragment();
+                if fragment.is_none() {
+                    warn!(logger, "cannot decode a fragment of the mempool journal");
+                }
+                fragment
+            })
+            .collect();
+        self.logs.insert_all(logs);
+
+        let tip = self.tip.get_ref().await;
+        let ledger = tip.ledger();
+        let validation = Validation {
+            ledger: &ledger,
+            ledger_params: tip.epoch_ledger_parameters(),
+            block_date: tip.block_date(),
+        };
+
+        for (origin, fragment) in fragments {
+            let id = fragment.id();
+            let is_pending = self
+                .logs
+                .get(&id.into())
+                .map_or(true, |log| log.is_pending());
+            if !is_pending {
+                continue;
+            }
+            // the fragment keeps the time it was first received at, so it
+            // does not outlive its TTL across restarts
+            let received_at = match self.logs.get(&id.into()) {
+                Some(log) => *log.received_at(),
+                None => {
+                    let log = FragmentLog::new(id, origin);
+                    let received_at = *log.received_at();
+                    self.logs.insert(log);
+                    received_at
+                }
+            };
+            let held = Held {
+                origin,
+                sender: None,
+                fragment,
+                journaled: true,
+                spends_unseen: false,
+            };
+            if let Some(provided) = self.readmit(id, held, received_at, &validation, logger) {
+                self.admit_orphans(provided, &validation, logger);
+            }
+        }
+        info!(
+            logger,
+            "{} pending fragments restored from the mempool journal, {} waiting for the fragments they depend upon",
+            self.pool.fragment_ids_oldest_first().len(),
+            self.orphans.len(),
+        );
+
+        self.compact_journal(logger);
+    }
+
+    fn add_to_journal(&mut self, origin: FragmentOrigin, fragment: &Fragment, logger: &Logger) {
+        if self.journal.is_none() {
+            return;
+        }
+        match JournalEntry::fragment(origin, fragment) {
+            Ok(entry) => self.journal_fragments.push(entry),
+            Err(e) => {
+                error!(logger, "cannot add the fragment to the mempool journal"; "reason" => %e)
+            }
+        }
+    }
+
+    /// write in the journal the fragments admitted in the pool and the
+    /// logs modified since the last call
+    pub fn flush_journal(&mut self, logger: &Logger) {
+        let journal = match self.journal.as_mut() {
+            Some(journal) => journal,
+            None => return,
+        };
+        let entries = self
+            .journal_fragments
+            .drain(..)
+            .chain(self.logs.take_changes().into_iter().map(JournalEntry::Log));
+        if let Err(e) = journal.append(entries) {
+            error!(logger, "cannot write the mempool journal"; "reason" => %e);
+        }
+        if journal.appended() > JOURNAL_COMPACTION_THRESHOLD {
+            self.compact_journal(logger);
+        }
+    }
+
+    /// rewrite the journal with only the fragments currently in the pool, and
+    /// the current logs
+    fn compact_journal(&mut self, logger: &Logger) {
+        let journal = match self.journal.as_mut() {
+            Some(journal) => journal,
+            None => return,
+        };
+        let mut entries = Vec::new();
+        let pending = self.pool.fragment_ids_oldest_first();
+        let pending = pending.iter().filter_map(|id| {
+            let origin = self
+                .logs
+                .get(&id.into())
+                .map_or(FragmentOrigin::Network, |log| *log.received_from());
+            self.pool.get(id).map(|fragment| (origin, fragment))
+        });
+        for (origin, fragment) in pending {
+            match JournalEntry::fragment(origin, fragment) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    error!(logger, "cannot add the fragment to the mempool journal"; "reason" => %e)
+                }
+            }
+        }
+        entries.extend(self.logs.logs().cloned().map(JournalEntry::Log));
+        self.journal_fragments.clear();
+        self.logs.take_changes();
+
+        if let Err(e) = journal.rewrite(entries) {
+            error!(logger, "cannot rewrite the mempool journal"; "reason" => %e);
+        }
+    }
+
+    /// the pending fragments among `fragment_ids`, in the same order, or
+    /// the pending fragments from the oldest if no identifier is given, for
+    /// a peer catching up on the mempool. At most `MAX_FRAGMENTS_SERVED` are
+    /// returned.
+    pub fn get_all(&self, fragment_ids: Vec<FragmentId>) -> Vec<Fragment> {
+        let fragment_ids = if fragment_ids.is_empty() {
+            self.pool.fragment_ids_oldest_first()
+        } else {
+            fragment_ids
+        };
+        fragment_ids
+            .iter()
+            .filter_map(|fragment_id| self.pool.get(fragment_id).cloned())
+            .take(MAX_FRAGMENTS_SERVED)
+            .collect()
+    }
+
+    /// the transactions whose outputs are spent by the fragment that are
+    /// neither pending nor known to the logs
+    fn unseen_inputs(&self, fragment: &Fragment) -> Vec<FragmentId> {
+        internal::spent_transactions(fragment)
+            .into_iter()
+            .filter(|id| !self.pool.contains(id) && !self.logs.exists(*id))
+            .collect()
     }
 
     pub fn remove_added_to_block(&mut self, fragment_ids: Vec<FragmentId>, status: FragmentStatus) {
+        for fragment_id in fragment_ids.iter() {
+            self.orphans.remove(fragment_id);
+        }
         self.pool.remove_all(fragment_ids.iter().cloned());
         self.logs.modify_all(fragment_ids, status);
     }
 
+    /// remove a fragment from the pool on request of the node operator, along
+    /// with the fragments depending on it. Returns the identifiers of all the
+    /// removed fragments, `None` if the fragment is neither in the pool nor
+    /// held.
+    pub fn evict(&mut self, fragment_id: FragmentId) -> Option<Vec<FragmentId>> {
+        let reason = "evicted from the mempool by the node operator".to_owned();
+        if self.orphans.remove(&fragment_id).is_some() {
+            self.logs
+                .modify(fragment_id, FragmentStatus::Rejected { reason });
+            return Some(vec![fragment_id]);
+        }
+        if !self.pool.contains(&fragment_id) {
+            return None;
+        }
+        let descendants = self.pool.remove_with_descendants(&fragment_id);
+        self.logs
+            .modify(fragment_id, FragmentStatus::Rejected { reason });
+        for descendant in descendants.iter() {
+            self.logs.modify(
+                *descendant,
+                FragmentStatus::Rejected {
+                    reason: format!("depends on evicted fragment {}", fragment_id),
+                },
+            );
+        }
+        let mut evicted = vec![fragment_id];
+        evicted.extend(descendants);
+        Some(evicted)
+    }
+
+    /// the fragments currently in the pool, from the oldest to the most
+    /// recent, with the fee they pay according to the ledger of the tip
+    pub async fn pending(&self) -> PendingFragments {
+        let tip = self.tip.get_ref().await;
+        let fees = &tip.epoch_ledger_parameters().fees;
+        let mut entries = self.pool.entries(fees);
+        entries.sort_by_key(|entry| *entry.received_at());
+
+        let stats = MempoolStats {
+            count: entries.len(),
+            capacity: self.pool.capacity(),
+            total_size: entries.iter().map(|entry| *entry.fragment_size()).sum(),
+            total_fee: Value(entries.iter().fold(0u64, |total, entry| {
+                total.saturating_add(entry.fragment_fee().0)
+            }))
+            .into(),
+        };
+        let fragments = entries
+            .iter()
+            .map(|entry| {
+                let fragment_id = *entry.fragment_ref();
+                let received_from = self
+                    .logs
+                    .get(&fragment_id.into())
+                    .map_or(FragmentOrigin::Network, |log| *log.received_from());
+                PendingFragment {
+                    fragment_id: fragment_id.into(),
+                    received_from,
+                    received_at: (*entry.received_at()).into(),
+                    size: *entry.fragment_size(),
+                    fee: (*entry.fragment_fee()).into(),
+                    ancestors_fee: (*entry.ancestors_fee()).into(),
+                    ancestors_size: *entry.ancestors_size(),
+                }
+            })
+            .collect();
+        PendingFragments { stats, fragments }
+    }
+
+    /// the duration of a slot of the blockchain at the current tip
+    pub async fn slot_duration(&self) -> Duration {
+        let tip = self.tip.get_ref().await;
+        Duration::from_secs(tip.time_frame().slot_duration())
+    }
+
+    /// remove the fragments that have been pending for longer than `ttl`
+    /// and mark them as expired in the logs. The fragments depending on
+    /// them are removed as well as they cannot be applied anymore.
+    pub fn remove_expired(&mut self, ttl: Duration, logger: &Logger) {
+        let deadline = match SystemTime::now().checked_sub(ttl) {
+            Some(deadline) => deadline,
+            None => return,
+        };
+        let expired: HashSet<FragmentId> =
+            self.pool.received_before(deadline).into_iter().collect();
+        for id in expired.iter() {
+            for descendant in self.pool.remove_with_descendants(id) {
+                if !expired.contains(&descendant) {
+                    self.logs.modify(
+                        descendant,
+                        FragmentStatus::Rejected {
+                            reason: format!("depends on expired fragment {}", id),
+                        },
+                    );
+                }
+            }
+            self.logs.modify(*id, FragmentStatus::Expired);
+        }
+        let held = self.orphans.take_received_before(deadline);
+        for (id, _, _) in held.iter() {
+            self.logs.modify(*id, FragmentStatus::Expired);
+        }
+        // also catch the fragments that were dropped from the pool
+        // without being added in a block
+        let count = expired.len() + held.len() + self.logs.expire_pending_received_before(deadline);
+        if count > 0 {
+            debug!(logger, "{} pending fragments expired", count);
+        }
+    }
+
     pub fn select(
         &mut self,
         ledger: Ledger,
@@ -88,6 +814,11 @@ impl Pool {
                 selection_alg.select(&ledger, &ledger_params, block_date, logs, pool);
                 selection_alg.finalize()
             }
+            FragmentSelectionAlgorithmParams::FeeRate => {
+                let mut selection_alg = FeeRate::new();
+                selection_alg.select(&ledger, &ledger_params, block_date, logs, pool);
+                selection_alg.finalize()
+            }
         }
     }
 }
@@ -119,49 +850,778 @@ fn is_transaction_valid<E>(tx: &Transaction<E>) -> bool {
 
 pub(super) mod internal {
     use super::*;
+    use crate::{
+        blockcfg::Value,
+        fragment::{
+            orphans::{AccountKey, Dependency},
+            selection::{apply_package, rejection_reason},
+            PoolEntry,
+        },
+    };
+    use chain_crypto::{Ed25519, PublicKey, Verification};
+    use chain_impl_mockchain::{
+        account::{self, SpendingCounter},
+        fee::LinearFee,
+        ledger,
+        transaction::{InputEnum, Payload, TransactionSignDataHash, Witness, WitnessAccountData},
+        utxo,
+    };
     use lru::LruCache;
+    use std::collections::{BTreeMap, HashMap, HashSet};
+
+    /// how far ahead of the spending counter following the pending fragments
+    /// of an account the counter used by a fragment may be. Such a fragment
+    /// is held until the fragment using the counter in between is received.
+    /// The witness of an account input is verified against at most
+    /// `MAX_COUNTER_GAP + 1` counters.
+    pub const MAX_COUNTER_GAP: u32 = 1;
+
+    /// outcome of the admission of a fragment in the pool
+    pub enum Admission {
+        /// the fragment was added to the pool, the fragments evicted to
+        /// make room for it are given
+        Accepted(Vec<FragmentId>),
+        /// the fragment is already in the pool
+        AlreadyPending,
+        /// the fragment cannot be checked against the ledger before the
+        /// fragments it depends upon are received
+        Missing(Vec<Dependency>),
+        /// the ledger rejected the fragment for spending the outputs of a
+        /// transaction it does not have, after the checks preceding that
+        /// input passed. The reason of the rejection is given.
+        UnknownInputs(String),
+    }
+
+    /// bookkeeping of a pending fragment, including what it spends so the
+    /// other pending fragments it depends upon can be found
+    pub(super) struct Record {
+        /// time when the fragment was received
+        received_at: SystemTime,
+        /// accounts the fragment is spending from, with the spending counter
+        /// it uses for each of them
+        accounts: Vec<(AccountKey, u32)>,
+        /// transactions whose outputs are spent by the fragment
+        utxos: Vec<FragmentId>,
+    }
 
     pub struct Pool {
         entries: LruCache<FragmentId, Fragment>,
+        dependencies: Dependencies,
+    }
+
+    /// the dependencies between the pending fragments
+    #[derive(Default)]
+    pub(super) struct Dependencies {
+        records: HashMap<FragmentId, Record>,
+        /// pending fragments spending from an account, by spending counter
+        accounts: HashMap<AccountKey, BTreeMap<u32, FragmentId>>,
+        /// pending fragments spending the outputs of a given transaction,
+        /// whether that transaction is in the pool or not (yet)
+        utxo_spenders: HashMap<FragmentId, Vec<FragmentId>>,
     }
 
     impl Pool {
         pub fn new(max_entries: usize) -> Self {
             Pool {
                 entries: LruCache::new(max_entries),
+                dependencies: Dependencies::default(),
             }
         }
 
-        /// Returns clone of fragment if it was registered
-        pub fn insert(&mut self, fragment: Fragment) -> Option<Fragment> {
-            let fragment_id = fragment.id();
-            if self.entries.contains(&fragment_id) {
-                None
-            } else {
-                self.entries.put(fragment_id, fragment.clone());
-                Some(fragment)
+        pub fn contains(&self, fragment_id: &FragmentId) -> bool {
+            self.entries.contains(fragment_id)
+        }
+
+        /// maximum number of fragments the pool can hold
+        pub fn capacity(&self) -> usize {
+            self.entries.cap()
+        }
+
+        pub fn get(&self, fragment_id: &FragmentId) -> Option<&Fragment> {
+            self.entries.peek(fragment_id)
+        }
+
+        /// make room for a fragment whose ancestors are `protected`, by
+        /// removing the oldest fragment that is not one of them along with
+        /// its descendants. Returns the identifiers of the removed fragments,
+        /// `None` if the pool is full of protected fragments.
+        fn make_room(&mut self, protected: &HashSet<FragmentId>) -> Option<Vec<FragmentId>> {
+            if self.entries.len() < self.entries.cap() {
+                return Some(Vec::new());
             }
+            let oldest_first = self.fragment_ids_oldest_first();
+            let victim = eviction_victim(&oldest_first, protected)?;
+            let mut evicted = vec![victim];
+            evicted.extend(self.remove_with_descendants(&victim));
+            Some(evicted)
         }
 
-        /// Returns clones of registered fragments
-        pub fn insert_all(
+        /// Register the fragment if it can be applied on top of `ledger`
+        /// once the pending fragments it depends upon are applied.
+        ///
+        /// A fragment using a spending counter ahead of the one of the
+        /// account in the ledger depends on the fragments using the counters
+        /// in between. If those are not pending, the fragment is not checked
+        /// and the missing dependencies are returned instead.
+        ///
+        /// The fragment is checked along with its pending ancestors before
+        /// anything is removed from a full pool, the oldest fragment that is
+        /// not one of its ancestors is evicted then to make room.
+        ///
+        /// Returns the outcome of the admission, or the reason the ledger
+        /// rejected the fragment.
+        pub fn insert_valid(
             &mut self,
-            fragments: impl IntoIterator<Item = Fragment>,
-        ) -> Vec<Fragment> {
-            fragments
+            fragment: &Fragment,
+            ledger: &Ledger,
+            ledger_params: &LedgerParameters,
+            block_date: BlockDate,
+            received_at: SystemTime,
+        ) -> Result<Admission, String> {
+            let fragment_id = fragment.id();
+            if self.entries.contains(&fragment_id) {
+                return Ok(Admission::AlreadyPending);
+            }
+            let (record, required) =
+                fragment_record(fragment, ledger, &self.dependencies, received_at)?;
+            if let Some((counter, other)) = self.dependencies.counter_conflict(&record) {
+                return Err(format!(
+                    "spending counter {} is already used by pending fragment {}",
+                    counter, other
+                ));
+            }
+            let missing: Vec<Dependency> = required
                 .into_iter()
-                .filter_map(|fragment| self.insert(fragment))
-                .collect()
+                .filter(|dependency| !self.dependencies.is_pending(dependency))
+                .collect();
+            if !missing.is_empty() {
+                return Ok(Admission::Missing(missing));
+            }
+
+            // only the dependencies are registered while the package is
+            // checked, nothing is evicted before the fragment is accepted
+            self.dependencies.insert(fragment_id, record);
+            let ancestors = self.dependencies.ancestors(&fragment_id);
+            let mut package: Vec<(FragmentId, Fragment)> = ancestors
+                .iter()
+                .filter_map(|id| {
+                    self.entries
+                        .peek(id)
+                        .map(|fragment| (*id, fragment.clone()))
+                })
+                .collect();
+            package.push((fragment_id, fragment.clone()));
+            let mut ledger = ledger.clone();
+            let (_, rejected) = apply_package(&mut ledger, ledger_params, block_date, package);
+            if let Some((_, _, error)) = rejected.into_iter().find(|(id, _, _)| *id == fragment_id)
+            {
+                self.dependencies.remove(&fragment_id);
+                let reason = rejection_reason(&error);
+                return match error {
+                    ledger::Error::UtxoError(utxo::Error::TransactionNotFound) => {
+                        Ok(Admission::UnknownInputs(reason))
+                    }
+                    _ => Err(reason),
+                };
+            }
+
+            match self.make_room(&ancestors.into_iter().collect()) {
+                Some(evicted) => {
+                    self.entries.put(fragment_id, fragment.clone());
+                    Ok(Admission::Accepted(evicted))
+                }
+                None => {
+                    self.dependencies.remove(&fragment_id);
+                    Err("the mempool is full of the fragments it depends upon".to_owned())
+                }
+            }
+        }
+
+        /// what the pending fragment provides to the fragments waiting for it
+        pub fn provides(&self, fragment_id: &FragmentId) -> Vec<Dependency> {
+            self.dependencies.provided_by(fragment_id)
+        }
+
+        pub fn remove(&mut self, fragment_id: &FragmentId) -> Option<Fragment> {
+            let fragment = self.entries.pop(fragment_id)?;
+            self.dependencies.remove(fragment_id);
+            Some(fragment)
         }
 
         pub fn remove_all(&mut self, fragment_ids: impl IntoIterator<Item = FragmentId>) {
             for fragment_id in fragment_ids {
-                self.entries.pop(&fragment_id);
+                self.remove(&fragment_id);
             }
         }
 
-        pub fn remove_oldest(&mut self) -> Option<Fragment> {
-            self.entries.pop_lru().map(|(_, value)| value)
+        /// remove the fragment and every pending fragment depending on it,
+        /// as none of them can be applied anymore. Returns the identifiers
+        /// of the removed descendants.
+        pub fn remove_with_descendants(&mut self, fragment_id: &FragmentId) -> Vec<FragmentId> {
+            let descendants = self.dependencies.descendants(fragment_id);
+            self.remove(fragment_id);
+            for descendant in descendants.iter() {
+                self.remove(descendant);
+            }
+            descendants
         }
+
+        /// identifiers of the fragments received before `deadline`
+        pub fn received_before(&self, deadline: SystemTime) -> Vec<FragmentId> {
+            self.dependencies.received_before(deadline)
+        }
+
+        /// identifiers of the fragments of the pool, from the oldest to the
+        /// most recently inserted one
+        pub fn fragment_ids_oldest_first(&self) -> Vec<FragmentId> {
+            let mut ids: Vec<FragmentId> = self.entries.iter().map(|(id, _)| *id).collect();
+            ids.reverse();
+            ids
+        }
+
+        /// the fragment along with all the pending fragments it depends
+        /// upon, in the order they are expected to be applied to the ledger.
+        pub fn package(&self, fragment_id: &FragmentId) -> Vec<(FragmentId, Fragment)> {
+            let mut package_ids = self.dependencies.ancestors(fragment_id);
+            package_ids.push(*fragment_id);
+            package_ids
+                .into_iter()
+                .filter_map(|id| {
+                    self.entries
+                        .peek(&id)
+                        .map(|fragment| (id, fragment.clone()))
+                })
+                .collect()
+        }
+
+        /// the entries of the pool, with the fee and size of their pending
+        /// ancestors and descendants accounted for
+        pub fn entries(&self, fees: &LinearFee) -> Vec<PoolEntry> {
+            let mut entries: HashMap<FragmentId, PoolEntry> = self
+                .entries
+                .iter()
+                .map(|(id, fragment)| {
+                    let received_at = self
+                        .dependencies
+                        .received_at(id)
+                        .unwrap_or_else(SystemTime::now);
+                    (*id, PoolEntry::new(fragment, fees, received_at))
+                })
+                .collect();
+            let relatives: Vec<_> = entries
+                .keys()
+                .map(|id| {
+                    let ancestors = total_of(&entries, self.dependencies.ancestors(id));
+                    let descendants = total_of(&entries, self.dependencies.descendants(id));
+                    (*id, ancestors, descendants)
+                })
+                .collect();
+            for (id, (ancestors_fee, ancestors_size), (descendants_fee, descendants_size)) in
+                relatives
+            {
+                if let Some(entry) = entries.get_mut(&id) {
+                    entry.set_ancestors(ancestors_fee, ancestors_size);
+                    entry.set_descendants(descendants_fee, descendants_size);
+                }
+            }
+            entries.into_iter().map(|(_, entry)| entry).collect()
+        }
+    }
+
+    impl Record {
+        #[cfg(test)]
+        pub(super) fn new(accounts: Vec<(AccountKey, u32)>, utxos: Vec<FragmentId>) -> Self {
+            Record {
+                received_at: SystemTime::now(),
+                accounts,
+                utxos,
+            }
+        }
+    }
+
+    impl Dependencies {
+        pub(super) fn insert(&mut self, fragment_id: FragmentId, record: Record) {
+            for (account, counter) in record.accounts.iter() {
+                self.accounts
+                    .entry(*account)
+                    .or_default()
+                    .insert(*counter, fragment_id);
+            }
+            for utxo in record.utxos.iter() {
+                self.utxo_spenders
+                    .entry(*utxo)
+                    .or_default()
+                    .push(fragment_id);
+            }
+            self.records.insert(fragment_id, record);
+        }
+
+        pub(super) fn remove(&mut self, fragment_id: &FragmentId) {
+            let record = match self.records.remove(fragment_id) {
+                Some(record) => record,
+                None => return,
+            };
+            for (account, counter) in record.accounts {
+                if let Some(chain) = self.accounts.get_mut(&account) {
+                    if chain.get(&counter) == Some(fragment_id) {
+                        chain.remove(&counter);
+                    }
+                    if chain.is_empty() {
+                        self.accounts.remove(&account);
+                    }
+                }
+            }
+            for utxo in record.utxos {
+                if let Some(ids) = self.utxo_spenders.get_mut(&utxo) {
+                    ids.retain(|id| id != fragment_id);
+                    if ids.is_empty() {
+                        self.utxo_spenders.remove(&utxo);
+                    }
+                }
+            }
+        }
+
+        fn received_at(&self, fragment_id: &FragmentId) -> Option<SystemTime> {
+            self.records
+                .get(fragment_id)
+                .map(|record| record.received_at)
+        }
+
+        fn received_before(&self, deadline: SystemTime) -> Vec<FragmentId> {
+            self.records
+                .iter()
+                .filter(|(_, record)| record.received_at < deadline)
+                .map(|(id, _)| *id)
+                .collect()
+        }
+
+        /// the pending fragment using the given spending counter of the
+        /// account
+        fn using_counter(&self, account: &AccountKey, counter: u32) -> Option<FragmentId> {
+            self.accounts.get(account)?.get(&counter).cloned()
+        }
+
+        /// the spending counter following the ones used by the pending
+        /// fragments of the account, from the counter `first` of the account
+        /// in the ledger
+        pub(super) fn next_counter(&self, account: &AccountKey, first: u32) -> u32 {
+            let mut next = first;
+            if let Some(chain) = self.accounts.get(account) {
+                while chain.contains_key(&next) {
+                    next = next.saturating_add(1);
+                }
+            }
+            next
+        }
+
+        /// a spending counter used by the record that is already used by
+        /// another pending fragment
+        pub(super) fn counter_conflict(&self, record: &Record) -> Option<(u32, FragmentId)> {
+            record.accounts.iter().find_map(|(account, counter)| {
+                self.using_counter(account, *counter)
+                    .map(|other| (*counter, other))
+            })
+        }
+
+        pub(super) fn is_pending(&self, dependency: &Dependency) -> bool {
+            match dependency {
+                Dependency::Counter(account, counter) => {
+                    self.using_counter(account, *counter).is_some()
+                }
+                Dependency::Transaction(fragment_id) => self.records.contains_key(fragment_id),
+            }
+        }
+
+        pub(super) fn provided_by(&self, fragment_id: &FragmentId) -> Vec<Dependency> {
+            let record = match self.records.get(fragment_id) {
+                Some(record) => record,
+                None => return Vec::new(),
+            };
+            record
+                .accounts
+                .iter()
+                .map(|(account, counter)| Dependency::Counter(*account, *counter))
+                .chain(std::iter::once(Dependency::Transaction(*fragment_id)))
+                .collect()
+        }
+
+        /// the pending fragments that need to be applied before this one:
+        /// the ones using the previous spending counter of the accounts it
+        /// spends from, and the ones whose outputs it spends
+        fn parents(&self, fragment_id: &FragmentId) -> Vec<FragmentId> {
+            let record = match self.records.get(fragment_id) {
+                Some(record) => record,
+                None => return Vec::new(),
+            };
+            let previous_from_account = record.accounts.iter().filter_map(|(account, counter)| {
+                let previous = counter.checked_sub(1)?;
+                self.using_counter(account, previous)
+            });
+            let spent_utxos = record
+                .utxos
+                .iter()
+                .filter(|utxo| self.records.contains_key(utxo))
+                .cloned();
+            previous_from_account.chain(spent_utxos).collect()
+        }
+
+        /// the pending fragments that can only be applied after this one
+        fn children(&self, fragment_id: &FragmentId) -> Vec<FragmentId> {
+            let record = match self.records.get(fragment_id) {
+                Some(record) => record,
+                None => return Vec::new(),
+            };
+            let next_from_account = record.accounts.iter().filter_map(|(account, counter)| {
+                let next = counter.checked_add(1)?;
+                self.using_counter(account, next)
+            });
+            let utxo_spenders = self
+                .utxo_spenders
+                .get(fragment_id)
+                .into_iter()
+                .flatten()
+                .cloned();
+            next_from_account.chain(utxo_spenders).collect()
+        }
+
+        /// all the pending ancestors of the fragment, ordered so that every
+        /// fragment comes after the fragments it depends upon
+        pub(super) fn ancestors(&self, fragment_id: &FragmentId) -> Vec<FragmentId> {
+            fn visit(
+                dependencies: &Dependencies,
+                fragment_id: &FragmentId,
+                visited: &mut HashSet<FragmentId>,
+                ordered: &mut Vec<FragmentId>,
+            ) {
+                for parent in dependencies.parents(fragment_id) {
+                    if visited.insert(parent) {
+                        visit(dependencies, &parent, visited, ordered);
+                        ordered.push(parent);
+                    }
+                }
+            }
+
+            let mut visited = HashSet::new();
+            visited.insert(*fragment_id);
+            let mut ordered = Vec::new();
+            visit(self, fragment_id, &mut visited, &mut ordered);
+            ordered
+        }
+
+        /// all the pending fragments depending, directly or not, on this one
+        pub(super) fn descendants(&self, fragment_id: &FragmentId) -> Vec<FragmentId> {
+            let mut visited = HashSet::new();
+            visited.insert(*fragment_id);
+            let mut descendants = Vec::new();
+            let mut to_visit = self.children(fragment_id);
+            while let Some(child) = to_visit.pop() {
+                if visited.insert(child) {
+                    to_visit.extend(self.children(&child));
+                    descendants.push(child);
+                }
+            }
+            descendants
+        }
+    }
+
+    /// the oldest fragment that is not protected from eviction
+    pub(super) fn eviction_victim(
+        oldest_first: &[FragmentId],
+        protected: &HashSet<FragmentId>,
+    ) -> Option<FragmentId> {
+        oldest_first
+            .iter()
+            .find(|id| !protected.contains(id))
+            .cloned()
+    }
+
+    fn total_of(entries: &HashMap<FragmentId, PoolEntry>, ids: Vec<FragmentId>) -> (Value, usize) {
+        let relatives: Vec<&PoolEntry> = ids.iter().filter_map(|id| entries.get(id)).collect();
+        let fee = Value::sum(relatives.iter().map(|entry| *entry.fragment_fee()))
+            .unwrap_or(Value(u64::MAX));
+        let size = relatives.iter().map(|entry| *entry.fragment_size()).sum();
+        (fee, size)
+    }
+
+    /// the transactions whose outputs are spent by the fragment
+    pub fn spent_transactions(fragment: &Fragment) -> Vec<FragmentId> {
+        fn spent<P: Payload>(tx: &Transaction<P>) -> Vec<FragmentId> {
+            tx.as_slice()
+                .inputs()
+                .iter()
+                .filter_map(|input| match input.to_enum() {
+                    InputEnum::UtxoInput(pointer) => Some(pointer.transaction_id),
+                    InputEnum::AccountInput(..) => None,
+                })
+                .collect()
+        }
+
+        match fragment {
+            Fragment::Transaction(ref tx) => spent(tx),
+            Fragment::StakeDelegation(ref tx) => spent(tx),
+            Fragment::OwnerStakeDelegation(ref tx) => spent(tx),
+            Fragment::PoolRegistration(ref tx) => spent(tx),
+            Fragment::PoolRetirement(ref tx) => spent(tx),
+            Fragment::PoolUpdate(ref tx) => spent(tx),
+            Fragment::VotePlan(ref tx) => spent(tx),
+            Fragment::VoteCast(ref tx) => spent(tx),
+            Fragment::VoteTally(ref tx) => spent(tx),
+            _ => Vec::new(),
+        }
+    }
+
+    /// the record of the fragment, along with the pending fragments it
+    /// requires: the ones using the spending counters preceding its own when
+    /// they are ahead of the counters of the accounts in `ledger`
+    fn fragment_record(
+        fragment: &Fragment,
+        ledger: &Ledger,
+        dependencies: &Dependencies,
+        received_at: SystemTime,
+    ) -> Result<(Record, Vec<Dependency>), String> {
+        let mut record = Record {
+            received_at,
+            accounts: Vec::new(),
+            utxos: Vec::new(),
+        };
+        let mut required = Vec::new();
+        match fragment {
+            Fragment::Transaction(ref tx) => {
+                record_inputs(&mut record, &mut required, tx, ledger, dependencies)
+            }
+            Fragment::StakeDelegation(ref tx) => {
+                record_inputs(&mut record, &mut required, tx, ledger, dependencies)
+            }
+            Fragment::OwnerStakeDelegation(ref tx) => {
+                record_inputs(&mut record, &mut required, tx, ledger, dependencies)
+            }
+            Fragment::PoolRegistration(ref tx) => {
+                record_inputs(&mut record, &mut required, tx, ledger, dependencies)
+            }
+            Fragment::PoolRetirement(ref tx) => {
+                record_inputs(&mut record, &mut required, tx, ledger, dependencies)
+            }
+            Fragment::PoolUpdate(ref tx) => {
+                record_inputs(&mut record, &mut required, tx, ledger, dependencies)
+            }
+            Fragment::VotePlan(ref tx) => {
+                record_inputs(&mut record, &mut required, tx, ledger, dependencies)
+            }
+            Fragment::VoteCast(ref tx) => {
+                record_inputs(&mut record, &mut required, tx, ledger, dependencies)
+            }
+            Fragment::VoteTally(ref tx) => {
+                record_inputs(&mut record, &mut required, tx, ledger, dependencies)
+            }
+            _ => Ok(()),
+        }?;
+        Ok((record, required))
+    }
+
+    fn record_inputs<P: Payload>(
+        record: &mut Record,
+        required: &mut Vec<Dependency>,
+        tx: &Transaction<P>,
+        ledger: &Ledger,
+        dependencies: &Dependencies,
+    ) -> Result<(), String> {
+        let tx = tx.as_slice();
+        let sign_data_hash = tx.transaction_sign_data_hash();
+        for (input, witness) in tx.inputs().iter().zip(tx.witnesses().iter()) {
+            match input.to_enum() {
+                InputEnum::AccountInput(account, _) => {
+                    // the spending counter used by a multisig account cannot
+                    // be told from its witness, such fragments are not chained
+                    let (identifier, signature) = match (account.to_single_account(), witness) {
+                        (Some(identifier), Witness::Account(signature)) => (identifier, signature),
+                        _ => continue,
+                    };
+                    let first = ledger
+                        .accounts()
+                        .get_state(&identifier)
+                        .map_or(0, |state| state.get_counter());
+                    let account: AccountKey = account.into();
+                    let next = dependencies.next_counter(&account, first);
+                    let counter =
+                        spending_counter(ledger, &sign_data_hash, identifier, &signature, next)?;
+                    if counter > first {
+                        required.push(Dependency::Counter(account, counter - 1));
+                    }
+                    record.accounts.push((account, counter));
+                }
+                InputEnum::UtxoInput(pointer) => record.utxos.push(pointer.transaction_id),
+            }
+        }
+        Ok(())
+    }
+
+    /// the spending counter the account witness was made with, looked for
+    /// from the counter `next` following the pending fragments of the
+    /// account up to `MAX_COUNTER_GAP` counters ahead. A witness made with
+    /// any other counter is invalid, or uses a counter already used.
+    fn spending_counter(
+        ledger: &Ledger,
+        sign_data_hash: &TransactionSignDataHash,
+        identifier: account::Identifier,
+        signature: &account::Witness,
+        next: u32,
+    ) -> Result<u32, String> {
+        let block0 = ledger.get_static_parameters().block0_initial_hash;
+        let public_key: PublicKey<Ed25519> = identifier.into();
+        let last = next.saturating_add(MAX_COUNTER_GAP);
+        (next..=last)
+            .find(|counter| {
+                let data = WitnessAccountData::new(
+                    &block0,
+                    sign_data_hash,
+                    SpendingCounter::from(*counter),
+                );
+                signature.verify(&public_key, &data) == Verification::Success
+            })
+            .ok_or_else(|| {
+                format!(
+                    "the account witness does not match any spending counter from {} to {}",
+                    next, last
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::internal::{eviction_victim, Dependencies, Record};
+    use super::{
+        is_rejected_as_invalid, REJECTED_ALREADY_RECEIVED, REJECTED_TOO_MANY_SPENDING_UNSEEN,
+    };
+    use crate::fragment::{orphans::Dependency, FragmentId};
+    use jormungandr_lib::interfaces::FragmentSubmission;
+    use std::collections::HashSet;
+
+    fn id(n: u8) -> FragmentId {
+        FragmentId::hash_bytes(&[n])
+    }
+
+    const ACCOUNT: [u8; 32] = [1; 32];
+    const OTHER_ACCOUNT: [u8; 32] = [2; 32];
+
+    #[test]
+    fn account_fragments_are_chained_by_spending_counter() {
+        let mut dependencies = Dependencies::default();
+        // received in the reverse order of their spending counters
+        dependencies.insert(id(2), Record::new(vec![(ACCOUNT, 2)], vec![]));
+        dependencies.insert(id(1), Record::new(vec![(ACCOUNT, 1)], vec![]));
+        dependencies.insert(id(0), Record::new(vec![(ACCOUNT, 0)], vec![]));
+
+        assert_eq!(dependencies.ancestors(&id(2)), vec![id(0), id(1)]);
+        assert_eq!(dependencies.ancestors(&id(0)), vec![]);
+        let descendants: HashSet<FragmentId> =
+            dependencies.descendants(&id(0)).into_iter().collect();
+        assert_eq!(descendants, vec![id(1), id(2)].into_iter().collect());
+    }
+
+    #[test]
+    fn gap_in_spending_counters_breaks_the_chain() {
+        let mut dependencies = Dependencies::default();
+        dependencies.insert(id(0), Record::new(vec![(ACCOUNT, 0)], vec![]));
+        dependencies.insert(id(2), Record::new(vec![(ACCOUNT, 2)], vec![]));
+
+        assert!(dependencies.ancestors(&id(2)).is_empty());
+        assert!(dependencies.is_pending(&Dependency::Counter(ACCOUNT, 0)));
+        assert!(!dependencies.is_pending(&Dependency::Counter(ACCOUNT, 1)));
+
+        dependencies.insert(id(1), Record::new(vec![(ACCOUNT, 1)], vec![]));
+        assert_eq!(dependencies.ancestors(&id(2)), vec![id(0), id(1)]);
+    }
+
+    #[test]
+    fn fragments_spending_pending_outputs_depend_on_them() {
+        let mut dependencies = Dependencies::default();
+        dependencies.insert(id(0), Record::new(vec![(ACCOUNT, 0)], vec![]));
+        dependencies.insert(id(1), Record::new(vec![(OTHER_ACCOUNT, 5)], vec![id(0)]));
+        // spends the output of a transaction that is not pending
+        dependencies.insert(id(2), Record::new(vec![], vec![id(9)]));
+
+        assert_eq!(dependencies.ancestors(&id(1)), vec![id(0)]);
+        assert_eq!(dependencies.descendants(&id(0)), vec![id(1)]);
+        assert!(dependencies.ancestors(&id(2)).is_empty());
+    }
+
+    #[test]
+    fn removed_fragment_leaves_the_chain() {
+        let mut dependencies = Dependencies::default();
+        dependencies.insert(id(0), Record::new(vec![(ACCOUNT, 0)], vec![]));
+        dependencies.insert(id(1), Record::new(vec![(ACCOUNT, 1)], vec![]));
+        dependencies.insert(id(2), Record::new(vec![(ACCOUNT, 2)], vec![]));
+
+        dependencies.remove(&id(1));
+        assert!(dependencies.ancestors(&id(2)).is_empty());
+        assert!(dependencies.descendants(&id(0)).is_empty());
+        assert!(dependencies.provided_by(&id(1)).is_empty());
+        assert_eq!(
+            dependencies.provided_by(&id(2)),
+            vec![
+                Dependency::Counter(ACCOUNT, 2),
+                Dependency::Transaction(id(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn next_counter_follows_the_pending_chain() {
+        let mut dependencies = Dependencies::default();
+        assert_eq!(dependencies.next_counter(&ACCOUNT, 2), 2);
+        dependencies.insert(id(0), Record::new(vec![(ACCOUNT, 2)], vec![]));
+        dependencies.insert(id(1), Record::new(vec![(ACCOUNT, 3)], vec![]));
+        dependencies.insert(id(2), Record::new(vec![(ACCOUNT, 5)], vec![]));
+        assert_eq!(dependencies.next_counter(&ACCOUNT, 2), 4);
+        assert_eq!(dependencies.next_counter(&OTHER_ACCOUNT, 2), 2);
+    }
+
+    #[test]
+    fn spending_counter_used_twice_is_a_conflict() {
+        let mut dependencies = Dependencies::default();
+        dependencies.insert(id(0), Record::new(vec![(ACCOUNT, 3)], vec![]));
+
+        let record = Record::new(vec![(OTHER_ACCOUNT, 3), (ACCOUNT, 3)], vec![]);
+        assert_eq!(dependencies.counter_conflict(&record), Some((3, id(0))));
+        let record = Record::new(vec![(ACCOUNT, 4)], vec![]);
+        assert_eq!(dependencies.counter_conflict(&record), None);
+    }
+
+    #[test]
+    fn oldest_fragment_is_evicted() {
+        let oldest_first = vec![id(0), id(1), id(2)];
+        assert_eq!(eviction_victim(&oldest_first, &HashSet::new()), Some(id(0)));
+    }
+
+    #[test]
+    fn ancestors_of_the_admitted_fragment_are_not_evicted() {
+        let mut dependencies = Dependencies::default();
+        dependencies.insert(id(0), Record::new(vec![(ACCOUNT, 0)], vec![]));
+        dependencies.insert(id(1), Record::new(vec![(OTHER_ACCOUNT, 0)], vec![]));
+        dependencies.insert(id(2), Record::new(vec![(ACCOUNT, 1)], vec![]));
+
+        let protected = dependencies.ancestors(&id(2)).into_iter().collect();
+        assert_eq!(eviction_victim(&[id(0), id(1)], &protected), Some(id(1)));
+        assert_eq!(eviction_victim(&[id(0)], &protected), None);
+    }
+
+    #[test]
+    fn only_invalid_fragments_count_against_the_sender() {
+        let rejected = |reason: &str| FragmentSubmission::rejected(Some(id(0)), reason.to_owned());
+        assert!(is_rejected_as_invalid(&rejected("invalid signature")));
+        assert!(!is_rejected_as_invalid(&rejected(
+            REJECTED_ALREADY_RECEIVED
+        )));
+        assert!(!is_rejected_as_invalid(&rejected(
+            REJECTED_TOO_MANY_SPENDING_UNSEEN
+        )));
+        assert!(!is_rejected_as_invalid(&FragmentSubmission::held(
+            id(0),
+            "held".to_owned()
+        )));
+        assert!(!is_rejected_as_invalid(&FragmentSubmission::accepted(id(
+            0
+        ))));
     }
 }
@@ -1,5 +1,7 @@
 use crate::{
-    fragment::{Logs, Pool},
+    blockchain::Tip,
+    events::Events,
+    fragment::{Journal, JournalEntry, Logs, Pool},
     intercom::{NetworkMsg, TransactionMsg},
     stats_counter::StatsCounter,
     utils::{
@@ -7,21 +9,43 @@ use crate::{
         task::TokioServiceInfo,
     },
 };
+use futures::{future::FutureExt, sink::SinkExt};
+use std::time::Duration;
 use tokio::stream::StreamExt;
 
+/// interval at which the pool is checked for expired fragments, and the
+/// held fragments are checked again
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
 pub struct Process {
     pool: Pool,
+    fragment_ttl: Option<Duration>,
+    fragment_ttl_slots: Option<u32>,
+    /// entries of the mempool journal to restore before starting
+    journal_entries: Vec<JournalEntry>,
 }
 
 impl Process {
     pub fn new(
         pool_max_entries: usize,
         logs_max_entries: usize,
+        fragment_ttl: Option<Duration>,
+        fragment_ttl_slots: Option<u32>,
+        journal: Option<(Journal, Vec<JournalEntry>)>,
         network_msg_box: MessageBox<NetworkMsg>,
+        tip: Tip,
+        events: Events,
     ) -> Self {
-        let logs = Logs::new(logs_max_entries);
+        let logs = Logs::new(logs_max_entries, events);
+        let (journal, journal_entries) = match journal {
+            Some((journal, entries)) => (Some(journal), entries),
+            None => (None, Vec::new()),
+        };
         Process {
-            pool: Pool::new(pool_max_entries, logs, network_msg_box),
+            pool: Pool::new(pool_max_entries, logs, network_msg_box, tip, journal),
+            fragment_ttl,
+            fragment_ttl_slots,
+            journal_entries,
         }
     }
 
@@ -32,26 +56,58 @@ impl Process {
         mut input: MessageQueue<TransactionMsg>,
     ) -> Result<(), ()> {
         let mut pool = self.pool;
+        pool.restore(self.journal_entries, service_info.logger())
+            .await;
 
-        while let Some(input_result) = input.next().await {
-            match input_result {
-                TransactionMsg::SendTransaction(origin, txs) => {
-                    // Note that we cannot use apply_block here, since we don't have a valid context to which to apply
-                    // those blocks. one valid tx in a given context, could be invalid in another. for example
-                    // fee calculations, existence utxo / account solvency.
+        let mut expiry_sweep = tokio::time::interval(EXPIRY_SWEEP_INTERVAL);
 
-                    // FIXME/TODO check that the txs are valid within themselves with basic requirements (e.g. inputs >= outputs).
-                    // we also want to keep a basic capability to filter away repetitive queries or definitely discarded txid.
+        loop {
+            let input_result = futures::select! {
+                input_result = input.next().fuse() => match input_result {
+                    Some(input_result) => input_result,
+                    None => break,
+                },
+                _ = expiry_sweep.tick().fuse() => {
+                    let ttl = match self.fragment_ttl_slots {
+                        Some(slots) => {
+                            let slot_duration = pool.slot_duration().await;
+                            effective_ttl(self.fragment_ttl, Some(slots), slot_duration)
+                        }
+                        None => self.fragment_ttl,
+                    };
+                    if let Some(ttl) = ttl {
+                        pool.remove_expired(ttl, service_info.logger());
+                    }
+                    pool.sweep_orphans(service_info.logger()).await?;
+                    pool.flush_journal(service_info.logger());
+                    continue;
+                },
+            };
+
+            match input_result {
+                TransactionMsg::SendTransaction(origin, sender, txs, reply_handle) => {
+                    // The fragments are checked against the ledger of the current tip (along with the
+                    // pending fragments they depend upon) before being admitted in the pool. A fragment
+                    // valid now may still become invalid by the time a block is built, so the selection
+                    // applies them again.
 
                     // This interface only makes sense for messages coming from arbitrary users (like transaction, certificates),
                     // for other message we don't want to receive them through this interface, and possibly
                     // put them in another pool.
 
-                    let stats_counter = stats_counter.clone();
-
-                    pool.insert_and_propagate_all(origin, txs, service_info.logger().clone())
-                        .await
-                        .map(move |count| stats_counter.add_tx_recv_cnt(count))?;
+                    let submissions = pool
+                        .insert_and_propagate_all(
+                            origin,
+                            sender,
+                            txs,
+                            service_info.logger().clone(),
+                        )
+                        .await?;
+                    let count = submissions.iter().filter(|s| s.is_accepted()).count();
+                    stats_counter.add_tx_recv_cnt(count);
+                    if let Some(reply_handle) = reply_handle {
+                        reply_handle.reply_ok(submissions);
+                    }
                 }
                 TransactionMsg::RemoveTransactions(fragment_ids, status) => {
                     pool.remove_added_to_block(fragment_ids, status);
@@ -60,6 +116,34 @@ impl Process {
                     let logs = pool.logs().logs().cloned().collect();
                     reply_handle.reply_ok(logs);
                 }
+                TransactionMsg::GetStatuses(fragment_ids, reply_handle) => {
+                    let logs = pool.logs().get_all(fragment_ids).cloned().collect();
+                    reply_handle.reply_ok(logs);
+                }
+                TransactionMsg::GetPending(reply_handle) => {
+                    reply_handle.reply_ok(pool.pending().await);
+                }
+                TransactionMsg::GetFragments(fragment_ids, handle) => {
+                    let fragments = pool.get_all(fragment_ids);
+                    service_info.spawn_fallible("get fragments", async move {
+                        let mut sink = handle.start_sending();
+                        for fragment in fragments {
+                            sink.send(Ok(fragment)).await?;
+                        }
+                        sink.close().await
+                    });
+                }
+                TransactionMsg::EvictTransaction(fragment_id, reply_handle) => {
+                    let evicted = pool.evict(fragment_id);
+                    if let Some(evicted) = evicted.as_ref() {
+                        info!(
+                            service_info.logger(),
+                            "{} fragments evicted from the pool", evicted.len();
+                            "fragment_id" => %fragment_id,
+                        );
+                    }
+                    reply_handle.reply_ok(evicted);
+                }
                 TransactionMsg::SelectTransactions {
                     ledger,
                     block_date,
@@ -71,8 +155,52 @@ impl Process {
                     reply_handle.reply_ok(contents);
                 }
             }
+
+            pool.flush_journal(service_info.logger());
         }
 
         Ok(())
     }
 }
+
+/// the time after which a pending fragment expires, the earliest of the
+/// wall-clock TTL and of the TTL in slots.
+///
+/// The TTL in slots is converted to a wall-clock duration with the slot
+/// duration of the current tip, it is not counted in block dates: if the slot
+/// duration changes while a fragment is pending, the slots it already waited
+/// are counted with the new duration.
+fn effective_ttl(
+    ttl: Option<Duration>,
+    ttl_slots: Option<u32>,
+    slot_duration: Duration,
+) -> Option<Duration> {
+    let ttl_slots = ttl_slots.map(|slots| slot_duration * slots);
+    match (ttl, ttl_slots) {
+        (Some(ttl), Some(ttl_slots)) => Some(ttl.min(ttl_slots)),
+        (ttl, ttl_slots) => ttl.or(ttl_slots),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::effective_ttl;
+    use std::time::Duration;
+
+    #[test]
+    fn fragments_expire_at_the_earliest_ttl() {
+        let slot = Duration::from_secs(2);
+        let minute = Duration::from_secs(60);
+        assert_eq!(effective_ttl(None, None, slot), None);
+        assert_eq!(effective_ttl(Some(minute), None, slot), Some(minute));
+        assert_eq!(
+            effective_ttl(None, Some(10), slot),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            effective_ttl(Some(minute), Some(10), slot),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(effective_ttl(Some(minute), Some(100), slot), Some(minute));
+    }
+}
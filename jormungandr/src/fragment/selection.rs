@@ -1,11 +1,14 @@
+use super::entry::PoolEntry;
 use super::logs::Logs;
 use super::pool::internal::Pool;
 use crate::{
     blockcfg::{BlockDate, Contents, ContentsBuilder, Ledger, LedgerParameters},
-    fragment::FragmentId,
+    fragment::{Fragment, FragmentId},
 };
 use chain_core::property::Fragment as _;
-use jormungandr_lib::interfaces::FragmentStatus;
+use chain_impl_mockchain::ledger;
+use jormungandr_lib::interfaces::{FragmentSelection, FragmentStatus};
+use std::cmp::Ordering;
 
 pub enum SelectionOutput {
     Commit { fragment_id: FragmentId },
@@ -27,28 +30,36 @@ pub trait FragmentSelectionAlgorithm {
     fn finalize(self) -> Contents;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum FragmentSelectionAlgorithmParams {
     OldestFirst,
+    FeeRate,
+}
+
+impl From<FragmentSelection> for FragmentSelectionAlgorithmParams {
+    fn from(selection: FragmentSelection) -> Self {
+        match selection {
+            FragmentSelection::OldestFirst => FragmentSelectionAlgorithmParams::OldestFirst,
+            FragmentSelection::FeeRate => FragmentSelectionAlgorithmParams::FeeRate,
+        }
+    }
 }
 
 pub struct OldestFirst {
-    builder: ContentsBuilder,
-    current_total_size: u32,
+    contents: BlockContents,
 }
 
 impl OldestFirst {
     pub fn new() -> Self {
         OldestFirst {
-            builder: ContentsBuilder::new(),
-            current_total_size: 0,
+            contents: BlockContents::new(),
         }
     }
 }
 
 impl FragmentSelectionAlgorithm for OldestFirst {
     fn finalize(self) -> Contents {
-        self.builder.into()
+        self.contents.builder.into()
     }
 
     fn select(
@@ -61,35 +72,275 @@ impl FragmentSelectionAlgorithm for OldestFirst {
     ) {
         let mut ledger_simulation = ledger.clone();
 
-        while let Some(fragment) = pool.remove_oldest() {
-            let id = fragment.id();
-            let fragment_raw = fragment.to_raw(); // TODO: replace everything to FragmentRaw in the node
-            let fragment_size = fragment_raw.size_bytes_plus_size() as u32;
-            let total_size = self.current_total_size + fragment_size;
-
-            if total_size <= ledger_params.block_content_max_size {
-                match ledger_simulation.apply_fragment(ledger_params, &fragment, block_date) {
-                    Ok(ledger_new) => {
-                        self.builder.push(fragment);
-                        ledger_simulation = ledger_new;
-                    }
-                    Err(error) => {
-                        use std::error::Error as _;
-                        let error = if let Some(source) = error.source() {
-                            format!("{}: {}", error, source)
-                        } else {
-                            error.to_string()
-                        };
-                        logs.modify(id, FragmentStatus::Rejected { reason: error })
-                    }
-                }
+        for id in pool.fragment_ids_oldest_first() {
+            // the fragment may have already been selected or evicted
+            // as part of the package of another fragment
+            if !pool.contains(&id) {
+                continue;
+            }
+
+            self.contents.select_package(
+                &mut ledger_simulation,
+                ledger_params,
+                block_date,
+                logs,
+                pool,
+                &id,
+            );
+
+            if self.contents.is_full(ledger_params) {
+                break;
+            }
+        }
+    }
+}
+
+/// select the fragments paying the highest fee per byte first.
+///
+/// The fee of every fragment is computed from the fee settings of the
+/// ledger the block is built on. A fragment is ranked by the fee rate of
+/// its package: the fragment along with the pending fragments it depends
+/// upon. Packages that do not fit in the remaining space of the block are
+/// left in the pool for a later block.
+pub struct FeeRate {
+    contents: BlockContents,
+}
+
+impl FeeRate {
+    pub fn new() -> Self {
+        FeeRate {
+            contents: BlockContents::new(),
+        }
+    }
+}
+
+impl FragmentSelectionAlgorithm for FeeRate {
+    fn finalize(self) -> Contents {
+        self.contents.builder.into()
+    }
+
+    fn select(
+        &mut self,
+        ledger: &Ledger,
+        ledger_params: &LedgerParameters,
+        block_date: BlockDate,
+        logs: &mut Logs,
+        pool: &mut Pool,
+    ) {
+        let mut ledger_simulation = ledger.clone();
+
+        let mut entries = pool.entries(&ledger_params.fees);
+        entries.sort_by(fee_rate_order);
+
+        for entry in entries {
+            let id = *entry.fragment_ref();
+            if !pool.contains(&id) {
+                continue;
+            }
+
+            self.contents.select_package(
+                &mut ledger_simulation,
+                ledger_params,
+                block_date,
+                logs,
+                pool,
+                &id,
+            );
+
+            if self.contents.is_full(ledger_params) {
+                break;
+            }
+        }
+    }
+}
+
+/// the order in which `FeeRate` selects the packages: the highest fee per
+/// byte first, then the oldest first, as `OldestFirst` does
+fn fee_rate_order(a: &PoolEntry, b: &PoolEntry) -> Ordering {
+    b.cmp_package_fee_rate(a)
+        .then_with(|| a.received_at().cmp(b.received_at()))
+}
+
+/// the contents of the block being built
+struct BlockContents {
+    builder: ContentsBuilder,
+    current_total_size: u32,
+}
+
+impl BlockContents {
+    fn new() -> Self {
+        BlockContents {
+            builder: ContentsBuilder::new(),
+            current_total_size: 0,
+        }
+    }
+
+    fn is_full(&self, ledger_params: &LedgerParameters) -> bool {
+        self.current_total_size >= ledger_params.block_content_max_size
+    }
+
+    /// add the fragment and the pending fragments it depends upon to the
+    /// block, if they fit in the remaining space.
+    ///
+    /// Fragments the ledger rejects are evicted from the pool along with
+    /// every fragment depending on them.
+    fn select_package(
+        &mut self,
+        ledger: &mut Ledger,
+        ledger_params: &LedgerParameters,
+        block_date: BlockDate,
+        logs: &mut Logs,
+        pool: &mut Pool,
+        fragment_id: &FragmentId,
+    ) {
+        let package = pool.package(fragment_id);
+        let package_size: u32 = package
+            .iter()
+            .map(|(_, fragment)| fragment.to_raw().size_bytes_plus_size() as u32)
+            .sum();
+
+        if self.current_total_size + package_size > ledger_params.block_content_max_size {
+            return;
+        }
+
+        let (applied, rejected) = apply_package(ledger, ledger_params, block_date, package);
+
+        for (id, fragment) in applied {
+            self.current_total_size += fragment.to_raw().size_bytes_plus_size() as u32;
+            self.builder.push(fragment);
+            pool.remove(&id);
+        }
 
-                self.current_total_size = total_size;
+        for (id, _, error) in rejected {
+            let reason = rejection_reason(&error);
+            for descendant in pool.remove_with_descendants(&id) {
+                logs.modify(
+                    descendant,
+                    FragmentStatus::Rejected {
+                        reason: format!("depends on rejected fragment {}", id),
+                    },
+                );
+            }
+            logs.modify(id, FragmentStatus::Rejected { reason });
+        }
+    }
+}
 
-                if total_size == ledger_params.block_content_max_size {
-                    break;
+/// apply the fragments of a package to the ledger, in order.
+///
+/// The fragments of a package spending from the same account are ordered by
+/// spending counter, the ones failing are still tried again as long as others
+/// could be applied, in case the ledger moved on since they were admitted.
+///
+/// Returns the fragments that were applied and the fragments that were
+/// rejected along with the error of the ledger.
+#[allow(clippy::type_complexity)]
+pub(super) fn apply_package(
+    ledger: &mut Ledger,
+    ledger_params: &LedgerParameters,
+    block_date: BlockDate,
+    package: Vec<(FragmentId, Fragment)>,
+) -> (
+    Vec<(FragmentId, Fragment)>,
+    Vec<(FragmentId, Fragment, ledger::Error)>,
+) {
+    let mut applied = Vec::new();
+    let mut pending = package;
+    let mut rejected = Vec::new();
+    loop {
+        let mut progress = false;
+        for (id, fragment) in pending.drain(..) {
+            match ledger.apply_fragment(ledger_params, &fragment, block_date) {
+                Ok(ledger_new) => {
+                    *ledger = ledger_new;
+                    applied.push((id, fragment));
+                    progress = true;
                 }
+                Err(error) => rejected.push((id, fragment, error)),
             }
         }
+
+        if rejected.is_empty() || !progress {
+            return (applied, rejected);
+        }
+        pending = rejected
+            .drain(..)
+            .map(|(id, fragment, _)| (id, fragment))
+            .collect();
+    }
+}
+
+pub(super) fn rejection_reason(error: &impl std::error::Error) -> String {
+    if let Some(source) = error.source() {
+        format!("{}: {}", error, source)
+    } else {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::Value;
+    use std::time::{Duration, SystemTime};
+
+    fn id(n: u8) -> FragmentId {
+        FragmentId::hash_bytes(&[n])
+    }
+
+    /// an entry received `age` seconds before the others
+    fn entry(n: u8, fee: u64, size: usize, age: u64) -> PoolEntry {
+        let received_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1000 - age);
+        PoolEntry::with_fee(id(n), fee, size, received_at)
+    }
+
+    fn sorted(mut entries: Vec<PoolEntry>) -> Vec<FragmentId> {
+        entries.sort_by(fee_rate_order);
+        entries.iter().map(|entry| *entry.fragment_ref()).collect()
+    }
+
+    #[test]
+    fn highest_fee_rate_first() {
+        let entries = vec![
+            entry(0, 100, 100, 3),
+            entry(1, 300, 100, 2),
+            entry(2, 200, 100, 1),
+        ];
+        assert_eq!(sorted(entries), vec![id(1), id(2), id(0)]);
+    }
+
+    #[test]
+    fn ties_are_selected_oldest_first() {
+        // the same fee per byte over different sizes
+        let entries = vec![
+            entry(0, 200, 200, 1),
+            entry(1, 100, 100, 3),
+            entry(2, 300, 300, 2),
+        ];
+        assert_eq!(sorted(entries), vec![id(1), id(2), id(0)]);
+    }
+
+    #[test]
+    fn child_is_ranked_by_its_package() {
+        // the parent pays little, its child pays for both of them
+        let parent = entry(0, 10, 100, 3);
+        let mut child = entry(1, 990, 100, 2);
+        child.set_ancestors(Value(10), 100);
+        let standalone = entry(2, 400, 100, 1);
+
+        // the package of the child, 5 per byte, goes before the standalone
+        // fragment, which goes before the parent on its own
+        assert_eq!(
+            sorted(vec![parent, child, standalone]),
+            vec![id(1), id(2), id(0)]
+        );
+    }
+
+    #[test]
+    fn package_tie_is_selected_oldest_first() {
+        let mut child = entry(0, 300, 100, 1);
+        child.set_ancestors(Value(100), 100);
+        let standalone = entry(1, 200, 100, 2);
+        assert_eq!(sorted(vec![child, standalone]), vec![id(1), id(0)]);
     }
 }
@@ -3,11 +3,13 @@ use crate::blockcfg::{
 };
 use crate::blockchain::{Checkpoints, StorageError};
 use crate::fragment::selection::FragmentSelectionAlgorithmParams;
-use crate::network::p2p::{comm::PeerInfo, Address};
+use crate::network::p2p::{comm::PeerInfo, Address, Behavior};
 use crate::utils::async_msg::{self, MessageBox, MessageQueue};
 use chain_impl_mockchain::fragment::Contents as FragmentContents;
 use chain_network::error as net_error;
-use jormungandr_lib::interfaces::{FragmentLog, FragmentOrigin, FragmentStatus};
+use jormungandr_lib::interfaces::{
+    FragmentLog, FragmentOrigin, FragmentStatus, FragmentSubmission, PendingFragments,
+};
 
 use futures::channel::{mpsc, oneshot};
 use futures::prelude::*;
@@ -512,9 +514,26 @@ pub fn stream_request<T, R>(
 /// ...
 #[derive(Debug)]
 pub enum TransactionMsg {
-    SendTransaction(FragmentOrigin, Vec<Fragment>),
+    /// submit fragments to the mempool, along with the address of the peer
+    /// they were received from if they come from the network. The outcome
+    /// of the admission of each fragment is sent back if a reply handle is
+    /// given
+    SendTransaction(
+        FragmentOrigin,
+        Option<Address>,
+        Vec<Fragment>,
+        Option<ReplyHandle<Vec<FragmentSubmission>>>,
+    ),
     RemoveTransactions(Vec<FragmentId>, FragmentStatus),
     GetLogs(ReplyHandle<Vec<FragmentLog>>),
+    GetStatuses(Vec<FragmentId>, ReplyHandle<Vec<FragmentLog>>),
+    GetPending(ReplyHandle<PendingFragments>),
+    /// the pending fragments with the given identifiers, the fragments
+    /// that are not in the pool are skipped
+    GetFragments(Vec<FragmentId>, ReplyStreamHandle<Fragment>),
+    /// remove a fragment and the fragments depending on it from the pool,
+    /// replies with the removed fragments or `None` if it was not pending
+    EvictTransaction(FragmentId, ReplyHandle<Option<Vec<FragmentId>>>),
     SelectTransactions {
         ledger: Ledger,
         block_date: BlockDate,
@@ -594,12 +613,17 @@ pub enum NetworkMsg {
     Propagate(PropagateMsg),
     GetBlocks(Vec<HeaderHash>),
     GetNextBlock(Address, HeaderHash),
+    /// fetch the fragments the mempool has not seen from the peer that sent
+    /// fragments depending on them
+    GetFragments(Address, Vec<FragmentId>),
     PullHeaders {
         node_address: Address,
         from: Checkpoints,
         to: HeaderHash,
     },
     PeerInfo(ReplyHandle<Vec<PeerInfo>>),
+    /// update the reputation of a peer
+    ReportPeer(Address, Behavior),
 }
 
 /// Messages to the explorer task
@@ -1,3 +1,4 @@
+use crate::events::{Event, Events};
 pub use jormungandr_lib::interfaces::LeadershipLogStatus;
 use jormungandr_lib::interfaces::{LeadershipLog, LeadershipLogId};
 use std::sync::Arc;
@@ -6,7 +7,10 @@ use tokio::sync::RwLock;
 /// all leadership logs, allow for following up on the different entity
 /// of the blockchain
 #[derive(Clone)]
-pub struct Logs(Arc<RwLock<internal::Logs>>);
+pub struct Logs {
+    inner: Arc<RwLock<internal::Logs>>,
+    events: Events,
+}
 
 /// leadership log handle. will allow to update the status of the log
 /// without having to hold the [`Logs`]
@@ -55,13 +59,18 @@ impl Logs {
     /// create a Leadership Logs. Logs will be removed once the `Logs` passed
     /// beyond a certain number of entries.
     ///
-    pub fn new(cap: usize) -> Self {
-        Logs(Arc::new(RwLock::new(internal::Logs::new(cap))))
+    /// Every new log and every update of a log is notified to `events`.
+    pub fn new(cap: usize, events: Events) -> Self {
+        Logs {
+            inner: Arc::new(RwLock::new(internal::Logs::new(cap))),
+            events,
+        }
     }
 
     pub async fn insert(&self, log: LeadershipLog) -> Result<LeadershipLogHandle, ()> {
         let logs = self.clone();
-        let id = logs.0.write().await.insert(log);
+        self.events.notify(Event::LeadershipLog(log.clone()));
+        let id = logs.inner.write().await.insert(log);
         Ok(LeadershipLogHandle {
             internal_id: id,
             logs,
@@ -69,23 +78,28 @@ impl Logs {
     }
 
     async fn mark_wake(&self, leadership_log_id: LeadershipLogId) {
-        let inner = self.0.clone();
-        inner.write().await.mark_wake(&leadership_log_id);
+        let mut inner = self.inner.write().await;
+        self.notify(inner.mark_wake(&leadership_log_id));
     }
 
     async fn set_status(&self, leadership_log_id: LeadershipLogId, status: LeadershipLogStatus) {
-        let inner = self.0.clone();
-        inner.write().await.set_status(&leadership_log_id, status);
+        let mut inner = self.inner.write().await;
+        self.notify(inner.set_status(&leadership_log_id, status));
     }
 
     async fn mark_finished(&self, leadership_log_id: LeadershipLogId) {
-        let inner = self.0.clone();
-        inner.write().await.mark_finished(&leadership_log_id);
+        let mut inner = self.inner.write().await;
+        self.notify(inner.mark_finished(&leadership_log_id));
+    }
+
+    fn notify(&self, updated: Option<&LeadershipLog>) {
+        if let Some(log) = updated {
+            self.events.notify(Event::LeadershipLog(log.clone()));
+        }
     }
 
     pub async fn logs(&self) -> Vec<LeadershipLog> {
-        let inner = self.0.clone();
-        let guard = inner.read().await;
+        let guard = self.inner.read().await;
         guard.logs().cloned().collect()
     }
 }
@@ -112,26 +126,29 @@ pub(super) mod internal {
             id
         }
 
-        pub fn mark_wake(&mut self, leadership_log_id: &LeadershipLogId) {
-            if let Some(ref mut log) = self.entries.get_mut(leadership_log_id) {
-                log.mark_wake();
-            }
+        pub fn mark_wake(&mut self, leadership_log_id: &LeadershipLogId) -> Option<&LeadershipLog> {
+            let log = self.entries.get_mut(leadership_log_id)?;
+            log.mark_wake();
+            Some(log)
         }
 
         pub fn set_status(
             &mut self,
             leadership_log_id: &LeadershipLogId,
             status: LeadershipLogStatus,
-        ) {
-            if let Some(ref mut log) = self.entries.get_mut(leadership_log_id) {
-                log.set_status(status);
-            }
+        ) -> Option<&LeadershipLog> {
+            let log = self.entries.get_mut(leadership_log_id)?;
+            log.set_status(status);
+            Some(log)
         }
 
-        pub fn mark_finished(&mut self, leadership_log_id: &LeadershipLogId) {
-            if let Some(ref mut log) = self.entries.get_mut(leadership_log_id) {
-                log.mark_finished();
-            }
+        pub fn mark_finished(
+            &mut self,
+            leadership_log_id: &LeadershipLogId,
+        ) -> Option<&LeadershipLog> {
+            let log = self.entries.get_mut(leadership_log_id)?;
+            log.mark_finished();
+            Some(log)
         }
 
         pub fn logs<'a>(&'a self) -> impl Iterator<Item = &'a LeadershipLog> {
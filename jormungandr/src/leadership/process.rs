@@ -3,7 +3,8 @@ use crate::{
         Block, BlockDate, BlockVersion, Contents, HeaderBuilderNew, LeaderOutput, Leadership,
         Ledger, LedgerParameters,
     },
-    blockchain::{new_epoch_leadership_from, Ref, Tip},
+    blockchain::{new_epoch_leadership_from, Ref, RewardsReport, Tip},
+    fragment::selection::FragmentSelectionAlgorithmParams,
     intercom::{unary_reply, BlockMsg, Error as IntercomError, TransactionMsg},
     leadership::{
         enclave::{Enclave, EnclaveError, LeaderEvent},
@@ -72,6 +73,7 @@ pub struct Module {
     pool: MessageBox<TransactionMsg>,
     enclave: Enclave,
     block_message: MessageBox<BlockMsg>,
+    selection_alg: FragmentSelectionAlgorithmParams,
 }
 
 impl Module {
@@ -82,6 +84,7 @@ impl Module {
         pool: MessageBox<TransactionMsg>,
         enclave: Enclave,
         block_message: MessageBox<BlockMsg>,
+        selection_alg: FragmentSelectionAlgorithmParams,
     ) -> Result<Self, LeadershipError> {
         let tip_ref = tip.get_ref().await;
 
@@ -94,6 +97,7 @@ impl Module {
             pool,
             enclave,
             block_message,
+            selection_alg,
         })
     }
 
@@ -422,8 +426,15 @@ impl Module {
             return Ok(());
         };
 
-        let contents =
-            prepare_block(pool, event.date, ledger, ledger_parameters, logger.clone()).await?;
+        let contents = prepare_block(
+            pool,
+            event.date,
+            ledger,
+            ledger_parameters,
+            self.selection_alg,
+            logger.clone(),
+        )
+        .await?;
 
         let event_logs_error = event_logs.clone();
         let signing = {
@@ -530,10 +541,10 @@ impl Module {
         ));
 
         if epoch_tip < current_slot_position.epoch {
-            let (_, leadership, _, _, _, _) = new_epoch_leadership_from(
+            let (_, leadership, _, _, _, _, _) = new_epoch_leadership_from(
                 current_slot_position.epoch.0,
                 Arc::clone(&self.tip_ref),
-                false,
+                &RewardsReport::default(),
             );
 
             let slot_start = current_slot_position.slot.0 + 1;
@@ -636,17 +647,16 @@ async fn prepare_block(
     block_date: BlockDate,
     ledger: Arc<Ledger>,
     epoch_parameters: Arc<LedgerParameters>,
+    selection_alg: FragmentSelectionAlgorithmParams,
     logger: Logger,
 ) -> Result<Contents, LeadershipError> {
-    use crate::fragment::selection::FragmentSelectionAlgorithmParams;
-
     let (reply_handle, reply_future) = unary_reply(logger.clone());
 
     let msg = TransactionMsg::SelectTransactions {
         ledger: ledger.as_ref().clone(),
         block_date,
         ledger_params: epoch_parameters.as_ref().clone(),
-        selection_alg: FragmentSelectionAlgorithmParams::OldestFirst,
+        selection_alg,
         reply_handle,
     };
 
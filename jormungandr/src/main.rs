@@ -20,7 +20,7 @@ extern crate slog_syslog;
 
 use crate::{
     blockcfg::{HeaderHash, Leader},
-    blockchain::Blockchain,
+    blockchain::{Blockchain, RewardsReport},
     diagnostic::Diagnostic,
     secure::enclave::Enclave,
     settings::start::Settings,
@@ -28,7 +28,7 @@ use crate::{
 };
 use futures::{executor::block_on, future::TryFutureExt};
 use jormungandr_lib::interfaces::NodeState;
-use settings::{start::RawSettings, CommandLine};
+use settings::{start::RawSettings, Command, CommandLine, StorageCommand};
 use slog::Logger;
 use tokio::signal::ctrl_c;
 
@@ -39,6 +39,7 @@ pub mod blockcfg;
 pub mod blockchain;
 pub mod client;
 pub mod diagnostic;
+pub mod events;
 pub mod explorer;
 pub mod fragment;
 pub mod intercom;
@@ -57,7 +58,13 @@ pub mod utils;
 use stats_counter::StatsCounter;
 
 fn start() -> Result<(), start_up::Error> {
-    let initialized_node = initialize_node()?;
+    let mut command_line = CommandLine::load();
+
+    if let Some(command) = command_line.command.take() {
+        return run_command(command, command_line);
+    }
+
+    let initialized_node = initialize_node(command_line)?;
 
     let bootstrapped_node = bootstrap(initialized_node)?;
 
@@ -71,6 +78,7 @@ pub struct BootstrappedNode {
     block0_hash: HeaderHash,
     logger: Logger,
     explorer_db: Option<explorer::ExplorerDB>,
+    peer_db: Option<network::p2p::peer_db::PeerDb>,
     rest_context: Option<rest::ContextLock>,
     services: Services,
 }
@@ -80,6 +88,8 @@ const FRAGMENT_TASK_QUEUE_LEN: usize = 1024;
 const NETWORK_TASK_QUEUE_LEN: usize = 32;
 const EXPLORER_TASK_QUEUE_LEN: usize = 32;
 const CLIENT_TASK_QUEUE_LEN: usize = 32;
+const EVENT_QUEUE_LEN: usize = 1024;
+const BLOCKCHAIN_CACHE_CAPACITY: usize = 102_400;
 const BOOTSTRAP_RETRY_WAIT: Duration = Duration::from_secs(5);
 
 fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::Error> {
@@ -101,17 +111,30 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
     let (client_msgbox, client_queue) = async_msg::channel(CLIENT_TASK_QUEUE_LEN);
     let blockchain_tip = bootstrapped_node.blockchain_tip;
     let blockchain = bootstrapped_node.blockchain;
-    let leadership_logs =
-        leadership::Logs::new(bootstrapped_node.settings.leadership.logs_capacity);
+    let events = events::Events::new(EVENT_QUEUE_LEN);
+    let leadership_logs = leadership::Logs::new(
+        bootstrapped_node.settings.leadership.logs_capacity,
+        events.clone(),
+    );
 
     let stats_counter = StatsCounter::default();
 
     {
         let stats_counter = stats_counter.clone();
+        let journal = open_mempool_journal(&bootstrapped_node.settings, &bootstrapped_node.logger)?;
         let process = fragment::Process::new(
             bootstrapped_node.settings.mempool.pool_max_entries.into(),
             bootstrapped_node.settings.mempool.log_max_entries.into(),
+            bootstrapped_node
+                .settings
+                .mempool
+                .fragment_ttl
+                .map(Into::into),
+            bootstrapped_node.settings.mempool.fragment_ttl_slots,
+            journal,
             network_msgbox.clone(),
+            blockchain_tip.clone(),
+            events.clone(),
         );
 
         services.spawn_try_future("fragment", move |info| {
@@ -148,9 +171,9 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         let network_msgbox = network_msgbox.clone();
         let fragment_msgbox = fragment_msgbox.clone();
         let explorer_msgbox = explorer.as_ref().map(|(msg_box, _context)| msg_box.clone());
-        // TODO: we should get this value from the configuration
-        let block_cache_ttl: Duration = Duration::from_secs(120);
+        let branch_gc = bootstrapped_node.settings.branch_gc.clone();
         let stats_counter = stats_counter.clone();
+        let events = events.clone();
         services.spawn_future("block", move |info| {
             let process = blockchain::Process {
                 blockchain,
@@ -159,7 +182,9 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
                 network_msgbox,
                 fragment_msgbox,
                 explorer_msgbox,
-                garbage_collection_interval: block_cache_ttl,
+                events,
+                garbage_collection_interval: branch_gc.interval.into(),
+                branch_max_lag: branch_gc.max_lag,
             };
             process.start(info, block_queue)
         });
@@ -181,6 +206,7 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         bootstrapped_node.block0_hash,
         bootstrapped_node.settings.network.clone(),
         stats_counter.clone(),
+        bootstrapped_node.peer_db.clone(),
         bootstrapped_node
             .logger
             .new(o!(crate::log::KEY_TASK => "network")),
@@ -227,6 +253,11 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         let blockchain_tip = blockchain_tip.clone();
         let enclave = leadership::Enclave::new(enclave.clone());
         let fragment_msgbox = fragment_msgbox.clone();
+        let selection_alg = bootstrapped_node
+            .settings
+            .mempool
+            .selection_algorithm
+            .into();
 
         services.spawn_try_future("leadership", move |info| {
             let fut = leadership::Module::new(
@@ -236,6 +267,7 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
                 fragment_msgbox,
                 enclave,
                 block_msgbox,
+                selection_alg,
             )
             .and_then(|module| module.run())
             .map_err(|e| {
@@ -255,6 +287,7 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
             enclave,
             network_state,
             explorer: explorer.as_ref().map(|(_msg_box, context)| context.clone()),
+            events,
         };
         block_on(async {
             let mut rest_context = rest_context.write().await;
@@ -304,6 +337,35 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
     }
 }
 
+/// open the mempool journal in the storage directory, if the mempool is
+/// configured to be persisted
+fn open_mempool_journal(
+    settings: &Settings,
+    logger: &Logger,
+) -> Result<Option<(fragment::Journal, Vec<fragment::JournalEntry>)>, start_up::Error> {
+    if !settings.mempool.persistent {
+        return Ok(None);
+    }
+    let dir = match &settings.storage {
+        Some(dir) => dir,
+        None => {
+            warn!(
+                logger,
+                "the mempool is configured to be persistent but no storage directory is set, the mempool will not be persisted"
+            );
+            return Ok(None);
+        }
+    };
+    let (journal, entries, invalid) = fragment::Journal::open(dir)?;
+    if invalid > 0 {
+        warn!(
+            logger,
+            "{} entries of the mempool journal could not be read", invalid
+        );
+    }
+    Ok(Some((journal, entries)))
+}
+
 /// # Bootstrap phase
 ///
 /// done at every startup: we need to bootstrap from whatever local state (including nothing)
@@ -329,6 +391,7 @@ fn bootstrap(initialized_node: InitializedNode) -> Result<BootstrappedNode, star
         blockchain_tip,
         block0_hash,
         explorer_db,
+        peer_db,
         rest_context,
         settings,
     } = services.block_on_task("bootstrap", |info| {
@@ -348,6 +411,7 @@ fn bootstrap(initialized_node: InitializedNode) -> Result<BootstrappedNode, star
         blockchain_tip,
         logger,
         explorer_db,
+        peer_db,
         rest_context,
         services,
     })
@@ -358,6 +422,7 @@ struct BootstrapData {
     blockchain_tip: blockchain::Tip,
     block0_hash: HeaderHash,
     explorer_db: Option<explorer::ExplorerDB>,
+    peer_db: Option<network::p2p::peer_db::PeerDb>,
     rest_context: Option<rest::ContextLock>,
     settings: Settings,
 }
@@ -385,13 +450,16 @@ async fn bootstrap_internal(
 
     let block0_explorer = block0.clone();
 
-    let cache_capacity = 102_400;
+    let snapshots = start_up::prepare_ledger_snapshots(&settings, &logger);
+    let rewards_report = start_up::prepare_rewards_report(&settings, &logger);
+    let peer_db = start_up::prepare_peer_db(&settings, &logger);
 
     let (blockchain, blockchain_tip) = start_up::load_blockchain(
         block0,
         storage,
-        cache_capacity,
-        settings.rewards_report_all,
+        BLOCKCHAIN_CACHE_CAPACITY,
+        rewards_report,
+        snapshots,
         &logger,
     )
     .await?;
@@ -423,6 +491,24 @@ async fn bootstrap_internal(
             .map(|result| result.into_inner().0)
             .shared();
 
+    if let Some(fast_sync) = &settings.fast_sync {
+        let synced = network::fast_sync(
+            &settings.network,
+            fast_sync,
+            blockchain.clone(),
+            blockchain_tip.clone(),
+            bootstrap_stopper.clone(),
+            &logger,
+        )
+        .await?;
+        if !synced {
+            warn!(
+                &logger,
+                "fast sync from the ledger snapshot failed, bootstrapping from the current tip"
+            );
+        }
+    }
+
     loop {
         bootstrap_attempt += 1;
 
@@ -441,6 +527,7 @@ async fn bootstrap_internal(
         // Will return true if we successfully bootstrap or there are no trusted peers defined.
         if network::bootstrap(
             &settings.network,
+            peer_db.as_ref(),
             blockchain.clone(),
             blockchain_tip.clone(),
             bootstrap_stopper.clone(),
@@ -491,6 +578,7 @@ async fn bootstrap_internal(
         blockchain,
         blockchain_tip,
         explorer_db,
+        peer_db,
         rest_context,
         settings,
     })
@@ -505,9 +593,7 @@ pub struct InitializedNode {
     pub services: Services,
 }
 
-fn initialize_node() -> Result<InitializedNode, start_up::Error> {
-    let command_line = CommandLine::load();
-
+fn initialize_node(command_line: CommandLine) -> Result<InitializedNode, start_up::Error> {
     if command_line.full_version {
         println!("{}", env!("FULL_VERSION"));
         std::process::exit(0);
@@ -607,6 +693,66 @@ fn initialize_node() -> Result<InitializedNode, start_up::Error> {
     })
 }
 
+/// run one of the commands working on the storage instead of starting the node
+fn run_command(command: Command, command_line: CommandLine) -> Result<(), start_up::Error> {
+    use std::io::{self, BufWriter};
+
+    let raw_settings = RawSettings::load(command_line)?;
+    let logger = raw_settings.log_settings().to_logger()?;
+    let mut services = Services::new(logger);
+
+    match command {
+        Command::ExportBlocks(args) => services.block_on_task("export_blocks", |info| async move {
+            let logger = info.logger();
+            let storage = start_up::open_storage(&args.storage, logger)?;
+            let stdout = io::stdout();
+            let count = start_up::export_blocks(
+                &storage,
+                args.from,
+                args.to,
+                BufWriter::new(stdout.lock()),
+            )
+            .await?;
+            info!(logger, "exported {} blocks", count);
+            Ok(())
+        }),
+        Command::ImportBlocks(args) => services.block_on_task("import_blocks", |info| async move {
+            let logger = info.logger();
+            let storage = start_up::open_storage(&args.storage, logger)?;
+            let block0 = start_up::load_block_0(&args.block_0, &storage).await?;
+            let (blockchain, blockchain_tip) = start_up::load_blockchain(
+                block0,
+                storage,
+                BLOCKCHAIN_CACHE_CAPACITY,
+                RewardsReport::default(),
+                None,
+                logger,
+            )
+            .await?;
+            let stdin = io::stdin();
+            let count =
+                start_up::import_blocks(blockchain, blockchain_tip, stdin.lock(), logger).await?;
+            info!(logger, "imported {} blocks", count);
+            Ok(())
+        }),
+        Command::Storage(StorageCommand::Verify(args)) => {
+            services.block_on_task("verify_storage", |info| async move {
+                let logger = info.logger();
+                let storage = start_up::open_storage(&args.storage, logger)?;
+                let block0 = start_up::load_block_0(&args.block_0, &storage).await?;
+                let blockchain = Blockchain::new(
+                    block0.header.hash(),
+                    storage,
+                    BLOCKCHAIN_CACHE_CAPACITY,
+                    RewardsReport::default(),
+                    None,
+                );
+                start_up::verify_storage(blockchain, block0, args.repair, logger).await
+            })
+        }
+    }
+}
+
 fn main() {
     use std::error::Error;
 
@@ -1,13 +1,23 @@
 use super::grpc;
-use crate::blockcfg::{Block, HeaderDesc, HeaderHash};
-use crate::blockchain::{self, Blockchain, Error as BlockchainError, PreCheckedHeader, Ref, Tip};
-use crate::settings::start::network::Peer;
+use crate::blockcfg::{Block, Header, HeaderDesc, HeaderHash};
+use crate::blockchain::{
+    self, Blockchain, ComparisonResult, Error as BlockchainError, HeaderChainVerifyError,
+    PreCheckedHeader, Ref, Snapshot, SnapshotError, StorageError, Tip,
+};
+use crate::settings::start::{
+    config::FastSync,
+    network::{Configuration, Peer},
+};
 use chain_core::property::{Deserialize, HasHeader};
+use chain_crypto::Blake2b256;
 use chain_network::data as net_data;
 use chain_network::error::Error as NetworkError;
 use futures::{future::Either, prelude::*, stream, task::Poll};
+use jormungandr_lib::crypto::hash::Hash;
 use slog::Logger;
 
+use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::fmt::Debug;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -26,6 +36,8 @@ pub enum Error {
     PullStreamFailed(#[source] NetworkError),
     #[error("decoding of a block failed")]
     BlockDecodingFailed(#[source] <Block as Deserialize>::Error),
+    #[error("decoding of a header failed")]
+    HeaderDecodingFailed(#[source] <Header as Deserialize>::Error),
     #[error("block header check failed")]
     HeaderCheckFailed(#[source] BlockchainError),
     #[error(
@@ -42,11 +54,44 @@ pub enum Error {
     ChainSelectionFailed(#[source] BlockchainError),
     #[error("the bootstrap process was interrupted")]
     Interrupted,
+    #[error("failed to download the ledger snapshot")]
+    SnapshotDownloadFailed(#[source] reqwest::Error),
+    #[error("the ledger snapshot is larger than the maximum size of {0} bytes")]
+    SnapshotTooLarge(u64),
+    #[error("the decoding of the ledger snapshot was aborted")]
+    SnapshotDecodeAborted(#[source] tokio::task::JoinError),
+    #[error("the ledger snapshot download failed with status {0}")]
+    SnapshotDownloadStatus(reqwest::StatusCode),
+    #[error("the hash of the ledger snapshot is {got}, expected {expected}")]
+    SnapshotHashMismatch { expected: Hash, got: Hash },
+    #[error("invalid ledger snapshot")]
+    SnapshotInvalid(#[source] SnapshotError),
+    #[error("received block {0} does not follow the previously received block")]
+    BlockLinkInvalid(HeaderHash, #[source] HeaderChainVerifyError),
+    #[error("the content of received block {0} does not match its header")]
+    BlockContentInvalid(HeaderHash),
+    #[error("received block {0} is not the next block leading to the ledger snapshot")]
+    BlockNotExpected(HeaderHash),
+    #[error("the peer did not send the block {0} of the ledger snapshot")]
+    SnapshotBlockNotReceived(HeaderHash),
+    #[error("failed to access the storage")]
+    Storage(#[source] StorageError),
+    #[error("failed to apply the ledger snapshot")]
+    ApplySnapshotFailed(#[source] BlockchainError),
+    #[error("block {0} forks from the current branch deeper than the stability depth")]
+    DeepForkRejected(HeaderHash),
 }
 
 const MAX_BOOTSTRAP_PEERS: u32 = 32;
 
-pub async fn peers_from_trusted_peer(peer: &Peer, logger: Logger) -> Result<Vec<Peer>, Error> {
+/// maximum size of a downloaded ledger snapshot, if not configured
+const DEFAULT_MAX_SNAPSHOT_SIZE: u64 = 1 << 30;
+
+pub async fn peers_from_trusted_peer(
+    peer: &Peer,
+    config: &Configuration,
+    logger: Logger,
+) -> Result<Vec<Peer>, Error> {
     info!(
         logger,
         "getting peers from bootstrap peer {}", peer.connection
@@ -64,7 +109,7 @@ pub async fn peers_from_trusted_peer(peer: &Peer, logger: Logger) -> Result<Vec<
         peer.connection,
         peers.len()
     );
-    let peers = peers.iter().map(|peer| Peer::new(peer.addr())).collect();
+    let peers = peers.iter().map(|peer| config.peer(peer.addr())).collect();
     Ok(peers)
 }
 
@@ -115,6 +160,251 @@ where
     }
 }
 
+/// download the ledger snapshot of the configured block from the REST API
+/// of another node, and check it against the configured hash.
+///
+/// The snapshot is downloaded over HTTP rather than with the p2p protocol,
+/// which has no request for it: its integrity relies on the configured hash
+/// only.
+pub async fn fetch_snapshot(
+    fast_sync: &FastSync,
+    block0: HeaderHash,
+    logger: &Logger,
+) -> Result<Snapshot, Error> {
+    let url = format!(
+        "{}/v0/ledger/snapshots/{}",
+        fast_sync.snapshot_service.trim_end_matches('/'),
+        fast_sync.block
+    );
+    info!(logger, "downloading the ledger snapshot from {}", url);
+
+    let max_size = fast_sync
+        .max_snapshot_size
+        .unwrap_or(DEFAULT_MAX_SNAPSHOT_SIZE);
+    let response = reqwest::get(&url)
+        .await
+        .map_err(Error::SnapshotDownloadFailed)?;
+    if response.status() != reqwest::StatusCode::OK {
+        return Err(Error::SnapshotDownloadStatus(response.status()));
+    }
+    if response
+        .content_length()
+        .map_or(false, |size| size > max_size)
+    {
+        return Err(Error::SnapshotTooLarge(max_size));
+    }
+    let chunks = stream::unfold(response, |mut response| async move {
+        match response.chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), response)),
+            Ok(None) => None,
+            Err(e) => Some((Err(Error::SnapshotDownloadFailed(e)), response)),
+        }
+    });
+    let bytes = read_snapshot(chunks, max_size).await?;
+
+    let fast_sync = fast_sync.clone();
+    tokio::task::spawn_blocking(move || check_snapshot(&fast_sync, &block0, bytes))
+        .await
+        .map_err(Error::SnapshotDecodeAborted)?
+}
+
+/// receive the chunks of the snapshot. The announced length is not trusted,
+/// the size is checked as the snapshot is received.
+async fn read_snapshot<S, B>(chunks: S, max_size: u64) -> Result<Vec<u8>, Error>
+where
+    S: Stream<Item = Result<B, Error>>,
+    B: AsRef<[u8]>,
+{
+    futures::pin_mut!(chunks);
+    let mut bytes = Vec::new();
+    while let Some(chunk) = chunks.try_next().await? {
+        let chunk = chunk.as_ref();
+        if (bytes.len() + chunk.len()) as u64 > max_size {
+            return Err(Error::SnapshotTooLarge(max_size));
+        }
+        bytes.extend_from_slice(chunk);
+    }
+    Ok(bytes)
+}
+
+/// check the downloaded snapshot against the configured hash and block, and
+/// decode it
+fn check_snapshot(
+    fast_sync: &FastSync,
+    block0: &HeaderHash,
+    bytes: Vec<u8>,
+) -> Result<Snapshot, Error> {
+    let got = Hash::from(Blake2b256::new(&bytes));
+    if got != fast_sync.snapshot_hash {
+        return Err(Error::SnapshotHashMismatch {
+            expected: fast_sync.snapshot_hash,
+            got,
+        });
+    }
+    Snapshot::decode_of(block0, &fast_sync.block.into_hash(), bytes.as_slice())
+        .map_err(Error::SnapshotInvalid)
+}
+
+/// pull the blocks from the peer up to the block of the ledger snapshot and
+/// store them without applying them, then make the block of the snapshot the
+/// new tip.
+///
+/// The headers up to the block of the snapshot, whose hash is pinned in the
+/// configuration, are pulled first and checked to be chained one to the
+/// other, so that only the blocks leading to the snapshot are stored. The
+/// blocks are then checked to match these headers, and their content to
+/// match the content hash of their header.
+pub async fn fast_sync_from_peer<S>(
+    peer: &Peer,
+    mut blockchain: Blockchain,
+    tip: Tip,
+    snapshot: Snapshot,
+    bootstrap_stopper: S,
+    logger: Logger,
+) -> Result<(), Error>
+where
+    S: Future<Output = Result<(), futures::channel::oneshot::Canceled>> + Unpin + Clone,
+{
+    use futures::future::select;
+
+    debug!(logger, "connecting to fast sync peer {}", peer.connection);
+
+    let blockchain1 = blockchain.clone();
+    let tip1 = tip.clone();
+    let snapshot_block = net_data::BlockId::try_from(snapshot.block.as_bytes()).unwrap();
+
+    let stream_future = async move {
+        let mut client = grpc::connect(&peer).await.map_err(Error::Connect)?;
+
+        let checkpoints = blockchain1.get_checkpoints(tip1.branch()).await;
+        let checkpoints = net_data::block::try_ids_from_iter(checkpoints).unwrap();
+
+        let headers = client
+            .pull_headers(checkpoints.clone(), snapshot_block)
+            .await
+            .map_err(Error::PullRequestFailed)?;
+        Ok::<_, Error>((client, checkpoints, headers))
+    };
+
+    let (mut client, checkpoints, headers, bootstrap_stopper) =
+        match select(stream_future.boxed(), bootstrap_stopper).await {
+            Either::Left((stream_result, bootstrap_stopper)) => {
+                let (client, checkpoints, headers) = stream_result?;
+                (client, checkpoints, headers, bootstrap_stopper)
+            }
+            Either::Right((bootstrap_stopper_result, _)) => match bootstrap_stopper_result {
+                Ok(()) => return Err(Error::Interrupted),
+                Err(_) => panic!("failed to wait for SIGINT"),
+            },
+        };
+
+    let block0 = blockchain.block0().clone();
+    let mut headers = stoppable(headers, bootstrap_stopper.clone());
+    let mut parent = tip.get_ref().await.header().clone();
+    let mut expected = VecDeque::new();
+    while let Some(header) = headers.next().await {
+        let header =
+            Header::deserialize(header?.as_bytes()).map_err(Error::HeaderDecodingFailed)?;
+        let block_id = header.hash();
+
+        if block_id == block0 {
+            continue;
+        }
+        if header.block_parent_hash() != parent.hash() {
+            return Err(Error::BlockMissingParent(block_id));
+        }
+        blockchain::pre_verify_link(&header, &parent)
+            .map_err(|e| Error::BlockLinkInvalid(block_id, e))?;
+
+        expected.push_back(block_id);
+        if block_id == snapshot.block {
+            break;
+        }
+        parent = header;
+    }
+    if expected.back() != Some(&snapshot.block) {
+        return Err(Error::SnapshotBlockNotReceived(snapshot.block));
+    }
+
+    let blocks = client
+        .pull_blocks_to_tip(checkpoints)
+        .await
+        .map_err(Error::PullRequestFailed)?;
+    let mut blocks = stoppable(blocks, bootstrap_stopper);
+
+    const PROCESS_LOGGING_DISTANCE: u64 = 2500;
+    let mut bootstrap_info = BootstrapInfo::new();
+
+    while let Some(block) = blocks.next().await {
+        let block = Block::deserialize(block?.as_bytes()).map_err(Error::BlockDecodingFailed)?;
+        let block_id = block.header.hash();
+
+        if block_id == block0 {
+            continue;
+        }
+        if expected.front() != Some(&block_id) {
+            return Err(Error::BlockNotExpected(block_id));
+        }
+        if !block.is_consistent() {
+            return Err(Error::BlockContentInvalid(block_id));
+        }
+
+        bootstrap_info.append_block(&block);
+        if bootstrap_info.block_received % PROCESS_LOGGING_DISTANCE == 0 {
+            bootstrap_info.report(&logger);
+        }
+
+        match blockchain.storage().put_block(block).await {
+            Ok(()) | Err(StorageError::BlockAlreadyPresent) => {}
+            Err(e) => return Err(Error::Storage(e)),
+        }
+
+        expected.pop_front();
+        if expected.is_empty() {
+            break;
+        }
+    }
+
+    if !expected.is_empty() {
+        return Err(Error::SnapshotBlockNotReceived(snapshot.block));
+    }
+
+    let snapshot_ref = blockchain
+        .import_snapshot(snapshot)
+        .await
+        .map_err(Error::ApplySnapshotFailed)?;
+    info!(
+        logger,
+        "fast synced up to the ledger snapshot at {}",
+        snapshot_ref.header().description()
+    );
+    let snapshot_block = snapshot_ref.hash();
+    let comparison = blockchain::process_new_ref(&logger, &mut blockchain, tip, snapshot_ref)
+        .await
+        .map_err(Error::ChainSelectionFailed)?;
+    check_selected(comparison, snapshot_block, &logger)
+}
+
+/// turn the outcome of the selection of a bootstrapped block as the new tip
+/// into an error if the block forks too deep to ever be selected
+fn check_selected(
+    comparison: ComparisonResult,
+    candidate: HeaderHash,
+    logger: &Logger,
+) -> Result<(), Error> {
+    match comparison {
+        ComparisonResult::PreferCandidate => Ok(()),
+        ComparisonResult::PreferCurrent => {
+            info!(
+                logger,
+                "the current tip is kept over bootstrapped block {}", candidate
+            );
+            Ok(())
+        }
+        ComparisonResult::RejectDeepFork => Err(Error::DeepForkRejected(candidate)),
+    }
+}
+
 struct BootstrapInfo {
     last_reported: std::time::SystemTime,
     last_bytes_received: u64,
@@ -180,6 +470,33 @@ impl BootstrapInfo {
     }
 }
 
+/// This stream will either end when the block stream is exhausted or when
+/// the cancellation signal arrives. Building such stream allows us to
+/// correctly write all blocks and update the block tip upon the arrival of
+/// the cancellation signal.
+fn stoppable<T, S, St>(stream: S, bootstrap_stopper: St) -> impl Stream<Item = Result<T, Error>>
+where
+    S: Stream<Item = Result<T, NetworkError>> + Unpin,
+    St: Future<Output = Result<(), futures::channel::oneshot::Canceled>> + Unpin,
+{
+    let mut stream = stream.map_err(Error::PullStreamFailed);
+    let mut bootstrap_stopper = bootstrap_stopper.map(|res| match res {
+        Ok(()) => Err(Error::Interrupted),
+        Err(_) => panic!("failed to wait for SIGINT"),
+    });
+
+    stream::poll_fn(move |cx| {
+        let bootstrap_stopper = Pin::new(&mut bootstrap_stopper);
+        match bootstrap_stopper.poll(cx) {
+            Poll::Pending => {
+                let stream = Pin::new(&mut stream);
+                stream.poll_next(cx)
+            }
+            Poll::Ready(value) => Poll::Ready(Some(value)),
+        }
+    })
+}
+
 async fn bootstrap_from_stream<S, St>(
     mut blockchain: Blockchain,
     branch: Tip,
@@ -197,26 +514,7 @@ where
     let mut bootstrap_info = BootstrapInfo::new();
     let mut maybe_parent_tip = None;
 
-    let mut stream = stream.map_err(Error::PullStreamFailed);
-    let mut bootstrap_stopper = bootstrap_stopper.map(|res| match res {
-        Ok(()) => Err(Error::Interrupted),
-        Err(_) => panic!("failed to wait for SIGINT"),
-    });
-
-    // This stream will either end when the block stream is exhausted or when
-    // the cancellation signal arrives. Building such stream allows us to
-    // correctly write all blocks and update the block tip upon the arrival of
-    // the cancellation signal.
-    let mut stream = stream::poll_fn(move |cx| {
-        let bootstrap_stopper = Pin::new(&mut bootstrap_stopper);
-        match bootstrap_stopper.poll(cx) {
-            Poll::Pending => {
-                let stream = Pin::new(&mut stream);
-                stream.poll_next(cx)
-            }
-            Poll::Ready(value) => Poll::Ready(Some(value)),
-        }
-    });
+    let mut stream = stoppable(stream, bootstrap_stopper);
 
     while let Some(block_result) = stream.next().await {
         let result = match block_result {
@@ -245,14 +543,17 @@ where
             }
             Err(err) => {
                 if let Some(parent_tip) = maybe_parent_tip {
-                    if let Err(err) = blockchain::process_new_ref(
+                    let parent_block = parent_tip.hash();
+                    let selected = blockchain::process_new_ref(
                         &logger,
                         &mut blockchain,
                         branch.clone(),
                         parent_tip.clone(),
                     )
                     .await
-                    {
+                    .map_err(Error::ChainSelectionFailed)
+                    .and_then(|comparison| check_selected(comparison, parent_block, &logger));
+                    if let Err(err) = selected {
                         warn!(logger, "couldn't gracefully exit from failed netboot"; "error" => ?err);
                     }
                 }
@@ -262,9 +563,11 @@ where
     }
 
     if let Some(parent_tip) = maybe_parent_tip {
-        blockchain::process_new_ref(&logger, &mut blockchain, branch, parent_tip)
+        let parent_block = parent_tip.hash();
+        let comparison = blockchain::process_new_ref(&logger, &mut blockchain, branch, parent_tip)
             .await
-            .map_err(Error::ChainSelectionFailed)
+            .map_err(Error::ChainSelectionFailed)?;
+        check_selected(comparison, parent_block, &logger)
     } else {
         info!(logger, "no new blocks in bootstrap stream");
         Ok(())
@@ -313,3 +616,72 @@ async fn handle_block(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deep_fork_is_an_error() {
+        let logger = Logger::root(slog::Discard, o!());
+        let block = HeaderHash::hash_bytes(&[0]);
+        assert!(check_selected(ComparisonResult::PreferCandidate, block, &logger).is_ok());
+        assert!(check_selected(ComparisonResult::PreferCurrent, block, &logger).is_ok());
+        match check_selected(ComparisonResult::RejectDeepFork, block, &logger) {
+            Err(Error::DeepForkRejected(rejected)) => assert_eq!(rejected, block),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    fn fast_sync(snapshot: &[u8]) -> FastSync {
+        FastSync {
+            snapshot_service: "http://127.0.0.1:8443/api".to_owned(),
+            block: HeaderHash::hash_bytes(&[1]).into(),
+            snapshot_hash: Hash::from(Blake2b256::new(snapshot)),
+            max_snapshot_size: None,
+        }
+    }
+
+    fn chunks(chunks: &[&'static [u8]]) -> impl Stream<Item = Result<&'static [u8], Error>> {
+        stream::iter(chunks.to_vec().into_iter().map(Ok))
+    }
+
+    #[tokio::test]
+    async fn snapshot_up_to_the_maximum_size_is_received() {
+        let bytes = read_snapshot(chunks(&[b"ledger", b" snapshot"]), 15)
+            .await
+            .unwrap();
+        assert_eq!(bytes, b"ledger snapshot".to_vec());
+    }
+
+    #[tokio::test]
+    async fn snapshot_larger_than_the_maximum_size_is_aborted() {
+        match read_snapshot(chunks(&[b"ledger", b" snapshot"]), 14).await {
+            Err(Error::SnapshotTooLarge(max_size)) => assert_eq!(max_size, 14),
+            other => panic!("unexpected outcome {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn snapshot_with_another_hash_is_rejected() {
+        let block0 = HeaderHash::hash_bytes(&[0]);
+        let fast_sync = fast_sync(b"expected snapshot");
+        match check_snapshot(&fast_sync, &block0, b"other snapshot".to_vec()) {
+            Err(Error::SnapshotHashMismatch { expected, got }) => {
+                assert_eq!(expected, fast_sync.snapshot_hash);
+                assert_eq!(got, Hash::from(Blake2b256::new(b"other snapshot")));
+            }
+            other => panic!("unexpected outcome {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn snapshot_with_the_expected_hash_is_decoded() {
+        let block0 = HeaderHash::hash_bytes(&[0]);
+        let fast_sync = fast_sync(b"not a snapshot");
+        match check_snapshot(&fast_sync, &block0, b"not a snapshot".to_vec()) {
+            Err(Error::SnapshotInvalid(_)) => {}
+            other => panic!("unexpected outcome {:?}", other.map(|_| ())),
+        }
+    }
+}
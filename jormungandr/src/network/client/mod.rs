@@ -9,17 +9,20 @@ use super::{
     },
     p2p::{
         comm::{OutboundSubscription, PeerComms},
-        Address,
+        Address, Behavior,
     },
     subscription::{BlockAnnouncementProcessor, FragmentProcessor, GossipProcessor},
     Channels, GlobalStateR,
 };
 use crate::{
-    intercom::{self, BlockMsg, ClientMsg},
+    fragment,
+    intercom::{self, BlockMsg, ClientMsg, TransactionMsg},
     utils::async_msg::MessageBox,
 };
 use chain_network::data as net_data;
 use chain_network::data::block::{BlockEvent, BlockIds, ChainPullRequest};
+use chain_network::data::FragmentIds;
+use jormungandr_lib::interfaces::FragmentOrigin;
 
 use futures::prelude::*;
 use futures::ready;
@@ -38,12 +41,16 @@ pub struct Client {
     inbound: InboundSubscriptions,
     block_solicitations: OutboundSubscription<BlockIds>,
     chain_pulls: OutboundSubscription<ChainPullRequest>,
+    fragment_solicitations: OutboundSubscription<FragmentIds>,
     block_sink: BlockAnnouncementProcessor,
     fragment_sink: FragmentProcessor,
     gossip_sink: GossipProcessor,
     client_box: MessageBox<ClientMsg>,
+    transaction_box: MessageBox<TransactionMsg>,
     incoming_block_announcement: Option<net_data::Header>,
     incoming_solicitation: Option<ClientMsg>,
+    /// whether the pending fragments of the peer have been requested
+    mempool_fetched: bool,
     shutting_down: bool,
 }
 
@@ -77,7 +84,7 @@ impl Client {
             logger.new(o!("stream" => "block_events", "direction" => "in")),
         );
         let fragment_sink = FragmentProcessor::new(
-            builder.channels.transaction_box,
+            builder.channels.transaction_box.clone(),
             inbound.node_id.clone(),
             global_state.clone(),
             logger.new(o!("stream" => "fragments", "direction" => "in")),
@@ -95,12 +102,15 @@ impl Client {
             inbound,
             block_solicitations: comms.subscribe_to_block_solicitations(),
             chain_pulls: comms.subscribe_to_chain_pulls(),
+            fragment_solicitations: comms.subscribe_to_fragment_solicitations(),
             block_sink,
             fragment_sink,
             gossip_sink,
             client_box: builder.channels.client_box,
+            transaction_box: builder.channels.transaction_box,
             incoming_block_announcement: None,
             incoming_solicitation: None,
+            mempool_fetched: false,
             shutting_down: false,
         }
     }
@@ -239,6 +249,12 @@ impl Client {
             BlockEvent::Announce(header) => {
                 debug_assert!(self.incoming_block_announcement.is_none());
                 self.incoming_block_announcement = Some(header);
+                if !self.mempool_fetched {
+                    // the first announcement tells the peer is in sync,
+                    // catch up on the fragments it has pending
+                    self.mempool_fetched = true;
+                    self.fetch_fragments(FragmentIds::from(Vec::new()));
+                }
             }
             BlockEvent::Solicit(block_ids) => {
                 self.upload_blocks(block_ids)?;
@@ -438,6 +454,69 @@ impl Client {
         });
     }
 
+    fn fetch_fragments(&mut self, fragment_ids: FragmentIds) {
+        let mut transaction_box = self.transaction_box.clone();
+        let logger = self.logger.new(o!("request" => "GetFragments"));
+        let mut client = self.inner.clone();
+        let node_id = self.inbound.node_id.clone();
+        let state = self.global_state.clone();
+        self.global_state.spawn(async move {
+            let fragments = match client.get_fragments(fragment_ids).await {
+                Err(e) => {
+                    info!(
+                        logger,
+                        "request failed";
+                        "reason" => %e,
+                    );
+                    return;
+                }
+                Ok(stream) => {
+                    let res = stream
+                        .and_then(|item| async { item.decode() })
+                        .try_collect::<Vec<_>>()
+                        .await;
+                    match res {
+                        Ok(fragments) => fragments,
+                        Err(e) => {
+                            info!(
+                                logger,
+                                "response stream failed";
+                                "reason" => %e,
+                            );
+                            return;
+                        }
+                    }
+                }
+            };
+            debug!(logger, "received {} fragments", fragments.len());
+            let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+            let res = transaction_box
+                .send(TransactionMsg::SendTransaction(
+                    FragmentOrigin::Network,
+                    Some(node_id.clone()),
+                    fragments,
+                    Some(reply_handle),
+                ))
+                .await;
+            if let Err(e) = res {
+                error!(
+                    logger,
+                    "failed to send fragments to the fragment task";
+                    "reason" => %e,
+                );
+                return;
+            }
+            if let Ok(submissions) = reply_future.await {
+                if submissions.iter().any(fragment::is_rejected_as_invalid) {
+                    state
+                        .peers
+                        .record_behavior(node_id, Behavior::InvalidFragment)
+                        .await;
+                }
+            }
+        });
+    }
+
     fn process_fragments(&mut self, cx: &mut Context<'_>) -> Poll<Result<ProcessingOutcome, ()>> {
         use self::ProcessingOutcome::*;
 
@@ -567,6 +646,21 @@ impl Future for Client {
                         }
                     })
             });
+            // Fragment solicitations are only sent to client connections.
+            progress.and_proceed_with(|| {
+                Pin::new(&mut self.fragment_solicitations)
+                    .poll_next(cx)
+                    .map(|maybe_item| match maybe_item {
+                        Some(fragment_ids) => {
+                            self.fetch_fragments(fragment_ids);
+                            Ok(Continue)
+                        }
+                        None => {
+                            debug!(self.logger, "outbound fragment solicitation stream closed");
+                            Ok(Disconnect)
+                        }
+                    })
+            });
 
             match progress {
                 Progress(Poll::Pending) => return Poll::Pending,
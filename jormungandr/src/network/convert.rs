@@ -1,5 +1,5 @@
 use super::p2p::Gossip;
-use crate::blockcfg::{Block, Fragment, Header, HeaderId};
+use crate::blockcfg::{Block, Fragment, FragmentId, Header, HeaderId};
 use crate::intercom;
 use chain_core::mempack::{ReadBuf, Readable};
 use chain_core::property::{Deserialize, Serialize};
@@ -63,6 +63,14 @@ impl Decode for net_data::BlockId {
     }
 }
 
+impl Decode for net_data::FragmentId {
+    type Object = FragmentId;
+
+    fn decode(self) -> Result<Self::Object, Error> {
+        read(&self)
+    }
+}
+
 impl Decode for net_data::Block {
     type Object = Block;
 
@@ -94,6 +102,15 @@ impl Decode for net_data::gossip::Node {
     }
 }
 
+// The fragment identifiers have the same type as the block identifiers,
+// so they cannot be encoded with the `Encode` trait.
+pub fn encode_fragment_ids(ids: &[FragmentId]) -> net_data::FragmentIds {
+    ids.iter()
+        .map(|id| net_data::FragmentId::try_from(id.as_bytes()).unwrap())
+        .collect::<Vec<_>>()
+        .into()
+}
+
 impl<T, N> Encode for Vec<T>
 where
     T: Encode<NetworkData = N>,
@@ -2,7 +2,7 @@ use crate::{
     blockcfg::{Block, HeaderHash},
     network::convert::Decode,
     network::{concurrency_limits, keepalive_durations},
-    settings::start::network::{Peer, Protocol},
+    settings::start::network::{Peer, Protocol, Tls},
 };
 use chain_network::data as net_data;
 use chain_network::error as net_error;
@@ -10,10 +10,9 @@ use chain_network::grpc::{client::Builder, legacy};
 use futures::prelude::*;
 use slog::Logger;
 use thiserror::Error;
-use tonic::transport;
+use tonic::transport::{self, ClientTlsConfig};
 
 use std::convert::TryFrom;
-use std::net::SocketAddr;
 
 pub use chain_network::grpc::client::{
     BlockSubscription, FragmentSubscription, GossipSubscription,
@@ -47,7 +46,7 @@ pub async fn connect_legacy(peer: &Peer, node_id: legacy::NodeId) -> Result<Clie
 
 async fn connect_internal(peer: &Peer, builder: Builder) -> Result<Client, ConnectError> {
     assert!(peer.protocol == Protocol::Grpc);
-    let endpoint = destination_endpoint(peer.connection)
+    let endpoint = destination_endpoint(peer)
         .concurrency_limit(concurrency_limits::CLIENT_REQUESTS)
         .tcp_keepalive(Some(keepalive_durations::TCP))
         .http2_keep_alive_interval(keepalive_durations::HTTP2)
@@ -55,9 +54,24 @@ async fn connect_internal(peer: &Peer, builder: Builder) -> Result<Client, Conne
     builder.connect(endpoint).await
 }
 
-fn destination_endpoint(addr: SocketAddr) -> transport::Endpoint {
-    let uri = format!("http://{}", addr);
-    transport::Endpoint::try_from(uri).unwrap()
+fn destination_endpoint(peer: &Peer) -> transport::Endpoint {
+    let scheme = if peer.tls.is_some() { "https" } else { "http" };
+    let uri = format!("{}://{}", scheme, peer.connection);
+    let endpoint = transport::Endpoint::try_from(uri).unwrap();
+    match &peer.tls {
+        Some(tls) => endpoint.tls_config(client_tls_config(tls, peer)),
+        None => endpoint,
+    }
+}
+
+fn client_tls_config(tls: &Tls, peer: &Peer) -> ClientTlsConfig {
+    ClientTlsConfig::new()
+        .domain_name(tls.server_name.clone())
+        .rustls_client_config(super::tls::client_config(
+            tls,
+            &peer.certificates,
+            peer.connection,
+        ))
 }
 
 // Fetches a block from a network peer.
@@ -1,5 +1,6 @@
 pub(super) mod client;
 mod server;
+mod tls;
 
 pub use self::client::{
     connect, connect_legacy, fetch_block, Client, ConnectError, FetchBlockError,
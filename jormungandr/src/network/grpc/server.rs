@@ -1,13 +1,38 @@
 use super::super::{
-    concurrency_limits, keepalive_durations, service::NodeService, Channels, GlobalStateR,
-    ListenError,
+    concurrency_limits, keepalive_durations, p2p::CertificateBindings, rate_limit::REMOTE_ADDR,
+    service::NodeService, Channels, GlobalStateR, ListenError,
 };
+use super::tls;
 use crate::settings::start::network::Listen;
 use chain_network::grpc;
 
-use tonic::transport::Server;
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use rustls::Session;
+use slog::Logger;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time;
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tonic::codegen::Service;
+use tonic::transport::{NamedService, Server};
 
 use std::convert::TryInto;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// maximum number of TLS handshakes done at once
+const MAX_HANDSHAKES: usize = 64;
+
+/// time given to the nodes connecting to this node to complete the TLS
+/// handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// time to wait before accepting connections again after a failure, which
+/// is usually due to the limit of open files
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_secs(1);
 
 pub async fn run_listen_socket(
     listen: &Listen,
@@ -24,13 +49,142 @@ pub async fn run_listen_socket(
         let node_id: grpc::legacy::NodeId = node_id.as_ref().try_into().unwrap();
         builder.legacy_node_id(node_id);
     }
-    let service = builder.build(NodeService::new(channels, state));
+    let acceptor = state.config.tls.as_ref().map(|settings| {
+        info!(logger, "gRPC connections are secured with TLS");
+        TlsAcceptor::from(Arc::new(tls::server_config(
+            settings,
+            &state.config.certificates,
+        )))
+    });
+    let certificates = state.config.certificates.clone();
+    let service = WithRemoteAddr(builder.build(NodeService::new(channels, state)));
 
-    Server::builder()
+    let server = Server::builder()
         .concurrency_limit_per_connection(concurrency_limits::SERVER_REQUESTS)
         .tcp_keepalive(Some(keepalive_durations::TCP))
-        .add_service(service)
-        .serve(sockaddr)
-        .await
-        .map_err(|cause| ListenError { cause, sockaddr })
+        .add_service(service);
+    match acceptor {
+        Some(acceptor) => {
+            let listener = TcpListener::bind(sockaddr)
+                .await
+                .map_err(|cause| ListenError {
+                    cause: cause.into(),
+                    sockaddr,
+                })?;
+            let incoming = tls_incoming(listener, acceptor, certificates, logger);
+            server.serve_with_incoming(incoming).await
+        }
+        None => server.serve(sockaddr).await,
+    }
+    .map_err(|cause| ListenError {
+        cause: cause.into(),
+        sockaddr,
+    })
+}
+
+/// the TLS connections of the nodes connecting to this node. The certificate
+/// of a node has to be issued by one of the configured authorities, or be
+/// pinned for a trusted peer, and has to be bound to the IP address the node
+/// connects from, or the connection is dropped.
+fn tls_incoming(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    certificates: CertificateBindings,
+    logger: Logger,
+) -> impl Stream<Item = Result<TlsStream<TcpStream>, io::Error>> {
+    let accept_logger = logger.clone();
+    stream::unfold(listener, move |mut listener| {
+        let logger = accept_logger.clone();
+        async move {
+            loop {
+                match listener.accept().await {
+                    Ok(accepted) => return Some((accepted, listener)),
+                    Err(e) => {
+                        warn!(logger, "failed to accept a connection"; "reason" => %e);
+                        time::delay_for(ACCEPT_ERROR_DELAY).await;
+                    }
+                }
+            }
+        }
+    })
+    .map(move |(stream, remote_addr)| {
+        handshake(
+            acceptor.clone(),
+            stream,
+            remote_addr,
+            certificates.clone(),
+            logger.new(o!("peer_addr" => remote_addr)),
+        )
+    })
+    .buffer_unordered(MAX_HANDSHAKES)
+    .filter_map(|stream| future::ready(stream.map(Ok::<_, io::Error>)))
+}
+
+async fn handshake(
+    acceptor: TlsAcceptor,
+    stream: TcpStream,
+    remote_addr: SocketAddr,
+    certificates: CertificateBindings,
+    logger: Logger,
+) -> Option<TlsStream<TcpStream>> {
+    if let Err(e) = stream.set_keepalive(Some(keepalive_durations::TCP)) {
+        debug!(logger, "failed to set the TCP keepalive"; "reason" => %e);
+    }
+    let stream = match time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            debug!(logger, "TLS handshake failed"; "reason" => %e);
+            return None;
+        }
+        Err(_) => {
+            debug!(logger, "TLS handshake timed out");
+            return None;
+        }
+    };
+    let fingerprint = stream
+        .get_ref()
+        .1
+        .get_peer_certificates()
+        .and_then(|presented| tls::fingerprint(&presented))?;
+    match certificates.check_client(remote_addr.ip(), &fingerprint) {
+        Ok(()) => Some(stream),
+        Err(e) => {
+            info!(logger, "refusing the connection of the node"; "reason" => %e);
+            None
+        }
+    }
+}
+
+/// serves the requests with the address of the peer making them, which the
+/// services of chain-network do not pass to the node, for the rate limits
+#[derive(Clone)]
+struct WithRemoteAddr<S>(S);
+
+impl<S: NamedService> NamedService for WithRemoteAddr<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S, B> Service<http::Request<B>> for WithRemoteAddr<S>
+where
+    S: Service<http::Request<B>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        // the transport of tonic records the address of the connection in
+        // the extensions of the request
+        let extensions = req.extensions();
+        let remote_addr = extensions
+            .get::<SocketAddr>()
+            .copied()
+            .or_else(|| extensions.get::<Option<SocketAddr>>().copied().flatten());
+        Box::pin(REMOTE_ADDR.scope(remote_addr, self.0.call(req)))
+    }
 }
@@ -0,0 +1,262 @@
+//! verification of the certificates presented by the nodes this node
+//! connects to and by the nodes connecting to this node.
+//!
+//! The certificates have to be issued by the configured authorities, unless
+//! they are pinned for a trusted peer, and are bound to the address of the
+//! nodes presenting them by the `CertificateBindings` of the network.
+
+use crate::network::p2p::CertificateBindings;
+use crate::settings::start::network::Tls;
+use chain_crypto::Blake2b256;
+use rustls::{
+    AllowAnyAuthenticatedClient, Certificate, ClientCertVerified, ClientCertVerifier, ClientConfig,
+    DistinguishedNames, RootCertStore, ServerCertVerified, ServerCertVerifier, ServerConfig,
+    TLSError, WebPKIVerifier,
+};
+use webpki::{DNSName, DNSNameRef};
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+const ALPN_H2: &[u8] = b"h2";
+
+/// the configuration of the TLS client connecting to the node at `address`
+pub fn client_config(
+    tls: &Tls,
+    bindings: &CertificateBindings,
+    address: SocketAddr,
+) -> ClientConfig {
+    let mut config = ClientConfig::new();
+    config.root_store = tls
+        .ca_certificates()
+        .expect("checked when loading the settings");
+    config
+        .set_single_client_cert(
+            tls.certificate_chain()
+                .expect("checked when loading the settings"),
+            tls.private_key()
+                .expect("checked when loading the settings"),
+        )
+        .expect("checked when loading the settings");
+    let verifier = AddressBoundVerifier {
+        address,
+        bindings: bindings.clone(),
+        ca: WebPKIVerifier::new(),
+    };
+    config
+        .dangerous()
+        .set_certificate_verifier(Arc::new(verifier));
+    config
+}
+
+/// the configuration of the TLS server accepting the connections of the
+/// nodes. The certificates the nodes present are bound to their address once
+/// the handshake is done, with `CertificateBindings::check_client`.
+pub fn server_config(tls: &Tls, bindings: &CertificateBindings) -> ServerConfig {
+    let roots = tls
+        .ca_certificates()
+        .expect("checked when loading the settings");
+    let verifier = PinnedOrIssuedVerifier {
+        bindings: bindings.clone(),
+        subjects: roots.get_subjects(),
+        ca: AllowAnyAuthenticatedClient::new(roots),
+    };
+    let mut config = ServerConfig::new(Arc::new(verifier));
+    config
+        .set_single_cert(
+            tls.certificate_chain()
+                .expect("checked when loading the settings"),
+            tls.private_key()
+                .expect("checked when loading the settings"),
+        )
+        .expect("checked when loading the settings");
+    config.set_protocols(&[ALPN_H2.to_vec()]);
+    config
+}
+
+/// the fingerprint a certificate is bound with
+pub fn fingerprint(presented_certs: &[Certificate]) -> Option<Blake2b256> {
+    presented_certs
+        .first()
+        .map(|certificate| Blake2b256::new(&certificate.0))
+}
+
+struct AddressBoundVerifier<V = WebPKIVerifier> {
+    address: SocketAddr,
+    bindings: CertificateBindings,
+    /// the verification against the authorities, skipped for the pinned
+    /// certificates
+    ca: V,
+}
+
+impl<V: ServerCertVerifier> ServerCertVerifier for AddressBoundVerifier<V> {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        dns_name: DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let presented = fingerprint(presented_certs).ok_or(TLSError::NoCertificatesPresented)?;
+        if self.bindings.pinned(self.address).is_none() {
+            self.ca
+                .verify_server_cert(roots, presented_certs, dns_name, ocsp_response)?;
+        }
+        self.bindings
+            .check_server(self.address, &presented)
+            .map_err(|e| TLSError::General(e.to_string()))?;
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// accepts the certificates pinned for the trusted peers, whether or not
+/// they are issued by the authorities
+struct PinnedOrIssuedVerifier {
+    bindings: CertificateBindings,
+    subjects: DistinguishedNames,
+    ca: Arc<dyn ClientCertVerifier>,
+}
+
+impl ClientCertVerifier for PinnedOrIssuedVerifier {
+    fn client_auth_mandatory(&self, _sni: Option<&DNSName>) -> Option<bool> {
+        Some(true)
+    }
+
+    fn client_auth_root_subjects(&self, _sni: Option<&DNSName>) -> Option<DistinguishedNames> {
+        Some(self.subjects.clone())
+    }
+
+    fn verify_client_cert(
+        &self,
+        presented_certs: &[Certificate],
+        sni: Option<&DNSName>,
+    ) -> Result<ClientCertVerified, TLSError> {
+        let presented = fingerprint(presented_certs).ok_or(TLSError::NoCertificatesPresented)?;
+        if self.bindings.is_pinned(&presented) {
+            Ok(ClientCertVerified::assertion())
+        } else {
+            self.ca.verify_client_cert(presented_certs, sni)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::p2p::certificates::BINDING_EXPIRY;
+
+    /// accepts every certificate, the authorities are not under test
+    struct AnyIssued;
+
+    impl ServerCertVerifier for AnyIssued {
+        fn verify_server_cert(
+            &self,
+            _roots: &RootCertStore,
+            _presented_certs: &[Certificate],
+            _dns_name: DNSNameRef,
+            _ocsp_response: &[u8],
+        ) -> Result<ServerCertVerified, TLSError> {
+            Ok(ServerCertVerified::assertion())
+        }
+    }
+
+    /// refuses every certificate
+    struct NoneIssued;
+
+    impl ServerCertVerifier for NoneIssued {
+        fn verify_server_cert(
+            &self,
+            _roots: &RootCertStore,
+            _presented_certs: &[Certificate],
+            _dns_name: DNSNameRef,
+            _ocsp_response: &[u8],
+        ) -> Result<ServerCertVerified, TLSError> {
+            Err(TLSError::General(
+                "not issued by the authorities".to_owned(),
+            ))
+        }
+    }
+
+    impl ClientCertVerifier for NoneIssued {
+        fn client_auth_root_subjects(&self, _sni: Option<&DNSName>) -> Option<DistinguishedNames> {
+            Some(DistinguishedNames::new())
+        }
+
+        fn verify_client_cert(
+            &self,
+            _presented_certs: &[Certificate],
+            _sni: Option<&DNSName>,
+        ) -> Result<ClientCertVerified, TLSError> {
+            Err(TLSError::General(
+                "not issued by the authorities".to_owned(),
+            ))
+        }
+    }
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn verify<V: ServerCertVerifier>(
+        verifier: &AddressBoundVerifier<V>,
+        certificate: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        verifier.verify_server_cert(
+            &RootCertStore::empty(),
+            &[Certificate(certificate.to_vec())],
+            DNSNameRef::try_from_ascii_str("node.example").unwrap(),
+            &[],
+        )
+    }
+
+    #[test]
+    fn first_use_binds_and_mismatch_is_rejected() {
+        let bindings = CertificateBindings::with_limits(None, 1, BINDING_EXPIRY);
+        let verifier = AddressBoundVerifier {
+            address: address(1),
+            bindings: bindings.clone(),
+            ca: AnyIssued,
+        };
+        assert!(verify(&verifier, b"node").is_ok());
+        assert!(verify(&verifier, b"node").is_ok());
+        assert!(verify(&verifier, b"impostor").is_err());
+        let fingerprint = Blake2b256::new(b"impostor");
+        assert!(bindings
+            .check_client(address(1).ip(), &fingerprint)
+            .is_err());
+    }
+
+    #[test]
+    fn certificate_not_issued_is_rejected() {
+        let verifier = AddressBoundVerifier {
+            address: address(1),
+            bindings: CertificateBindings::new(None),
+            ca: NoneIssued,
+        };
+        assert!(verify(&verifier, b"node").is_err());
+    }
+
+    #[test]
+    fn pinned_certificate_is_honored() {
+        let bindings = CertificateBindings::new(vec![(address(1), &b"pinned"[..])]);
+        let verifier = AddressBoundVerifier {
+            address: address(1),
+            bindings: bindings.clone(),
+            ca: NoneIssued,
+        };
+        assert!(verify(&verifier, b"pinned").is_ok());
+        assert!(verify(&verifier, b"other").is_err());
+
+        let client_verifier = PinnedOrIssuedVerifier {
+            bindings,
+            subjects: DistinguishedNames::new(),
+            ca: Arc::new(NoneIssued),
+        };
+        assert!(client_verifier
+            .verify_client_cert(&[Certificate(b"pinned".to_vec())], None)
+            .is_ok());
+        assert!(client_verifier
+            .verify_client_cert(&[Certificate(b"other".to_vec())], None)
+            .is_err());
+    }
+}
@@ -10,6 +10,7 @@ mod client;
 mod convert;
 mod grpc;
 pub mod p2p;
+pub mod rate_limit;
 mod service;
 mod subscription;
 
@@ -48,6 +49,10 @@ mod buffer_sizes {
         // (GetBlocks response or an UploadBlocks request)
         // before the client request task producing them gets preempted.
         pub const BLOCKS: usize = 8;
+
+        // The maximum number of fragments to buffer for a GetFragments
+        // response before the fragment task producing them gets preempted.
+        pub const FRAGMENTS: usize = 128;
     }
 }
 
@@ -70,26 +75,28 @@ mod keepalive_durations {
 }
 
 use self::client::ConnectError;
-use self::p2p::{comm::Peers, P2pTopology};
+use self::p2p::{comm::Peers, peer_db::PeerDb, Behavior, P2pTopology, StrikeReason, View};
+use self::rate_limit::RateLimits;
 use crate::blockcfg::{Block, HeaderHash};
 use crate::blockchain::{Blockchain as NewBlockchain, Tip};
 use crate::intercom::{BlockMsg, ClientMsg, NetworkMsg, PropagateMsg, TransactionMsg};
 use crate::log;
-use crate::settings::start::network::{Configuration, Peer, Protocol};
+use crate::settings::start::{
+    config::FastSync,
+    network::{Configuration, Peer, Protocol},
+};
 use crate::utils::{
     async_msg::{MessageBox, MessageQueue},
     task::TokioServiceInfo,
 };
 use chain_network::data::gossip::Gossip;
-use poldercast::StrikeReason;
 use rand::seq::SliceRandom;
 use slog::Logger;
-use tonic::transport;
 
 use std::collections::BTreeMap;
 use std::error;
 use std::fmt;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -98,7 +105,7 @@ use crate::stats_counter::StatsCounter;
 
 #[derive(Debug)]
 pub struct ListenError {
-    cause: transport::Error,
+    cause: Box<dyn error::Error + Send + Sync + 'static>,
     sockaddr: SocketAddr,
 }
 
@@ -114,7 +121,7 @@ impl fmt::Display for ListenError {
 
 impl error::Error for ListenError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        Some(&self.cause)
+        Some(&*self.cause)
     }
 }
 
@@ -144,6 +151,8 @@ pub struct GlobalState {
     stats_counter: StatsCounter,
     topology: P2pTopology,
     peers: Peers,
+    peer_db: Option<PeerDb>,
+    rate_limits: RateLimits,
     logger: Logger,
 }
 
@@ -155,9 +164,11 @@ impl GlobalState {
         block0_hash: HeaderHash,
         config: Configuration,
         stats_counter: StatsCounter,
+        peer_db: Option<PeerDb>,
         logger: Logger,
     ) -> Self {
         let peers = Peers::new(config.max_connections, logger.clone());
+        let rate_limits = RateLimits::new(&config.rate_limits);
 
         let mut rng_seed = [0; 32];
         rand::thread_rng().fill(&mut rng_seed);
@@ -175,6 +186,8 @@ impl GlobalState {
             stats_counter,
             topology,
             peers,
+            peer_db,
+            rate_limits,
             logger,
         }
     }
@@ -191,6 +204,13 @@ impl GlobalState {
         &self.topology
     }
 
+    /// the view of the topology, with the peers ranked by their reputation
+    pub async fn view(&self, selection: poldercast::Selection) -> View {
+        let mut view = self.topology.view(selection).await;
+        view.peers = self.peers.rank(view.peers).await;
+        view
+    }
+
     pub fn spawn<F>(&self, f: F)
     where
         F: Future<Output = ()> + Send + 'static,
@@ -198,6 +218,32 @@ impl GlobalState {
         tokio::spawn(f);
     }
 
+    /// save the peers known to the topology in the peer database, if any
+    async fn save_peers(&self) -> Result<(), p2p::peer_db::Error> {
+        match &self.peer_db {
+            Some(peer_db) => peer_db.write(self.topology.saved_peers().await),
+            None => Ok(()),
+        }
+    }
+
+    /// the peer connecting from `ip` went over its rate limit, strike the
+    /// node it is in the policy and lower its reputation. The peer is not
+    /// reported if it is not a node of the topology, as the address it
+    /// connects from is not the one it is gossiped with.
+    async fn report_rate_limited(&self, ip: IpAddr) {
+        let node = match self.topology.node_with_ip(ip).await {
+            Some(node) => node,
+            None => return,
+        };
+        future::join(
+            self.topology
+                .report_node(node.clone(), StrikeReason::RateLimited),
+            self.peers
+                .record_behavior(node, Behavior::RateLimitExceeded),
+        )
+        .await;
+    }
+
     fn inc_client_count(&self) {
         self.stats_counter.add_peer_connected_cnt(1);
     }
@@ -247,7 +293,10 @@ impl ConnectionState {
     }
 
     fn peer(&self) -> Peer {
-        Peer::with_timeout(self.connection, self.timeout)
+        Peer {
+            timeout: self.timeout,
+            ..self.global.config.peer(self.connection)
+        }
     }
 
     fn logger(&self) -> &Logger {
@@ -307,6 +356,14 @@ pub async fn start(service_info: TokioServiceInfo, params: TaskParams) {
         });
     }
 
+    if global_state.peer_db.is_some() {
+        let save_state = global_state.clone();
+        service_info.run_periodic_fallible("save peers", p2p::peer_db::SAVE_INTERVAL, move || {
+            let state = save_state.clone();
+            async move { state.save_peers().await }
+        });
+    }
+
     let gossip = time::interval(global_state.config.gossip_interval)
         .for_each(move |_| send_gossip(global_state.clone(), channels.clone()));
 
@@ -330,6 +387,12 @@ async fn handle_network_input(
                     .solicit_blocks(node_id, Box::new([block_id.encode()]))
                     .await;
             }
+            NetworkMsg::GetFragments(node_id, fragment_ids) => {
+                state
+                    .peers
+                    .fetch_fragments(node_id, convert::encode_fragment_ids(&fragment_ids))
+                    .await;
+            }
             NetworkMsg::PullHeaders {
                 node_address,
                 from,
@@ -341,6 +404,9 @@ async fn handle_network_input(
                     .pull_headers(node_address, from.encode(), to.encode())
                     .await;
             }
+            NetworkMsg::ReportPeer(node_id, behavior) => {
+                state.peers.record_behavior(node_id, behavior).await;
+            }
             NetworkMsg::PeerInfo(reply) => {
                 state.peers.infos().map(|infos| reply.reply_ok(infos)).await;
             }
@@ -355,7 +421,6 @@ async fn handle_propagation_msg(msg: PropagateMsg, state: GlobalStateR, channels
             debug!(state.logger(), "block to propagate"; "hash" => %header.hash());
             let header = header.encode();
             let view = state
-                .topology
                 .view(poldercast::Selection::Topic {
                     topic: p2p::topic::BLOCKS,
                 })
@@ -366,7 +431,6 @@ async fn handle_propagation_msg(msg: PropagateMsg, state: GlobalStateR, channels
             debug!(state.logger(), "fragment to propagate"; "hash" => %fragment.hash());
             let fragment = fragment.encode();
             let view = state
-                .topology
                 .view(poldercast::Selection::Topic {
                     topic: p2p::topic::MESSAGES,
                 })
@@ -406,6 +470,22 @@ async fn start_gossiping(state: GlobalStateR, channels: Channels) {
     let topology = &state.topology;
     let logger = state.logger().new(o!(log::KEY_SUB_TASK => "start_gossip"));
     let address = config.profile.address().unwrap();
+    // restore the quarantine records and the peers saved before the node
+    // restarted, the available ones are injected with the trusted peers
+    let saved_peers = state
+        .peer_db
+        .as_ref()
+        .map(|peer_db| peer_db.read_or_log())
+        .unwrap_or_default();
+    topology.restore_policy_records(&saved_peers);
+    let saved_peers = saved_peers
+        .into_iter()
+        .filter(|peer| peer.available)
+        .map(|peer| {
+            let mut builder = poldercast::NodeProfileBuilder::new();
+            builder.address(peer.address);
+            builder.build()
+        });
     // inject the trusted peers as initial gossips, this will make the node
     // gossip with them at least at the beginning
     topology
@@ -422,12 +502,13 @@ async fn start_gossiping(state: GlobalStateR, channels: Channels) {
                     }
                     builder.build()
                 })
+                .chain(saved_peers)
                 .map(p2p::Gossip::from)
                 .collect::<Vec<p2p::Gossip>>()
                 .into(),
         )
         .await;
-    let view = topology.view(poldercast::Selection::Any).await;
+    let view = state.view(poldercast::Selection::Any).await;
     let peers: Vec<p2p::Address> = view.peers;
     debug!(logger, "sending gossip to {} peers", peers.len());
     for address in peers {
@@ -449,7 +530,7 @@ async fn start_gossiping(state: GlobalStateR, channels: Channels) {
 async fn send_gossip(state: GlobalStateR, channels: Channels) {
     let topology = &state.topology;
     let logger = state.logger().new(o!(log::KEY_SUB_TASK => "send_gossip"));
-    let view = topology.view(poldercast::Selection::Any).await;
+    let view = state.view(poldercast::Selection::Any).await;
     let peers = view.peers;
     debug!(logger, "sending gossip to {} peers", peers.len());
     for address in peers {
@@ -493,7 +574,7 @@ fn connect_and_propagate(
         error!(state.logger(), "topology tells the node to connect to itself, ignoring"; "address" => %node);
         return;
     }
-    let peer = Peer::new(addr);
+    let peer = state.config.peer(addr);
     let conn_state = ConnectionState::new(state.clone(), &peer);
     let conn_logger = conn_state.logger().new(o!("address" => node.to_string()));
     info!(conn_logger, "connecting to peer");
@@ -525,11 +606,14 @@ fn connect_and_propagate(
                     }
                 };
                 if !benign {
-                    future::join(
+                    future::join3(
                         state
                             .topology
                             .report_node(node.clone(), StrikeReason::CannotConnect),
                         state.peers.remove_peer(node.clone()),
+                        state
+                            .peers
+                            .record_behavior(node.clone(), Behavior::Unreachable),
                     )
                     .await;
                 }
@@ -604,7 +688,7 @@ async fn netboot_peers(config: &Configuration, logger: &Logger) -> BootstrapPeer
         .trusted_peers
         .iter()
         .filter_map(|tp| {
-            multiaddr_to_socket_addr(tp.address.multi_address()).map(|sa| Peer::new(sa.clone()))
+            multiaddr_to_socket_addr(tp.address.multi_address()).map(|sa| config.peer(sa))
         })
         .collect::<Vec<_>>();
     if config.bootstrap_from_trusted_peers {
@@ -616,16 +700,17 @@ async fn netboot_peers(config: &Configuration, logger: &Logger) -> BootstrapPeer
         for tpeer in trusted_peers {
             // let peer = Peer::new(peer, Protocol::Grpc);
             let tp_logger = logger.new(o!("peer_addr" => tpeer.address().to_string()));
-            let received_peers = bootstrap::peers_from_trusted_peer(&tpeer, tp_logger.clone())
-                .await
-                .unwrap_or_else(|e| {
-                    warn!(
-                        tp_logger,
-                        "failed to retrieve the list of bootstrap peers from trusted peer";
-                        "reason" => %e,
-                    );
-                    vec![tpeer]
-                });
+            let received_peers =
+                bootstrap::peers_from_trusted_peer(&tpeer, config, tp_logger.clone())
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!(
+                            tp_logger,
+                            "failed to retrieve the list of bootstrap peers from trusted peer";
+                            "reason" => %e,
+                        );
+                        vec![tpeer]
+                    });
             let added = peers.add_peers(&received_peers);
             info!(logger, "adding {} peers from peer", added);
 
@@ -637,8 +722,24 @@ async fn netboot_peers(config: &Configuration, logger: &Logger) -> BootstrapPeer
     peers
 }
 
+/// the peers saved in the peer database which were available, to bootstrap
+/// from when none of the trusted peers can be bootstrapped from
+fn saved_bootstrap_peers(config: &Configuration, peer_db: &PeerDb) -> BootstrapPeers {
+    let mut peers = BootstrapPeers::new();
+    for saved_peer in peer_db.read_or_log() {
+        if !saved_peer.available {
+            continue;
+        }
+        if let Some(addr) = multiaddr_to_socket_addr(saved_peer.address.multi_address()) {
+            let _: usize = peers.add_peer(config.peer(addr));
+        }
+    }
+    peers
+}
+
 pub async fn bootstrap<S>(
     config: &Configuration,
+    peer_db: Option<&PeerDb>,
     blockchain: NewBlockchain,
     branch: Tip,
     bootstrap_stopper: S,
@@ -665,8 +766,6 @@ where
         //panic!("use non empty trusted-peers or set skip_bootstrap");
     }
 
-    let mut bootstrapped = false;
-
     let (netboot_peers, bootstrap_stopper) =
         match select(netboot_peers(config, logger).boxed(), bootstrap_stopper).await {
             Either::Left(result) => result,
@@ -676,7 +775,48 @@ where
             },
         };
 
-    for peer in netboot_peers.randomly() {
+    let bootstrapped = bootstrap_from_peers(
+        &netboot_peers,
+        blockchain.clone(),
+        branch.clone(),
+        bootstrap_stopper.clone(),
+        logger,
+    )
+    .await?;
+    if bootstrapped {
+        return Ok(true);
+    }
+
+    // the trusted peers may all be down, try the peers known before the
+    // node restarted
+    let saved_peers = match peer_db {
+        Some(peer_db) => saved_bootstrap_peers(config, peer_db),
+        None => return Ok(false),
+    };
+    if saved_peers.count() == 0 {
+        return Ok(false);
+    }
+    warn!(
+        logger,
+        "cannot bootstrap from the trusted peers, trying {} saved peers",
+        saved_peers.count()
+    );
+    bootstrap_from_peers(&saved_peers, blockchain, branch, bootstrap_stopper, logger).await
+}
+
+async fn bootstrap_from_peers<S>(
+    peers: &BootstrapPeers,
+    blockchain: NewBlockchain,
+    branch: Tip,
+    bootstrap_stopper: S,
+    logger: &Logger,
+) -> Result<bool, bootstrap::Error>
+where
+    S: Future<Output = Result<(), futures::channel::oneshot::Canceled>> + Unpin + Clone,
+{
+    let mut bootstrapped = false;
+
+    for peer in peers.randomly() {
         let logger = logger.new(o!("peer_addr" => peer.address().to_string()));
         let res = bootstrap::bootstrap_from_peer(
             peer,
@@ -709,6 +849,101 @@ where
     Ok(bootstrapped)
 }
 
+/// bootstrap from the configured ledger snapshot: the snapshot is downloaded,
+/// the blocks up to the block of the snapshot are pulled from the trusted
+/// peers without being applied, then the snapshot becomes the tip. The normal
+/// bootstrap can then continue from there.
+///
+/// Returns `false` if the fast sync failed, the node can still bootstrap from
+/// its current tip.
+pub async fn fast_sync<S>(
+    config: &Configuration,
+    fast_sync: &FastSync,
+    blockchain: NewBlockchain,
+    branch: Tip,
+    bootstrap_stopper: S,
+    logger: &Logger,
+) -> Result<bool, bootstrap::Error>
+where
+    S: Future<Output = Result<(), futures::channel::oneshot::Canceled>> + Unpin + Clone,
+{
+    use futures::future::{select, Either, FutureExt};
+
+    // the blocks up to the block of the snapshot may be stored while the
+    // fast sync failed afterwards, only a tip at or after this block means
+    // the snapshot was loaded
+    let block = fast_sync.block.into_hash();
+    let tip = branch.get_ref().await.hash();
+    let already_synced = blockchain
+        .storage()
+        .is_ancestor(block, tip)
+        .await
+        .map_err(bootstrap::Error::Storage)?
+        .is_some();
+    if already_synced {
+        info!(
+            logger,
+            "the tip already descends from the block {} of the ledger snapshot, skipping fast sync",
+            block
+        );
+        return Ok(true);
+    }
+
+    let fetch = bootstrap::fetch_snapshot(fast_sync, *blockchain.block0(), logger);
+    let (snapshot, bootstrap_stopper) = match select(fetch.boxed(), bootstrap_stopper).await {
+        Either::Left((Ok(snapshot), bootstrap_stopper)) => (snapshot, bootstrap_stopper),
+        Either::Left((Err(e), _)) => {
+            warn!(logger, "cannot use the ledger snapshot for fast sync"; "reason" => %e);
+            return Ok(false);
+        }
+        Either::Right((bootstrap_stopper_result, _)) => match bootstrap_stopper_result {
+            Ok(()) => return Err(bootstrap::Error::Interrupted),
+            Err(_) => panic!("failed to wait for SIGINT"),
+        },
+    };
+
+    let (netboot_peers, bootstrap_stopper) =
+        match select(netboot_peers(config, logger).boxed(), bootstrap_stopper).await {
+            Either::Left(result) => result,
+            Either::Right((bootstrap_stopper_result, _)) => match bootstrap_stopper_result {
+                Ok(()) => return Err(bootstrap::Error::Interrupted),
+                Err(_) => panic!("failed to wait for SIGINT"),
+            },
+        };
+
+    for peer in netboot_peers.randomly() {
+        let logger = logger.new(o!("peer_addr" => peer.address().to_string()));
+        let res = bootstrap::fast_sync_from_peer(
+            peer,
+            blockchain.clone(),
+            branch.clone(),
+            snapshot.clone(),
+            bootstrap_stopper.clone(),
+            logger.clone(),
+        )
+        .await;
+
+        match res {
+            Err(bootstrap::Error::Connect(e)) => {
+                warn!(logger, "unable to reach peer for fast sync"; "reason" => %e);
+            }
+            Err(bootstrap::Error::Interrupted) => {
+                warn!(logger, "the fast sync was interrupted");
+                return Err(bootstrap::Error::Interrupted);
+            }
+            Err(e) => {
+                warn!(logger, "fast sync failed"; "error" => ?e);
+            }
+            Ok(()) => {
+                info!(logger, "fast sync completed");
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
 /// Queries the trusted peers for a block identified with the hash.
 /// The calling thread is blocked until the block is retrieved.
 /// This function is called during blockchain initialization
@@ -732,7 +967,7 @@ pub async fn fetch_block(
 
     for address in trusted_peers_shuffled(&config) {
         let logger = logger.new(o!("peer_address" => address.to_string()));
-        let peer = Peer::new(address);
+        let peer = config.peer(address);
         match grpc::fetch_block(&peer, hash, &logger).await {
             Err(grpc::FetchBlockError::Connect { source: e }) => {
                 warn!(logger, "unable to reach peer for block download"; "reason" => %e);
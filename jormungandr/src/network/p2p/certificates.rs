@@ -0,0 +1,287 @@
+//! binding of the TLS certificates of the nodes to their IP addresses.
+//!
+//! The key pair of the TLS certificate is the identity of a node. The
+//! certificates presented from an IP address, by the nodes this node connects
+//! to and by the nodes connecting to this node, are bound to this address:
+//! once as many certificates as allowed were presented from an address, the
+//! connections presenting another certificate from it are refused. A binding
+//! expires when its certificate has not been presented for a while, which
+//! lets the nodes rotate their certificates.
+//!
+//! The certificate pinned for a trusted peer is always bound to its address,
+//! and is refused from any other address.
+
+use chain_crypto::Blake2b256;
+use lru::LruCache;
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// number of IP addresses whose bindings are kept
+const MAX_BOUND_ADDRESSES: usize = 24_000;
+
+/// number of certificates which can be bound to an IP address at once, for
+/// the nodes sharing a host
+const MAX_CERTIFICATES_PER_ADDRESS: usize = 4;
+
+/// time after which the binding of a certificate which was not presented
+/// again expires
+pub(crate) const BINDING_EXPIRY: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BindingError {
+    #[error("the node at {0} did not present its pinned certificate")]
+    NotPinned(SocketAddr),
+    #[error("the certificate is pinned for another address than {0}")]
+    PinnedElsewhere(IpAddr),
+    #[error("other certificates are bound to {0}")]
+    AddressBound(IpAddr),
+}
+
+/// the certificates bound to the addresses of the nodes, shared by all the
+/// connections
+#[derive(Clone)]
+pub struct CertificateBindings {
+    pinned: Arc<HashMap<SocketAddr, Blake2b256>>,
+    bound: Arc<Mutex<LruCache<IpAddr, Vec<Binding>>>>,
+    per_address: usize,
+    expiry: Duration,
+}
+
+impl fmt::Debug for CertificateBindings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CertificateBindings")
+            .field("pinned", &self.pinned.len())
+            .field("per_address", &self.per_address)
+            .field("expiry", &self.expiry)
+            .finish()
+    }
+}
+
+struct Binding {
+    fingerprint: Blake2b256,
+    last_seen: Instant,
+}
+
+impl CertificateBindings {
+    /// the bindings of the nodes, starting with the DER-encoded certificates
+    /// pinned for the trusted peers
+    pub fn new<'a, I>(pinned: I) -> Self
+    where
+        I: IntoIterator<Item = (SocketAddr, &'a [u8])>,
+    {
+        Self::with_limits(pinned, MAX_CERTIFICATES_PER_ADDRESS, BINDING_EXPIRY)
+    }
+
+    /// the bindings with at most `per_address` certificates bound to an IP
+    /// address, for `expiry` since they were last presented
+    pub(crate) fn with_limits<'a, I>(pinned: I, per_address: usize, expiry: Duration) -> Self
+    where
+        I: IntoIterator<Item = (SocketAddr, &'a [u8])>,
+    {
+        CertificateBindings {
+            pinned: Arc::new(
+                pinned
+                    .into_iter()
+                    .map(|(address, certificate)| (address, Blake2b256::new(certificate)))
+                    .collect(),
+            ),
+            bound: Arc::new(Mutex::new(LruCache::new(MAX_BOUND_ADDRESSES))),
+            per_address,
+            expiry,
+        }
+    }
+
+    /// the fingerprint of the certificate pinned for the node at `address`
+    pub fn pinned(&self, address: SocketAddr) -> Option<&Blake2b256> {
+        self.pinned.get(&address)
+    }
+
+    /// whether the certificate is pinned for any trusted peer
+    pub fn is_pinned(&self, fingerprint: &Blake2b256) -> bool {
+        self.pinned.values().any(|pinned| pinned == fingerprint)
+    }
+
+    /// check the certificate presented by the node this node connects to at
+    /// `address`, binding it on first use. The certificate of a node which
+    /// is not pinned must have been verified against the authorities first.
+    pub fn check_server(
+        &self,
+        address: SocketAddr,
+        fingerprint: &Blake2b256,
+    ) -> Result<(), BindingError> {
+        match self.pinned(address) {
+            Some(pinned) if pinned == fingerprint => Ok(()),
+            Some(_) => Err(BindingError::NotPinned(address)),
+            None => self.check_bound(address.ip(), fingerprint, Instant::now()),
+        }
+    }
+
+    /// check the certificate presented by a node connecting from `ip`,
+    /// binding it on first use
+    pub fn check_client(&self, ip: IpAddr, fingerprint: &Blake2b256) -> Result<(), BindingError> {
+        let mut pinned_at = self
+            .pinned
+            .iter()
+            .filter(|(_, pinned)| *pinned == fingerprint)
+            .map(|(address, _)| address.ip())
+            .peekable();
+        if pinned_at.peek().is_none() {
+            self.check_bound(ip, fingerprint, Instant::now())
+        } else if pinned_at.any(|pinned_ip| pinned_ip == ip) {
+            Ok(())
+        } else {
+            Err(BindingError::PinnedElsewhere(ip))
+        }
+    }
+
+    fn check_bound(
+        &self,
+        ip: IpAddr,
+        fingerprint: &Blake2b256,
+        now: Instant,
+    ) -> Result<(), BindingError> {
+        let mut bound = self.bound.lock().unwrap();
+        if !bound.contains(&ip) {
+            bound.put(ip, Vec::new());
+        }
+        let bindings = bound.get_mut(&ip).unwrap();
+        let expiry = self.expiry;
+        bindings.retain(|binding| now.duration_since(binding.last_seen) < expiry);
+        if let Some(binding) = bindings
+            .iter_mut()
+            .find(|binding| binding.fingerprint == *fingerprint)
+        {
+            binding.last_seen = now;
+            Ok(())
+        } else if bindings.len() < self.per_address {
+            bindings.push(Binding {
+                fingerprint: fingerprint.clone(),
+                last_seen: now,
+            });
+            Ok(())
+        } else {
+            Err(BindingError::AddressBound(ip))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn fingerprint(certificate: &[u8]) -> Blake2b256 {
+        Blake2b256::new(certificate)
+    }
+
+    #[test]
+    fn first_use_binds() {
+        let bindings = CertificateBindings::with_limits(None, 1, BINDING_EXPIRY);
+        assert_eq!(
+            bindings.check_server(address(1), &fingerprint(b"a")),
+            Ok(())
+        );
+        assert_eq!(
+            bindings.check_server(address(1), &fingerprint(b"a")),
+            Ok(())
+        );
+        assert_eq!(
+            bindings.check_client(address(1).ip(), &fingerprint(b"a")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn mismatch_is_rejected() {
+        let bindings = CertificateBindings::with_limits(None, 1, BINDING_EXPIRY);
+        assert_eq!(
+            bindings.check_server(address(1), &fingerprint(b"a")),
+            Ok(())
+        );
+        assert_eq!(
+            bindings.check_server(address(1), &fingerprint(b"b")),
+            Err(BindingError::AddressBound(address(1).ip()))
+        );
+        assert_eq!(
+            bindings.check_client(address(1).ip(), &fingerprint(b"b")),
+            Err(BindingError::AddressBound(address(1).ip()))
+        );
+        let other = SocketAddr::from(([127, 0, 0, 2], 1));
+        assert_eq!(bindings.check_server(other, &fingerprint(b"b")), Ok(()));
+    }
+
+    #[test]
+    fn certificates_are_bound_up_to_the_limit() {
+        let bindings = CertificateBindings::with_limits(None, 2, BINDING_EXPIRY);
+        assert_eq!(
+            bindings.check_server(address(1), &fingerprint(b"a")),
+            Ok(())
+        );
+        assert_eq!(
+            bindings.check_server(address(2), &fingerprint(b"b")),
+            Ok(())
+        );
+        assert_eq!(
+            bindings.check_server(address(3), &fingerprint(b"c")),
+            Err(BindingError::AddressBound(address(3).ip()))
+        );
+    }
+
+    #[test]
+    fn pinned_certificate_is_honored() {
+        let bindings =
+            CertificateBindings::with_limits(vec![(address(1), &b"pinned"[..])], 1, BINDING_EXPIRY);
+        assert!(bindings.is_pinned(&fingerprint(b"pinned")));
+        assert_eq!(
+            bindings.check_server(address(1), &fingerprint(b"pinned")),
+            Ok(())
+        );
+        assert_eq!(
+            bindings.check_server(address(1), &fingerprint(b"other")),
+            Err(BindingError::NotPinned(address(1)))
+        );
+        assert_eq!(
+            bindings.check_client(address(1).ip(), &fingerprint(b"pinned")),
+            Ok(())
+        );
+        let other = SocketAddr::from(([127, 0, 0, 2], 1));
+        assert_eq!(
+            bindings.check_client(other.ip(), &fingerprint(b"pinned")),
+            Err(BindingError::PinnedElsewhere(other.ip()))
+        );
+    }
+
+    #[test]
+    fn expired_binding_allows_rotation() {
+        let expiry = Duration::from_secs(60);
+        let bindings = CertificateBindings::with_limits(None, 1, expiry);
+        let ip = address(1).ip();
+        let start = Instant::now();
+        assert_eq!(bindings.check_bound(ip, &fingerprint(b"a"), start), Ok(()));
+        assert_eq!(
+            bindings.check_bound(ip, &fingerprint(b"b"), start + expiry / 2),
+            Err(BindingError::AddressBound(ip))
+        );
+        assert_eq!(
+            bindings.check_bound(ip, &fingerprint(b"a"), start + expiry / 2),
+            Ok(())
+        );
+        assert_eq!(
+            bindings.check_bound(ip, &fingerprint(b"b"), start + expiry),
+            Err(BindingError::AddressBound(ip))
+        );
+        assert_eq!(
+            bindings.check_bound(ip, &fingerprint(b"b"), start + expiry / 2 + expiry),
+            Ok(())
+        );
+    }
+}
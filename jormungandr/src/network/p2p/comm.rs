@@ -2,9 +2,12 @@ mod peer_map;
 
 use peer_map::{CommStatus, PeerMap};
 
-use crate::network::{client::ConnectHandle, p2p::Address};
+use crate::network::{
+    client::ConnectHandle,
+    p2p::{Address, Behavior},
+};
 use chain_network::data::block::{BlockEvent, ChainPullRequest};
-use chain_network::data::{BlockId, BlockIds, Fragment, Gossip, Header};
+use chain_network::data::{BlockId, BlockIds, Fragment, FragmentIds, Gossip, Header};
 use futures::channel::mpsc;
 use futures::lock::{Mutex, MutexLockFuture};
 use futures::prelude::*;
@@ -239,6 +242,7 @@ pub struct PeerComms {
     block_solicitations: CommHandle<BlockIds>,
     chain_pulls: CommHandle<ChainPullRequest>,
     fragments: CommHandle<Fragment>,
+    fragment_solicitations: CommHandle<FragmentIds>,
     gossip: CommHandle<Gossip>,
 }
 
@@ -262,6 +266,8 @@ impl PeerComms {
         self.gossip.update(newer.gossip);
         self.block_solicitations.update(newer.block_solicitations);
         self.chain_pulls.update(newer.chain_pulls);
+        self.fragment_solicitations
+            .update(newer.fragment_solicitations);
     }
 
     pub fn clear_pending(&mut self) {
@@ -270,6 +276,7 @@ impl PeerComms {
         self.gossip.clear_pending();
         self.block_solicitations.clear_pending();
         self.chain_pulls.clear_pending();
+        self.fragment_solicitations.clear_pending();
     }
 
     pub fn set_pending_block_announcement(&mut self, header: Header) {
@@ -314,6 +321,12 @@ impl PeerComms {
         self.chain_pulls.subscribe()
     }
 
+    /// Fragment solicitations are only served by client connections,
+    /// there is no event to feed them into on the server side.
+    pub fn subscribe_to_fragment_solicitations(&mut self) -> OutboundSubscription<FragmentIds> {
+        self.fragment_solicitations.subscribe()
+    }
+
     pub fn subscribe_to_block_events(&mut self) -> BlockEventSubscription {
         let announce_events: BlockEventAnnounceStream = self
             .block_announcements
@@ -350,6 +363,10 @@ impl PeerComms {
     pub fn gossip_subscribed(&self) -> bool {
         self.gossip.is_subscribed()
     }
+
+    pub fn fragment_solicitations_subscribed(&self) -> bool {
+        self.fragment_solicitations.is_subscribed()
+    }
 }
 
 /// Options for Peers::add_connecting
@@ -444,6 +461,8 @@ fn update_last_timestamp(field: &mut Option<SystemTime>, timestamp: SystemTime)
 pub struct PeerInfo {
     pub addr: Option<SocketAddr>,
     pub stats: PeerStats,
+    /// the reputation score of the peer
+    pub score: i32,
 }
 
 /// The collection of currently connected peer nodes.
@@ -672,6 +691,41 @@ impl Peers {
         }
     }
 
+    /// fetch the fragments from the given peer, or from another peer if this
+    /// one is not connected
+    pub async fn fetch_fragments(&self, node_id: Address, ids: FragmentIds) {
+        let mut map = self.inner().await;
+        let subscribed = map
+            .peer_comms(&node_id)
+            .map_or(false, |comms| comms.fragment_solicitations_subscribed());
+        let peer = if subscribed {
+            map.peer_comms(&node_id).map(|comms| (node_id, comms))
+        } else {
+            map.next_peer_for_fragment_fetch()
+        };
+        if let Some((node_id, comms)) = peer {
+            debug!(
+                self.logger,
+                "fetching {} fragments from {}",
+                ids.len(),
+                node_id
+            );
+            comms
+                .fragment_solicitations
+                .try_send(ids)
+                .unwrap_or_else(|e| {
+                    debug!(
+                        self.logger,
+                        "fragment fetch from {} failed: {:?}", node_id, e
+                    );
+                    debug!(self.logger, "unsubscribing peer {}", node_id);
+                    map.remove_peer(node_id);
+                });
+        } else {
+            debug!(self.logger, "no peers to fetch fragments from");
+        }
+    }
+
     pub async fn solicit_blocks(&self, node_id: Address, hashes: BlockIds) {
         let mut map = self.inner().await;
         match map.peer_comms(&node_id) {
@@ -730,6 +784,25 @@ impl Peers {
         }
     }
 
+    pub async fn record_behavior(&self, node_id: Address, behavior: Behavior) {
+        let mut map = self.inner().await;
+        let score = map.record_behavior(node_id.clone(), behavior);
+        debug!(
+            self.logger,
+            "peer reputation updated";
+            "node_id" => %node_id,
+            "behavior" => ?behavior,
+            "score" => score,
+        );
+    }
+
+    /// rank the peers from the best to the worst reputation, leaving out
+    /// the peers with a bad reputation
+    pub async fn rank(&self, nodes: Vec<Address>) -> Vec<Address> {
+        let map = self.inner().await;
+        map.rank(nodes)
+    }
+
     pub async fn infos(&self) -> Vec<PeerInfo> {
         let map = self.inner().await;
         map.infos()
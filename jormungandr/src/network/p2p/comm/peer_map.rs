@@ -2,6 +2,7 @@ use crate::network::{
     client::ConnectHandle,
     p2p::{
         comm::{PeerComms, PeerInfo, PeerStats},
+        reputation::{Behavior, Reputation},
         Address,
     },
 };
@@ -10,6 +11,8 @@ use linked_hash_map::LinkedHashMap;
 pub struct PeerMap {
     map: LinkedHashMap<Address, PeerData>,
     capacity: usize,
+    /// kept for the peers that are no longer connected as well
+    reputation: Reputation,
 }
 
 #[derive(Default)]
@@ -73,6 +76,7 @@ impl PeerMap {
         PeerMap {
             map: LinkedHashMap::new(),
             capacity,
+            reputation: Reputation::default(),
         }
     }
 
@@ -143,6 +147,18 @@ impl PeerMap {
         None
     }
 
+    pub fn next_peer_for_fragment_fetch(&mut self) -> Option<(Address, &mut PeerComms)> {
+        let mut iter = self.map.iter_mut();
+        while let Some((id, data)) = iter.next_back() {
+            if let CommStatus::Established(comms) = data.update_comm_status() {
+                if comms.fragment_solicitations_subscribed() {
+                    return Some((id.clone(), comms));
+                }
+            }
+        }
+        None
+    }
+
     pub fn infos(&self) -> Vec<PeerInfo> {
         use jormungandr_lib::multiaddr::multiaddr_to_socket_addr;
 
@@ -151,24 +167,45 @@ impl PeerMap {
             .map(|(id, data)| PeerInfo {
                 addr: multiaddr_to_socket_addr(id.multi_address()),
                 stats: data.stats.clone(),
+                score: self.reputation.score(id),
             })
             .collect()
     }
 
+    pub fn record_behavior(&mut self, id: Address, behavior: Behavior) -> i32 {
+        self.reputation.record(id, behavior)
+    }
+
+    pub fn rank(&self, ids: Vec<Address>) -> Vec<Address> {
+        self.reputation.rank(ids)
+    }
+
+    /// evict the clients with the worst reputation, the least recently
+    /// used first among the clients with the same score
     pub fn evict_clients(&mut self, num: usize) {
-        for entry in self
+        let mut clients: Vec<Address> = self
             .map
-            .entries()
-            .filter(|entry| entry.get().comms.has_client_subscriptions())
-            .take(num)
-        {
-            entry.remove();
+            .iter()
+            .filter(|(_, data)| data.comms.has_client_subscriptions())
+            .map(|(id, _)| id.clone())
+            .collect();
+        clients.sort_by_key(|id| self.reputation.score(id));
+        for id in clients.into_iter().take(num) {
+            self.map.remove(&id);
         }
     }
 
     fn evict_if_full(&mut self) {
         if self.map.len() >= self.capacity {
-            self.map.pop_front();
+            let reputation = &self.reputation;
+            let worst = self
+                .map
+                .keys()
+                .min_by_key(|id| reputation.score(id))
+                .cloned();
+            if let Some(id) = worst {
+                self.map.remove(&id);
+            }
         }
     }
 }
@@ -1,12 +1,17 @@
+pub mod certificates;
 pub mod comm;
 mod gossip;
 pub mod layers;
+pub mod peer_db;
 mod policy;
+mod reputation;
 mod topology;
 
+pub use self::certificates::CertificateBindings;
 pub use self::gossip::{Gossip, Gossips, Peer, Peers};
 pub use self::policy::{Policy, PolicyConfig};
-pub use self::topology::P2pTopology;
+pub use self::reputation::Behavior;
+pub use self::topology::{P2pTopology, StrikeReason, View};
 
 pub use poldercast::Address;
 
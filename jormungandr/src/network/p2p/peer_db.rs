@@ -0,0 +1,197 @@
+//! database of the peers known to the node, so they are not forgotten when
+//! the node restarts.
+//!
+//! The nodes known to the topology are saved periodically in the storage
+//! directory, with the number of times the `Policy` quarantined them. At
+//! startup, the quarantine records are restored and the nodes that were
+//! available are injected back in the topology. They are also the peers the
+//! node falls back to when none of the trusted peers can be bootstrapped from.
+
+use crate::network::p2p::Address;
+use serde::{Deserialize, Serialize};
+use slog::Logger;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::PathBuf,
+    time::Duration,
+};
+use thiserror::Error;
+
+/// how often the known peers are saved
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const PEER_DB_VERSION: u32 = 1;
+const PEER_DB_FILE: &str = "peers.json";
+const PEER_DB_TMP_FILE: &str = "tmp.peers.json";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot access the peer database")]
+    Io(#[from] io::Error),
+    #[error("cannot encode or decode the peer database")]
+    Json(#[from] serde_json::Error),
+    #[error("unsupported peer database version {0}")]
+    UnsupportedVersion(u32),
+}
+
+/// a node known to the topology when the peers were saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPeer {
+    pub address: Address,
+    /// the node was available, neither quarantined nor forgotten
+    pub available: bool,
+    /// the number of times the node has been quarantined
+    pub quarantine: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PeerDbFile {
+    version: u32,
+    peers: Vec<SavedPeer>,
+}
+
+/// the directory where the known peers are saved
+#[derive(Clone)]
+pub struct PeerDb {
+    dir: PathBuf,
+    logger: Logger,
+}
+
+impl PeerDb {
+    pub fn new(dir: PathBuf, logger: Logger) -> Self {
+        PeerDb { dir, logger }
+    }
+
+    pub fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    /// save the peers, replacing the previously saved ones
+    pub fn write(&self, peers: Vec<SavedPeer>) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir)?;
+        let tmp_path = self.dir.join(PEER_DB_TMP_FILE);
+        let count = peers.len();
+        let db_file = PeerDbFile {
+            version: PEER_DB_VERSION,
+            peers,
+        };
+        {
+            let mut file = BufWriter::new(File::create(&tmp_path)?);
+            serde_json::to_writer(&mut file, &db_file)?;
+            file.flush()?;
+        }
+        fs::rename(&tmp_path, self.dir.join(PEER_DB_FILE))?;
+        debug!(self.logger, "saved {} peers", count);
+        Ok(())
+    }
+
+    /// read the saved peers, there are none if the peers were never saved
+    pub fn read(&self) -> Result<Vec<SavedPeer>, Error> {
+        let file = match File::open(self.dir.join(PEER_DB_FILE)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let db_file: PeerDbFile = serde_json::from_reader(BufReader::new(file))?;
+        if db_file.version != PEER_DB_VERSION {
+            return Err(Error::UnsupportedVersion(db_file.version));
+        }
+        Ok(db_file.peers)
+    }
+
+    /// the saved peers, or none if they cannot be read
+    pub fn read_or_log(&self) -> Vec<SavedPeer> {
+        self.read().unwrap_or_else(|e| {
+            warn!(self.logger, "cannot read the saved peers"; "reason" => %e);
+            Vec::new()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestDir;
+
+    fn peer_db(dir: &TestDir) -> PeerDb {
+        PeerDb::new(dir.path().to_owned(), Logger::root(slog::Discard, o!()))
+    }
+
+    fn peer(port: u16, available: bool, quarantine: u32) -> SavedPeer {
+        SavedPeer {
+            address: format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap(),
+            available,
+            quarantine,
+        }
+    }
+
+    fn summary(peers: &[SavedPeer]) -> Vec<(String, bool, u32)> {
+        peers
+            .iter()
+            .map(|peer| {
+                (
+                    peer.address.multi_address().to_string(),
+                    peer.available,
+                    peer.quarantine,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn saved_peers_are_read_back() {
+        let dir = TestDir::new("peer-db-round-trip");
+        let db = peer_db(&dir);
+        let peers = vec![peer(3000, true, 0), peer(3001, false, 2)];
+        db.write(peers.clone()).unwrap();
+        assert_eq!(summary(&db.read().unwrap()), summary(&peers));
+
+        // the saved peers are replaced
+        let peers = vec![peer(3002, true, 1)];
+        db.write(peers.clone()).unwrap();
+        assert_eq!(summary(&db.read().unwrap()), summary(&peers));
+        assert!(!dir.path().join(PEER_DB_TMP_FILE).exists());
+    }
+
+    #[test]
+    fn no_peers_without_file() {
+        let dir = TestDir::new("peer-db-missing");
+        let db = peer_db(&dir);
+        assert!(db.read().unwrap().is_empty());
+
+        fs::create_dir_all(dir.path()).unwrap();
+        assert!(db.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn corrupt_file_is_an_error() {
+        let dir = TestDir::new("peer-db-corrupt");
+        let db = peer_db(&dir);
+        db.write(vec![peer(3000, true, 0)]).unwrap();
+        let path = dir.path().join(PEER_DB_FILE);
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() / 2]).unwrap();
+
+        assert!(matches!(db.read(), Err(Error::Json(_))));
+        assert!(db.read_or_log().is_empty());
+    }
+
+    #[test]
+    fn unsupported_version_is_an_error() {
+        let dir = TestDir::new("peer-db-version");
+        let db = peer_db(&dir);
+        fs::create_dir_all(dir.path()).unwrap();
+        fs::write(
+            dir.path().join(PEER_DB_FILE),
+            format!(r#"{{"version":{},"peers":[]}}"#, PEER_DB_VERSION + 1),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            db.read(),
+            Err(Error::UnsupportedVersion(version)) if version == PEER_DB_VERSION + 1
+        ));
+        assert!(db.read_or_log().is_empty());
+    }
+}
@@ -4,6 +4,7 @@ use poldercast::{Address, Node, PolicyReport};
 use serde::{Deserialize, Serialize};
 use slog::Logger;
 use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 use std::time::Duration as StdDuration;
 
 /// default quarantine duration is 10min
@@ -22,11 +23,16 @@ const DEFAULT_MAX_NUM_QUARANTINE_RECORDS: usize = 24_000;
 pub struct Policy {
     quarantine_duration: StdDuration,
     max_quarantine: StdDuration,
-    records: LruCache<Address, Records>,
+    records: PolicyRecords,
     quarantine_whitelist: HashSet<Address>,
     logger: Logger,
 }
 
+/// the quarantine records of the `Policy`, shared with the topology so they
+/// can be saved and restored when the node restarts
+#[derive(Debug, Clone)]
+pub struct PolicyRecords(Arc<Mutex<LruCache<Address, Records>>>);
+
 pub struct Records {
     /// record the number of time the given node has been quarantined
     /// in known time.
@@ -53,7 +59,7 @@ impl Policy {
                 .max_quarantine
                 .unwrap_or(DEFAULT_MAX_QUARANTINE_DURATION.into())
                 .into(),
-            records: LruCache::new(
+            records: PolicyRecords::new(
                 pc.max_num_quarantine_records
                     .unwrap_or(DEFAULT_MAX_NUM_QUARANTINE_RECORDS),
             ),
@@ -62,23 +68,54 @@ impl Policy {
         }
     }
 
+    pub fn records(&self) -> PolicyRecords {
+        self.records.clone()
+    }
+
     fn quarantine_duration_for(&mut self, id: Address) -> StdDuration {
-        if let Some(r) = self.records.get_mut(&id) {
+        let mut records = self.records.0.lock().unwrap();
+        if let Some(r) = records.get_mut(&id) {
             r.quarantine_for(self.quarantine_duration, self.max_quarantine)
         } else {
             let r = Records::new();
             let t = r.quarantine_for(self.quarantine_duration, self.max_quarantine);
-            self.records.put(id, r);
+            records.put(id, r);
             t
         }
     }
 
     fn update(&mut self, id: Address) {
-        if let Some(r) = self.records.get_mut(&id) {
+        let mut records = self.records.0.lock().unwrap();
+        if let Some(r) = records.get_mut(&id) {
             r.update();
         } else {
             let r = Records::new();
-            self.records.put(id, r);
+            records.put(id, r);
+        }
+    }
+}
+
+impl PolicyRecords {
+    fn new(capacity: usize) -> Self {
+        PolicyRecords(Arc::new(Mutex::new(LruCache::new(capacity))))
+    }
+
+    /// the number of times every recorded node has been quarantined
+    pub fn quarantines(&self) -> Vec<(Address, u32)> {
+        let records = self.0.lock().unwrap();
+        records
+            .iter()
+            .map(|(address, r)| (address.clone(), r.quarantine))
+            .collect()
+    }
+
+    /// restore the number of times the node has been quarantined, as saved
+    /// before the node restarted. Records already updated since the node
+    /// started are kept.
+    pub fn restore(&self, address: Address, quarantine: u32) {
+        let mut records = self.0.lock().unwrap();
+        if !records.contains(&address) {
+            records.put(address, Records { quarantine });
         }
     }
 }
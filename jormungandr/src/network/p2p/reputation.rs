@@ -0,0 +1,197 @@
+//! reputation of the peers, beyond the quarantine of the `Policy`.
+//!
+//! Every peer has a score, raised by the useful behaviour of the peer (being
+//! the first to announce a new block, delivering it within its slot) and
+//! lowered by its bad behaviour
+//! (invalid headers or fragments, being unreachable). The score slowly goes
+//! back to neutral over time so a peer is not punished (or favoured) forever.
+//!
+//! The peers are ranked with their score when selecting the peers to
+//! propagate to and when evicting peers from the connected peers.
+
+use crate::{blockcfg::HeaderHash, network::p2p::Address};
+use lru::LruCache;
+use std::time::{Duration, Instant};
+
+/// the score of a peer never seen or whose score went back to neutral
+pub const NEUTRAL_SCORE: i32 = 0;
+
+const MIN_SCORE: i32 = -100;
+const MAX_SCORE: i32 = 100;
+
+/// peers with a score at or below this are left out of the views
+const BAD_SCORE_THRESHOLD: i32 = -50;
+
+/// time for a score to move one point back to neutral
+const SCORE_DECAY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// number of peers whose score is kept
+const MAX_SCORE_RECORDS: usize = 24_000;
+
+/// number of block announcements remembered to find the first peer
+/// announcing a block
+const MAX_ANNOUNCEMENT_RECORDS: usize = 1_024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behavior {
+    /// the peer announced a block not yet known, that was applied once
+    /// fetched. Only the first peer announcing it gets rewarded
+    BlockAnnounced(HeaderHash),
+    /// the block the peer was the first to announce was received within
+    /// its slot
+    LowLatency,
+    /// the peer announced a header failing validation
+    InvalidHeader,
+    /// the peer sent a fragment failing validation
+    InvalidFragment,
+    /// the connection to the peer failed or timed out
+    Unreachable,
+    /// the peer sent more fragments or gossip than it is allowed to
+    RateLimitExceeded,
+}
+
+struct Score {
+    value: i32,
+    updated: Instant,
+}
+
+pub struct Reputation {
+    scores: LruCache<Address, Score>,
+    announcements: LruCache<HeaderHash, ()>,
+}
+
+impl Behavior {
+    fn weight(self) -> i32 {
+        match self {
+            Behavior::BlockAnnounced(_) => 5,
+            Behavior::LowLatency => 2,
+            Behavior::InvalidHeader => -30,
+            Behavior::InvalidFragment => -5,
+            Behavior::Unreachable => -10,
+            Behavior::RateLimitExceeded => -20,
+        }
+    }
+}
+
+impl Score {
+    fn new() -> Self {
+        Score {
+            value: NEUTRAL_SCORE,
+            updated: Instant::now(),
+        }
+    }
+
+    /// the score, moved back towards neutral for the time elapsed since
+    /// the last update
+    fn current(&self) -> i32 {
+        let decay = (self.updated.elapsed().as_secs() / SCORE_DECAY_INTERVAL.as_secs()) as i32;
+        if self.value > NEUTRAL_SCORE {
+            std::cmp::max(self.value.saturating_sub(decay), NEUTRAL_SCORE)
+        } else {
+            std::cmp::min(self.value.saturating_add(decay), NEUTRAL_SCORE)
+        }
+    }
+
+    fn add(&mut self, weight: i32) -> i32 {
+        let value = self.current().saturating_add(weight);
+        self.value = std::cmp::min(std::cmp::max(value, MIN_SCORE), MAX_SCORE);
+        self.updated = Instant::now();
+        self.value
+    }
+}
+
+impl Default for Reputation {
+    fn default() -> Self {
+        Reputation {
+            scores: LruCache::new(MAX_SCORE_RECORDS),
+            announcements: LruCache::new(MAX_ANNOUNCEMENT_RECORDS),
+        }
+    }
+}
+
+impl Reputation {
+    pub fn score(&self, address: &Address) -> i32 {
+        self.scores
+            .peek(address)
+            .map_or(NEUTRAL_SCORE, |score| score.current())
+    }
+
+    /// a peer with a bad reputation should not be selected to propagate to
+    pub fn is_bad(&self, address: &Address) -> bool {
+        self.score(address) <= BAD_SCORE_THRESHOLD
+    }
+
+    /// update the score of the peer for the given behaviour, returns the
+    /// new score
+    pub fn record(&mut self, address: Address, behavior: Behavior) -> i32 {
+        if let Behavior::BlockAnnounced(header_hash) = behavior {
+            if self.announcements.put(header_hash, ()).is_some() {
+                return self.score(&address);
+            }
+        }
+        match self.scores.get_mut(&address) {
+            Some(score) => score.add(behavior.weight()),
+            None => {
+                let mut score = Score::new();
+                let value = score.add(behavior.weight());
+                self.scores.put(address, score);
+                value
+            }
+        }
+    }
+
+    /// sort the addresses from the best to the worst reputation, leaving
+    /// out the peers with a bad reputation
+    pub fn rank(&self, mut addresses: Vec<Address>) -> Vec<Address> {
+        addresses.retain(|address| !self.is_bad(address));
+        addresses.sort_by_key(|address| std::cmp::Reverse(self.score(address)));
+        addresses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn address(port: u16) -> Address {
+        Address::new(SocketAddr::from(([127, 0, 0, 1], port))).unwrap()
+    }
+
+    #[test]
+    fn only_first_announcement_is_rewarded() {
+        let mut reputation = Reputation::default();
+        let header_hash = HeaderHash::hash_bytes(&[0]);
+        reputation.record(address(1), Behavior::BlockAnnounced(header_hash));
+        reputation.record(address(2), Behavior::BlockAnnounced(header_hash));
+        assert!(reputation.score(&address(1)) > NEUTRAL_SCORE);
+        assert_eq!(reputation.score(&address(2)), NEUTRAL_SCORE);
+    }
+
+    #[test]
+    fn low_latency_adds_to_the_announcement_reward() {
+        let mut reputation = Reputation::default();
+        let header_hash = HeaderHash::hash_bytes(&[0]);
+        reputation.record(address(1), Behavior::BlockAnnounced(header_hash));
+        reputation.record(address(2), Behavior::BlockAnnounced(header_hash));
+        reputation.record(address(2), Behavior::LowLatency);
+        reputation.record(address(1), Behavior::LowLatency);
+        assert!(reputation.score(&address(1)) > reputation.score(&address(2)));
+        assert!(reputation.score(&address(2)) > NEUTRAL_SCORE);
+    }
+
+    #[test]
+    fn rank_leaves_out_bad_peers() {
+        let mut reputation = Reputation::default();
+        for _ in 0..2 {
+            reputation.record(address(1), Behavior::InvalidHeader);
+        }
+        reputation.record(address(2), Behavior::Unreachable);
+        reputation.record(
+            address(3),
+            Behavior::BlockAnnounced(HeaderHash::hash_bytes(&[0])),
+        );
+        let ranked = reputation.rank(vec![address(1), address(2), address(3), address(4)]);
+        assert_eq!(ranked, vec![address(3), address(4), address(2)]);
+    }
+}
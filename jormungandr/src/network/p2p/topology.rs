@@ -3,18 +3,45 @@
 
 use crate::{
     log::KEY_SUB_TASK,
-    network::p2p::{layers::PreferredListLayer, Address, Gossips, Policy, PolicyConfig},
+    network::p2p::{
+        layers::PreferredListLayer, peer_db::SavedPeer, policy::PolicyRecords, Address, Gossips,
+        Policy, PolicyConfig,
+    },
     settings::start::network::Configuration,
 };
+use jormungandr_lib::multiaddr::multiaddr_to_socket_addr;
 use poldercast::{
     custom_layers,
     poldercast::{Cyclon, Rings, Vicinity},
-    NodeProfile, PolicyReport, StrikeReason, Topology,
+    NodeProfile, PolicyReport, Topology,
 };
 use rand_chacha::ChaChaRng;
 use slog::Logger;
+use std::collections::HashMap;
+use std::net::IpAddr;
 use tokio::sync::RwLock;
 
+/// the reason a node is struck in the topology
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrikeReason {
+    /// the node cannot be connected to
+    CannotConnect,
+    /// the node went over its rate limits
+    RateLimited,
+}
+
+impl StrikeReason {
+    /// the records of poldercast only know of connection failures, the
+    /// policy quarantines a node with a strike whatever its reason
+    fn to_poldercast(self) -> poldercast::StrikeReason {
+        match self {
+            StrikeReason::CannotConnect | StrikeReason::RateLimited => {
+                poldercast::StrikeReason::CannotConnect
+            }
+        }
+    }
+}
+
 pub struct View {
     pub self_node: NodeProfile,
     pub peers: Vec<Address>,
@@ -23,11 +50,13 @@ pub struct View {
 /// object holding the P2pTopology of the Node
 pub struct P2pTopology {
     lock: RwLock<Topology>,
+    policy_records: PolicyRecords,
 }
 
 /// Builder object used to initialize the `P2pTopology`
 struct Builder {
     topology: Topology,
+    policy_records: Option<PolicyRecords>,
     logger: Logger,
 }
 
@@ -36,15 +65,15 @@ impl Builder {
     fn new(node: poldercast::NodeProfile, logger: Logger) -> Self {
         Builder {
             topology: Topology::new(node),
+            policy_records: None,
             logger,
         }
     }
 
     fn set_policy(mut self, policy: PolicyConfig) -> Self {
-        self.topology.set_policy(Policy::new(
-            policy,
-            self.logger.new(o!(KEY_SUB_TASK => "policy")),
-        ));
+        let policy = Policy::new(policy, self.logger.new(o!(KEY_SUB_TASK => "policy")));
+        self.policy_records = Some(policy.records());
+        self.topology.set_policy(policy);
         self
     }
 
@@ -76,6 +105,7 @@ impl Builder {
     fn build(self) -> P2pTopology {
         P2pTopology {
             lock: RwLock::new(self.topology),
+            policy_records: self.policy_records.expect("the policy to be set"),
         }
     }
 }
@@ -160,6 +190,40 @@ impl P2pTopology {
         topology.nodes().node_count()
     }
 
+    /// the nodes known to the topology and the quarantine records of the
+    /// policy, to be saved in the peer database
+    pub async fn saved_peers(&self) -> Vec<SavedPeer> {
+        let mut peers: HashMap<Address, SavedPeer> = HashMap::new();
+        for (address, quarantine) in self.policy_records.quarantines() {
+            let peer = SavedPeer {
+                address: address.clone(),
+                available: false,
+                quarantine,
+            };
+            peers.insert(address, peer);
+        }
+        for node in self.list_available().await {
+            let address = node.address().clone();
+            peers
+                .entry(address.clone())
+                .or_insert(SavedPeer {
+                    address,
+                    available: false,
+                    quarantine: 0,
+                })
+                .available = true;
+        }
+        peers.into_iter().map(|(_, peer)| peer).collect()
+    }
+
+    /// restore the quarantine records saved in the peer database
+    pub fn restore_policy_records(&self, peers: &[SavedPeer]) {
+        for peer in peers.iter().filter(|peer| peer.quarantine > 0) {
+            self.policy_records
+                .restore(peer.address.clone(), peer.quarantine);
+        }
+    }
+
     /// register a strike against the given node id
     ///
     /// the function returns `None` if the node was not even in the
@@ -167,7 +231,24 @@ impl P2pTopology {
     pub async fn report_node(&self, address: Address, issue: StrikeReason) -> Option<PolicyReport> {
         let mut topology = self.lock.write().await;
         topology.update_node(address, |node| {
-            node.record_mut().strike(issue);
+            node.record_mut().strike(issue.to_poldercast());
         })
     }
+
+    /// the available node gossiped with the given IP address, `None` if
+    /// there is no such node or if several nodes share the address
+    pub async fn node_with_ip(&self, ip: IpAddr) -> Option<Address> {
+        let topology = self.lock.read().await;
+        let nodes = topology.nodes().all_available_nodes();
+        let mut matching = nodes
+            .into_iter()
+            .map(|node| node.address())
+            .filter(|address| {
+                multiaddr_to_socket_addr(address.multi_address()).map(|addr| addr.ip()) == Some(ip)
+            });
+        match (matching.next(), matching.next()) {
+            (Some(address), None) => Some(address.clone()),
+            _ => None,
+        }
+    }
 }
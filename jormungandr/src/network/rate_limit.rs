@@ -0,0 +1,336 @@
+//! rate limits of what the other nodes ask from or send to this node.
+//!
+//! The limits are token buckets: every request or item takes a token from
+//! the bucket, which is refilled at a constant rate up to its burst size.
+//!
+//! * the requests for blocks, headers and fragments are limited for every
+//!   IP address the requests come from and for all the peers at once. The
+//!   requests made while a bucket is empty are refused, and the known nodes
+//!   going over their own limit get a strike;
+//! * the blocks sent in the responses are limited for all the peers at once
+//!   too. The responses are slowed down while the bucket is empty, to keep the
+//!   uplink of the node from being saturated;
+//! * the fragments and the gossip received on the subscriptions are limited
+//!   for every peer and for all the peers at once. The items received while a
+//!   bucket is empty are dropped, and the peers going over their own limit
+//!   get a strike.
+
+use crate::network::p2p::Address;
+use futures::{prelude::*, ready};
+use lru::LruCache;
+use pin_project::pin_project;
+use serde::{Deserialize, Serialize};
+use std::{
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use tokio::time::{self, Delay};
+
+/// number of peers whose buckets are kept
+const MAX_PEER_BUCKETS: usize = 24_000;
+
+tokio::task_local! {
+    /// the address of the peer whose request is being served
+    pub static REMOTE_ADDR: Option<SocketAddr>;
+}
+
+#[derive(Debug, Error)]
+#[error("too many requests, the node is rate limiting the requests")]
+pub struct RequestLimitExceeded(pub Exceeded);
+
+/// a limit which cannot be exceeded for long
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// the number of requests or items allowed every second
+    pub rate: u32,
+    /// the number of requests or items allowed at once, above the rate
+    pub burst: u32,
+}
+
+/// limits for every peer and for all the peers at once
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PeerRateLimits {
+    #[serde(default)]
+    pub per_peer: Option<RateLimit>,
+    #[serde(default)]
+    pub global: Option<RateLimit>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitsConfig {
+    /// the requests for blocks, headers and fragments
+    #[serde(default)]
+    pub requests: PeerRateLimits,
+    /// the blocks sent in the responses
+    #[serde(default)]
+    pub blocks: Option<RateLimit>,
+    /// the fragments received on the subscriptions
+    #[serde(default)]
+    pub fragments: PeerRateLimits,
+    /// the gossip received on the subscriptions
+    #[serde(default)]
+    pub gossip: PeerRateLimits,
+}
+
+/// the reason an item received from a peer is dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exceeded {
+    /// the limit of the peer is exceeded, `newly` is set for the first
+    /// item over the limit since the peer was last under it
+    Peer { newly: bool },
+    /// the limit of all the peers is exceeded
+    Global,
+}
+
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+    exceeded: bool,
+}
+
+/// limits of the items received from the peers, identified by `K`
+pub struct PeerRateLimiter<K = Address> {
+    per_peer: Option<(RateLimit, Mutex<LruCache<K, TokenBucket>>)>,
+    global: Option<Mutex<TokenBucket>>,
+}
+
+/// all the rate limits of the node
+pub struct RateLimits {
+    /// the requests, limited by the IP address they come from as a peer
+    /// gets another port every time it connects
+    requests: PeerRateLimiter<IpAddr>,
+    blocks: Option<Arc<Mutex<TokenBucket>>>,
+    pub fragments: PeerRateLimiter,
+    pub gossip: PeerRateLimiter,
+}
+
+/// A stream adapter taking a token for every item of the wrapped stream,
+/// waiting for the bucket to be refilled when it is empty.
+#[must_use = "streams do nothing unless polled"]
+#[pin_project]
+pub struct Throttle<S> {
+    #[pin]
+    stream: S,
+    bucket: Option<Arc<Mutex<TokenBucket>>>,
+    #[pin]
+    delay: Option<Delay>,
+    has_token: bool,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> Self {
+        TokenBucket {
+            limit,
+            tokens: f64::from(limit.burst),
+            updated: Instant::now(),
+            exceeded: false,
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        let tokens = self.tokens + elapsed * f64::from(self.limit.rate);
+        self.tokens = tokens.min(f64::from(self.limit.burst));
+        self.updated = now;
+    }
+
+    /// take a token, returns the time to wait for one if the bucket is empty
+    fn take(&mut self) -> Result<(), Duration> {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.exceeded = false;
+            Ok(())
+        } else if self.limit.rate == 0 {
+            Err(Duration::from_secs(1))
+        } else {
+            let missing = 1.0 - self.tokens;
+            Err(Duration::from_secs_f64(
+                missing / f64::from(self.limit.rate),
+            ))
+        }
+    }
+
+    /// take a token, returns `Some` if the bucket is empty with whether it
+    /// just went empty
+    fn try_take(&mut self) -> Option<bool> {
+        match self.take() {
+            Ok(()) => None,
+            Err(_) => {
+                let newly = !self.exceeded;
+                self.exceeded = true;
+                Some(newly)
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> PeerRateLimiter<K> {
+    fn new(limits: &PeerRateLimits) -> Self {
+        PeerRateLimiter {
+            per_peer: limits
+                .per_peer
+                .map(|limit| (limit, Mutex::new(LruCache::new(MAX_PEER_BUCKETS)))),
+            global: limits
+                .global
+                .map(|limit| Mutex::new(TokenBucket::new(limit))),
+        }
+    }
+
+    /// take the tokens for an item received from the peer
+    pub fn check(&self, peer: &K) -> Result<(), Exceeded> {
+        self.check_peer(peer)?;
+        self.check_global()
+    }
+
+    fn check_peer(&self, peer: &K) -> Result<(), Exceeded> {
+        if let Some((limit, buckets)) = &self.per_peer {
+            let mut buckets = buckets.lock().unwrap();
+            let exceeded = match buckets.get_mut(peer) {
+                Some(bucket) => bucket.try_take(),
+                None => {
+                    let mut bucket = TokenBucket::new(*limit);
+                    let exceeded = bucket.try_take();
+                    buckets.put(peer.clone(), bucket);
+                    exceeded
+                }
+            };
+            if let Some(newly) = exceeded {
+                return Err(Exceeded::Peer { newly });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_global(&self) -> Result<(), Exceeded> {
+        if let Some(bucket) = &self.global {
+            if bucket.lock().unwrap().try_take().is_some() {
+                return Err(Exceeded::Global);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RateLimits {
+    pub fn new(config: &RateLimitsConfig) -> Self {
+        RateLimits {
+            requests: PeerRateLimiter::new(&config.requests),
+            blocks: config
+                .blocks
+                .map(|limit| Arc::new(Mutex::new(TokenBucket::new(limit)))),
+            fragments: PeerRateLimiter::new(&config.fragments),
+            gossip: PeerRateLimiter::new(&config.gossip),
+        }
+    }
+
+    /// take the tokens for a request for blocks, headers or fragments made
+    /// from the IP address, only the global limit applies if it is not known
+    pub fn check_request(&self, ip: Option<IpAddr>) -> Result<(), RequestLimitExceeded> {
+        match ip {
+            Some(ip) => self.requests.check(&ip),
+            None => self.requests.check_global(),
+        }
+        .map_err(RequestLimitExceeded)
+    }
+
+    /// limit the rate of the blocks sent in a response
+    pub fn throttle_blocks<S: Stream>(&self, stream: S) -> Throttle<S> {
+        Throttle {
+            stream,
+            bucket: self.blocks.clone(),
+            delay: None,
+            has_token: false,
+        }
+    }
+}
+
+impl<S: Stream> Stream for Throttle<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        if let Some(bucket) = this.bucket {
+            while !*this.has_token {
+                if let Some(delay) = this.delay.as_mut().as_pin_mut() {
+                    ready!(delay.poll(cx));
+                    this.delay.set(None);
+                }
+                match bucket.lock().unwrap().take() {
+                    Ok(()) => *this.has_token = true,
+                    Err(wait) => this.delay.set(Some(time::delay_for(wait))),
+                }
+            }
+        }
+        let item = ready!(this.stream.poll_next(cx));
+        *this.has_token = false;
+        Poll::Ready(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn address(port: u16) -> Address {
+        Address::new(SocketAddr::from(([127, 0, 0, 1], port))).unwrap()
+    }
+
+    #[test]
+    fn peers_are_limited_separately() {
+        let limiter = PeerRateLimiter::new(&PeerRateLimits {
+            per_peer: Some(RateLimit { rate: 0, burst: 2 }),
+            global: None,
+        });
+        assert_eq!(limiter.check(&address(1)), Ok(()));
+        assert_eq!(limiter.check(&address(1)), Ok(()));
+        assert_eq!(
+            limiter.check(&address(1)),
+            Err(Exceeded::Peer { newly: true })
+        );
+        assert_eq!(
+            limiter.check(&address(1)),
+            Err(Exceeded::Peer { newly: false })
+        );
+        assert_eq!(limiter.check(&address(2)), Ok(()));
+    }
+
+    #[test]
+    fn requests_of_unknown_peers_are_only_limited_globally() {
+        let limits = RateLimits::new(&RateLimitsConfig {
+            requests: PeerRateLimits {
+                per_peer: Some(RateLimit { rate: 0, burst: 1 }),
+                global: Some(RateLimit { rate: 0, burst: 3 }),
+            },
+            ..RateLimitsConfig::default()
+        });
+        let ip = |n: u8| Some(IpAddr::from([10, 0, 0, n]));
+        assert!(limits.check_request(ip(1)).is_ok());
+        assert!(limits.check_request(ip(1)).is_err());
+        assert!(limits.check_request(None).is_ok());
+        assert!(limits.check_request(None).is_ok());
+        assert!(limits.check_request(ip(2)).is_err());
+    }
+
+    #[test]
+    fn global_limit_applies_to_all_peers() {
+        let limiter = PeerRateLimiter::new(&PeerRateLimits {
+            per_peer: Some(RateLimit { rate: 0, burst: 2 }),
+            global: Some(RateLimit { rate: 0, burst: 2 }),
+        });
+        assert_eq!(limiter.check(&address(1)), Ok(()));
+        assert_eq!(limiter.check(&address(2)), Ok(()));
+        assert_eq!(limiter.check(&address(3)), Err(Exceeded::Global));
+    }
+}
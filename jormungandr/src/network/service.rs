@@ -3,10 +3,11 @@ use super::{
     convert::{self, Decode, Encode, ResponseStream},
     p2p::comm::{BlockEventSubscription, FragmentSubscription, GossipSubscription},
     p2p::Address,
+    rate_limit::{Exceeded, RequestLimitExceeded, Throttle, REMOTE_ADDR},
     subscription, Channels, GlobalStateR,
 };
 use crate::blockcfg as app_data;
-use crate::intercom::{self, BlockMsg, ClientMsg};
+use crate::intercom::{self, BlockMsg, ClientMsg, TransactionMsg};
 use crate::utils::async_msg::MessageBox;
 use chain_network::core::server::{BlockService, FragmentService, GossipService, Node, PushStream};
 use chain_network::data::{
@@ -49,6 +50,30 @@ impl NodeService {
         self.logger
             .new(o!("peer" => subscriber.to_string(), "stream" => stream_name))
     }
+
+    fn check_request_rate(&self, logger: &Logger) -> Result<(), Error> {
+        // the address of the peer is known when the request is served by the
+        // listener of the node
+        let remote_addr = REMOTE_ADDR.try_with(|addr| *addr).ok().flatten();
+        let ip = remote_addr.map(|addr| addr.ip());
+        let rate_limits = &self.global_state.rate_limits;
+        rate_limits.check_request(ip).map_err(|e| {
+            match (&e, ip) {
+                (RequestLimitExceeded(Exceeded::Peer { newly: true }), Some(ip)) => {
+                    info!(
+                        logger,
+                        "peer exceeded its rate limit, refusing its requests";
+                        "peer_addr" => ?remote_addr,
+                    );
+                    let state = self.global_state.clone();
+                    self.global_state
+                        .spawn(async move { state.report_rate_limited(ip).await });
+                }
+                _ => debug!(logger, "refusing request"; "reason" => %e),
+            }
+            Error::new(net_error::Code::Unavailable, e)
+        })
+    }
 }
 
 impl Node for NodeService {
@@ -89,8 +114,8 @@ fn serve_subscription<S: Stream>(sub: S) -> SubscriptionStream<S> {
 
 #[async_trait]
 impl BlockService for NodeService {
-    type PullBlocksToTipStream = ResponseStream<app_data::Block>;
-    type GetBlocksStream = ResponseStream<app_data::Block>;
+    type PullBlocksToTipStream = Throttle<ResponseStream<app_data::Block>>;
+    type GetBlocksStream = Throttle<ResponseStream<app_data::Block>>;
     type PullHeadersStream = ResponseStream<app_data::Header>;
     type GetHeadersStream = ResponseStream<app_data::Header>;
     type SubscriptionStream = SubscriptionStream<BlockEventSubscription>;
@@ -114,28 +139,37 @@ impl BlockService for NodeService {
     ) -> Result<Self::PullBlocksToTipStream, Error> {
         let from = from.decode()?;
         let logger = self.logger().new(o!("request" => "PullBlocksToTip"));
+        self.check_request_rate(&logger)?;
         let (handle, future) =
             intercom::stream_reply(buffer_sizes::outbound::BLOCKS, logger.clone());
         let client_box = self.channels.client_box.clone();
         send_message(client_box, ClientMsg::PullBlocksToTip(from, handle), logger).await?;
         let stream = future.await?;
-        Ok(convert::response_stream(stream))
+        Ok(self
+            .global_state
+            .rate_limits
+            .throttle_blocks(convert::response_stream(stream)))
     }
 
     async fn get_blocks(&self, ids: BlockIds) -> Result<Self::GetBlocksStream, Error> {
         let ids = ids.decode()?;
         let logger = self.logger().new(o!("request" => "GetBlocks"));
+        self.check_request_rate(&logger)?;
         let (handle, future) =
             intercom::stream_reply(buffer_sizes::outbound::BLOCKS, logger.clone());
         let client_box = self.channels.client_box.clone();
         send_message(client_box, ClientMsg::GetBlocks(ids, handle), logger).await?;
         let stream = future.await?;
-        Ok(convert::response_stream(stream))
+        Ok(self
+            .global_state
+            .rate_limits
+            .throttle_blocks(convert::response_stream(stream)))
     }
 
     async fn get_headers(&self, ids: BlockIds) -> Result<Self::GetHeadersStream, Error> {
         let ids = ids.decode()?;
         let logger = self.logger().new(o!("request" => "GetHeaders"));
+        self.check_request_rate(&logger)?;
         let (handle, future) =
             intercom::stream_reply(buffer_sizes::outbound::HEADERS, logger.clone());
         let client_box = self.channels.client_box.clone();
@@ -152,6 +186,7 @@ impl BlockService for NodeService {
         let from = from.decode()?;
         let to = to.decode()?;
         let logger = self.logger().new(o!("request" => "PullHeaders"));
+        self.check_request_rate(&logger)?;
         let (handle, future) =
             intercom::stream_reply(buffer_sizes::outbound::HEADERS, logger.clone());
         let client_box = self.channels.client_box.clone();
@@ -227,8 +262,21 @@ impl FragmentService for NodeService {
     type GetFragmentsStream = ResponseStream<app_data::Fragment>;
     type SubscriptionStream = SubscriptionStream<FragmentSubscription>;
 
-    async fn get_fragments(&self, _ids: FragmentIds) -> Result<Self::GetFragmentsStream, Error> {
-        Err(net_error::Error::unimplemented())
+    async fn get_fragments(&self, ids: FragmentIds) -> Result<Self::GetFragmentsStream, Error> {
+        let ids = ids.decode()?;
+        let logger = self.logger().new(o!("request" => "GetFragments"));
+        self.check_request_rate(&logger)?;
+        let (handle, future) =
+            intercom::stream_reply(buffer_sizes::outbound::FRAGMENTS, logger.clone());
+        let transaction_box = self.channels.transaction_box.clone();
+        send_message(
+            transaction_box,
+            TransactionMsg::GetFragments(ids, handle),
+            logger,
+        )
+        .await?;
+        let stream = future.await?;
+        Ok(convert::response_stream(stream))
     }
 
     async fn fragment_subscription(
@@ -288,8 +336,7 @@ impl GossipService for NodeService {
     async fn peers(&self, limit: u32) -> Result<Peers, Error> {
         use jormungandr_lib::multiaddr::multiaddr_to_socket_addr;
 
-        let topology = &self.global_state.topology;
-        let view = topology.view(poldercast::Selection::Any).await;
+        let view = self.global_state.view(poldercast::Selection::Any).await;
         let mut peers = Vec::new();
         for n in view.peers.into_iter() {
             if let Some(addr) = multiaddr_to_socket_addr(n.multi_address()) {
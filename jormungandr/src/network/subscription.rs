@@ -1,18 +1,21 @@
 use super::{
     buffer_sizes,
     convert::Decode,
-    p2p::{Address, Gossip},
+    p2p::{Address, Behavior, Gossip},
+    rate_limit::Exceeded,
     GlobalStateR,
 };
 use crate::{
     blockcfg::Fragment,
-    intercom::{BlockMsg, TransactionMsg},
+    fragment,
+    intercom::{self, BlockMsg, TransactionMsg},
     settings::start::network::Configuration,
     utils::async_msg::{self, MessageBox},
 };
 use chain_network::data as net_data;
 use chain_network::error::{Code, Error};
 use jormungandr_lib::interfaces::FragmentOrigin;
+use jormungandr_lib::multiaddr::multiaddr_to_socket_addr;
 
 use futures::future::BoxFuture;
 use futures::prelude::*;
@@ -41,6 +44,35 @@ fn handle_mbox_error(err: async_msg::SendError, logger: &Logger) -> Error {
     Error::new(Code::Internal, err)
 }
 
+/// drop an item over the rate limits, the peer going over its own limit is
+/// reported once until it gets under the limit again
+fn handle_rate_limit_exceeded(
+    exceeded: Exceeded,
+    node_id: &Address,
+    global_state: &GlobalStateR,
+    logger: &Logger,
+) {
+    match exceeded {
+        Exceeded::Peer { newly: true } => {
+            info!(
+                logger,
+                "peer exceeded its rate limit, dropping the items it sends"
+            );
+            if let Some(addr) = multiaddr_to_socket_addr(node_id.multi_address()) {
+                let state = global_state.clone();
+                global_state.spawn(async move { state.report_rate_limited(addr.ip()).await });
+            }
+        }
+        Exceeded::Peer { newly: false } => {}
+        Exceeded::Global => {
+            debug!(
+                logger,
+                "rate limit of all the peers exceeded, dropping item"
+            );
+        }
+    }
+}
+
 pub async fn process_block_announcements<S>(
     stream: S,
     mbox: MessageBox<BlockMsg>,
@@ -296,12 +328,18 @@ impl Sink<net_data::Fragment> for FragmentProcessor {
             self.buffered_fragments.len() < buffer_sizes::inbound::FRAGMENTS,
             "should call `poll_ready` which returns `Poll::Ready(Ok(()))` before `start_send`",
         );
+        let rate_limits = &self.global_state.rate_limits;
+        if let Err(exceeded) = rate_limits.fragments.check(&self.node_id) {
+            handle_rate_limit_exceeded(exceeded, &self.node_id, &self.global_state, &self.logger);
+            return Ok(());
+        }
         let fragment = raw_fragment.decode().map_err(|e| {
             info!(
                 self.logger,
                 "failed to decode incoming fragment";
                 "reason" => %e.source().unwrap(),
             );
+            self.report_invalid_fragments();
             e
         })?;
         debug!(self.logger, "received fragment"; "hash" => %fragment.hash());
@@ -355,10 +393,13 @@ impl FragmentProcessor {
             &mut self.buffered_fragments,
             Vec::with_capacity(buffer_sizes::inbound::FRAGMENTS),
         );
+        let (reply_handle, reply_future) = intercom::unary_reply(self.logger.clone());
         self.mbox
             .start_send(TransactionMsg::SendTransaction(
                 FragmentOrigin::Network,
+                Some(self.node_id.clone()),
                 fragments,
+                Some(reply_handle),
             ))
             .map_err(|e| {
                 error!(
@@ -369,9 +410,32 @@ impl FragmentProcessor {
                 Error::new(Code::Internal, e)
             })?;
         self.refresh_stat();
+        let state = self.global_state.clone();
+        let node_id = self.node_id.clone();
+        self.global_state.spawn(async move {
+            if let Ok(submissions) = reply_future.await {
+                if submissions.iter().any(fragment::is_rejected_as_invalid) {
+                    state
+                        .peers
+                        .record_behavior(node_id, Behavior::InvalidFragment)
+                        .await;
+                }
+            }
+        });
         Poll::Ready(Ok(()))
     }
 
+    fn report_invalid_fragments(&self) {
+        let state = self.global_state.clone();
+        let node_id = self.node_id.clone();
+        self.global_state.spawn(async move {
+            state
+                .peers
+                .record_behavior(node_id, Behavior::InvalidFragment)
+                .await;
+        });
+    }
+
     fn poll_flush_mbox(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
         Pin::new(&mut self.mbox).poll_flush(cx).map_err(|e| {
             error!(
@@ -397,6 +461,11 @@ impl Sink<net_data::Gossip> for GossipProcessor {
     }
 
     fn start_send(mut self: Pin<&mut Self>, gossip: net_data::Gossip) -> Result<(), Error> {
+        let rate_limits = &self.global_state.rate_limits;
+        if let Err(exceeded) = rate_limits.gossip.check(&self.node_id) {
+            handle_rate_limit_exceeded(exceeded, &self.node_id, &self.global_state, &self.logger);
+            return Ok(());
+        }
         let nodes = gossip.nodes.decode().map_err(|e| {
             info!(
                 self.logger,
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use crate::{
     blockchain::{Blockchain, Tip},
     diagnostic::Diagnostic,
+    events::Events,
     intercom::{NetworkMsg, TransactionMsg},
     leadership::Logs as LeadershipLogs,
     network::GlobalStateR as NetworkStateR,
@@ -140,4 +141,5 @@ pub struct FullContext {
     pub enclave: Enclave,
     pub network_state: NetworkStateR,
     pub explorer: Option<crate::explorer::Explorer>,
+    pub events: Events,
 }
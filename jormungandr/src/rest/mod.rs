@@ -10,7 +10,7 @@ use crate::settings::start::{Rest, Tls};
 
 use futures::{channel::mpsc, prelude::*};
 use std::{error::Error, net::SocketAddr, time::Duration};
-use warp::Filter;
+use warp::{http::StatusCode, Filter, Reply};
 
 #[derive(Clone)]
 pub struct ServerStopper(mpsc::Sender<()>);
@@ -104,3 +104,29 @@ pub(self) fn display_internal_server_error(err: &impl Error) -> String {
 
     error_to_body(err).unwrap_or_else(|err| format!("failed to process internal error: {}", err))
 }
+
+/// body of the error responses of the REST API
+#[derive(Serialize)]
+struct ErrorBody {
+    /// HTTP status code of the response
+    code: u16,
+    message: String,
+    /// the chain of errors that caused this one, from the closest to the
+    /// root cause
+    causes: Vec<String>,
+}
+
+pub(self) fn error_reply(err: &dyn Error, code: StatusCode) -> impl Reply {
+    let mut causes = Vec::new();
+    let mut source = err.source();
+    while let Some(err) = source {
+        causes.push(err.to_string());
+        source = err.source();
+    }
+    let body = ErrorBody {
+        code: code.as_u16(),
+        message: err.to_string(),
+        causes,
+    };
+    warp::reply::with_status(warp::reply::json(&body), code)
+}
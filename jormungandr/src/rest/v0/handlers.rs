@@ -2,20 +2,41 @@ use crate::{
     rest::{v0::logic, ContextLock},
     secure::NodeSecret,
 };
+use futures::{future, stream::StreamExt};
+use std::convert::Infallible;
+use tokio::sync::broadcast::RecvError;
 use warp::{reject::Reject, Rejection, Reply};
 
 impl Reject for logic::Error {}
 
+/// the requested resource does not exist
+#[derive(Debug, thiserror::Error)]
+#[error("{0} not found")]
+pub struct NotFound(&'static str);
+
+impl Reject for NotFound {}
+
+fn not_found(what: &'static str) -> Rejection {
+    warp::reject::custom(NotFound(what))
+}
+
+/// the block a query is made at, see `logic::get_ref_at`
+#[derive(Deserialize)]
+pub struct AtBlockQuery {
+    at: Option<String>,
+}
+
 pub async fn get_account_state(
     account_id_hex: String,
+    query: AtBlockQuery,
     context: ContextLock,
 ) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
-    logic::get_account_state(&context, &account_id_hex)
+    logic::get_account_state(&context, &account_id_hex, query.at.as_deref())
         .await
         .map_err(warp::reject::custom)?
         .map(|r| warp::reply::json(&r))
-        .ok_or(warp::reject::not_found())
+        .ok_or_else(|| not_found("account"))
 }
 
 pub async fn get_message_logs(context: ContextLock) -> Result<impl Reply, Rejection> {
@@ -26,6 +47,49 @@ pub async fn get_message_logs(context: ContextLock) -> Result<impl Reply, Reject
         .map(|r| warp::reply::json(&r))
 }
 
+pub async fn get_fragment_status(
+    id_hex: String,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_fragment_status(&context, &id_hex)
+        .await
+        .map_err(warp::reject::custom)?
+        .map(|r| warp::reply::json(&r))
+        .ok_or_else(|| not_found("fragment log"))
+}
+
+pub async fn get_fragment_statuses(
+    ids: Vec<String>,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_fragment_statuses(&context, ids)
+        .await
+        .map_err(warp::reject::custom)
+        .map(|r| warp::reply::json(&r))
+}
+
+pub async fn get_pending_fragments(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_pending_fragments(&context)
+        .await
+        .map_err(warp::reject::custom)
+        .map(|r| warp::reply::json(&r))
+}
+
+pub async fn delete_pending_fragment(
+    id_hex: String,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::delete_pending_fragment(&context, &id_hex)
+        .await
+        .map_err(warp::reject::custom)?
+        .map(|r| warp::reply::json(&r))
+        .ok_or_else(|| not_found("pending fragment"))
+}
+
 pub async fn post_message(
     message: bytes::Bytes,
     context: ContextLock,
@@ -36,11 +100,49 @@ pub async fn post_message(
         .map_err(warp::reject::custom)
 }
 
+pub async fn post_fragments(
+    fragments: Vec<String>,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::post_fragments(&context, fragments)
+        .await
+        .map_err(warp::reject::custom)
+        .map(|r| warp::reply::json(&r))
+}
+
+pub async fn get_events(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    let events = logic::subscribe_events(&context)
+        .await
+        .map_err(warp::reject::custom)?;
+    let stream = events.filter_map(|event| {
+        let sse = match event {
+            Ok(event) => logic::event_data(event).ok(),
+            // the client did not keep up, let it know events were missed
+            Err(RecvError::Lagged(missed)) => Some(("lagged", missed.into())),
+            Err(RecvError::Closed) => None,
+        };
+        future::ready(sse.map(|(name, data)| {
+            Ok::<_, Infallible>((warp::sse::event(name), warp::sse::json(data)))
+        }))
+    });
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(stream)))
+}
+
 pub async fn get_tip(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
     logic::get_tip(&context).await.map_err(warp::reject::custom)
 }
 
+pub async fn get_branches(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_branches(&context)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
 pub async fn get_stats_counter(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
     logic::get_stats_counter(&context)
@@ -57,7 +159,26 @@ pub async fn get_block_id(
     logic::get_block_id(&context, &block_id_hex)
         .await
         .map_err(warp::reject::custom)?
-        .ok_or(warp::reject::not_found())
+        .ok_or_else(|| not_found("block"))
+}
+
+pub async fn get_ledger_snapshots(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_ledger_snapshots(&context)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
+pub async fn get_ledger_snapshot(
+    block_id_hex: String,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_ledger_snapshot(&context, &block_id_hex)
+        .await
+        .map_err(warp::reject::custom)?
+        .ok_or_else(|| not_found("ledger snapshot"))
 }
 
 #[derive(Deserialize)]
@@ -75,12 +196,15 @@ pub async fn get_block_next_id(
     logic::get_block_next_id(&context, &block_id_hex, count as usize)
         .await
         .map_err(warp::reject::custom)?
-        .ok_or(warp::reject::not_found())
+        .ok_or_else(|| not_found("block"))
 }
 
-pub async fn get_stake_distribution(context: ContextLock) -> Result<impl Reply, Rejection> {
+pub async fn get_stake_distribution(
+    query: AtBlockQuery,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
-    logic::get_stake_distribution(&context)
+    logic::get_stake_distribution(&context, query.at.as_deref())
         .await
         .map(|r| warp::reply::json(&r))
         .map_err(warp::reject::custom)
@@ -95,7 +219,7 @@ pub async fn get_stake_distribution_at(
         .await
         .map_err(warp::reject::custom)?
         .map(|r| warp::reply::json(&r))
-        .ok_or(warp::reject::not_found())
+        .ok_or_else(|| not_found("stake distribution"))
 }
 
 pub async fn get_settings(context: ContextLock) -> Result<impl Reply, Rejection> {
@@ -139,7 +263,7 @@ pub async fn delete_leaders(leader_id: u32, context: ContextLock) -> Result<impl
         .await
         .map_err(warp::reject::custom)?
         .map(|()| warp::reply())
-        .ok_or(warp::reject::not_found())
+        .ok_or_else(|| not_found("leader"))
 }
 
 pub async fn get_leaders_logs(context: ContextLock) -> Result<impl Reply, Rejection> {
@@ -175,7 +299,7 @@ pub async fn get_rewards_info_epoch(
         .await
         .map_err(warp::reject::custom)?
         .map(|r| warp::reply::json(&r))
-        .ok_or(warp::reject::not_found())
+        .ok_or_else(|| not_found("rewards information"))
 }
 
 pub async fn get_rewards_info_history(
@@ -189,17 +313,35 @@ pub async fn get_rewards_info_history(
         .map_err(warp::reject::custom)
 }
 
-pub async fn get_utxo(
-    fragment_id_hex: String,
-    output_index: u8,
+pub async fn get_rewards_info_account(
+    account_id_hex: String,
     context: ContextLock,
 ) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
-    logic::get_utxo(&context, &fragment_id_hex, output_index)
+    logic::get_rewards_info_account(&context, &account_id_hex)
         .await
         .map_err(warp::reject::custom)?
         .map(|r| warp::reply::json(&r))
-        .ok_or(warp::reject::not_found())
+        .ok_or_else(|| not_found("rewards index"))
+}
+
+pub async fn get_utxo(
+    fragment_id_hex: String,
+    output_index: u8,
+    query: AtBlockQuery,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_utxo(
+        &context,
+        &fragment_id_hex,
+        output_index,
+        query.at.as_deref(),
+    )
+    .await
+    .map_err(warp::reject::custom)?
+    .map(|r| warp::reply::json(&r))
+    .ok_or_else(|| not_found("UTxO"))
 }
 
 pub async fn get_stake_pool(
@@ -211,7 +353,7 @@ pub async fn get_stake_pool(
         .await
         .map_err(warp::reject::custom)?
         .map(|r| warp::reply::json(&r))
-        .ok_or(warp::reject::not_found())
+        .ok_or_else(|| not_found("stake pool"))
 }
 
 pub async fn get_diagnostic(context: ContextLock) -> Result<impl Reply, Rejection> {
@@ -7,8 +7,13 @@
 //   they are 400 or 500.
 
 use crate::{
-    blockchain::StorageError,
+    blockcfg::Header,
+    blockchain::{
+        AccountReward, Error as BlockchainError, ErrorKind as BlockchainErrorKind, Ref,
+        RewardsDumpError, RewardsIndexError, SnapshotError, Storage, StorageError,
+    },
     diagnostic::Diagnostic,
+    events::Event,
     intercom::{self, NetworkMsg, TransactionMsg},
     rest::Context,
     secure::NodeSecret,
@@ -30,18 +35,23 @@ use chain_impl_mockchain::{
     value::{Value, ValueError},
 };
 use jormungandr_lib::{
+    crypto::account::Identifier as AccountIdentifier,
     interfaces::{
-        AccountState, EnclaveLeaderId, EpochRewardsInfo, FragmentLog, FragmentOrigin,
-        LeadershipLog, NodeStats, NodeStatsDto, PeerStats, Rewards as StakePoolRewards,
-        SettingsDto, StakeDistribution, StakeDistributionDto, StakePoolStats, TaxTypeSerde,
-        TransactionOutput, VotePlanStatus,
+        AccountState, EnclaveLeaderId, EpochRewardsInfo, FragmentLog, FragmentLookup,
+        FragmentOrigin, FragmentSubmission, LeadershipLog, NodeStats, NodeStatsDto, PeerStats,
+        PendingFragments, Rewards as StakePoolRewards, SettingsDto, StakeDistribution,
+        StakeDistributionDto, StakePoolStats, TaxTypeSerde, TransactionOutput, VotePlanStatus,
     },
     time::SystemTime,
 };
 
-use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 use futures::{channel::mpsc::SendError, channel::mpsc::TrySendError, prelude::*};
+use tokio::{sync::broadcast, task::JoinError};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -63,6 +73,12 @@ pub enum Error {
     Value(#[from] ValueError),
     #[error("Could not find block for tip")]
     TipBlockNotFound,
+    #[error("Could not find the block {0}")]
+    BlockNotFound(String),
+    #[error("Could not rebuild the ledger state of the block: {0}")]
+    LedgerReplay(String),
+    #[error("{0}")]
+    ReplayTooDeep(String),
     #[error(transparent)]
     Hash(#[from] HashError),
     #[error(transparent)]
@@ -71,8 +87,23 @@ pub enum Error {
     Storage(#[from] StorageError),
     #[error("Invalid topic")]
     InvalidTopic,
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+    #[error(transparent)]
+    RewardsDump(#[from] RewardsDumpError),
+    #[error(transparent)]
+    RewardsIndex(#[from] RewardsIndexError),
+    #[error("Too many fragments in the request: {count}, at most {max} are accepted")]
+    TooManyFragments { count: usize, max: usize },
+    #[error(transparent)]
+    BlockingError(#[from] JoinError),
 }
 
+/// maximum number of fragments submitted or looked up in a single request
+pub const MAX_FRAGMENTS_PER_REQUEST: usize = 1024;
+
 fn parse_account_id(id_hex: &str) -> Result<Identifier, Error> {
     PublicKey::<AccountAlg>::from_str(id_hex)
         .map(Into::into)
@@ -92,14 +123,83 @@ fn parse_fragment_id(id_hex: &str) -> Result<FragmentId, Error> {
     }
 }
 
+/// the block a query is made at
+#[derive(Debug, PartialEq, Eq)]
+enum RefAt {
+    ChainLength(u32),
+    Block(Hash),
+}
+
+/// parse the `at` parameter of a query, either the chain length of a block
+/// of the tip's branch or the hash of a block of the storage
+fn parse_ref_at(at: &str) -> Result<RefAt, Error> {
+    match at.parse::<u32>() {
+        Ok(chain_length) => Ok(RefAt::ChainLength(chain_length)),
+        Err(_) => parse_block_hash(at).map(RefAt::Block),
+    }
+}
+
+fn replay_error(error: BlockchainError) -> Error {
+    match error.kind() {
+        BlockchainErrorKind::ReplayTooDeep(..) => Error::ReplayTooDeep(error.to_string()),
+        _ => Error::LedgerReplay(error.to_string()),
+    }
+}
+
+/// the `Ref` of the block a query is made at. `at` is either the hash of a
+/// block of the storage or the chain length of a block of the tip's branch,
+/// the tip is used if it is not set.
+async fn get_ref_at(context: &Context, at: Option<&str>) -> Result<Arc<Ref>, Error> {
+    let tip = context.blockchain_tip()?.get_ref().await;
+    let at = match at {
+        Some(at) => at,
+        None => return Ok(tip),
+    };
+    let blockchain = context.blockchain()?;
+
+    let block_id = match parse_ref_at(at)? {
+        RefAt::ChainLength(chain_length) => {
+            get_block_id_at_chain_length(context, &tip, chain_length)
+                .await?
+                .ok_or_else(|| Error::BlockNotFound(at.to_owned()))?
+        }
+        RefAt::Block(block_id) => block_id,
+    };
+    blockchain
+        .get_or_replay_ref(block_id)
+        .await
+        .map_err(replay_error)?
+        .ok_or_else(|| Error::BlockNotFound(at.to_owned()))
+}
+
+/// the block of the tip's branch at the given chain length
+async fn get_block_id_at_chain_length(
+    context: &Context,
+    tip: &Ref,
+    chain_length: u32,
+) -> Result<Option<Hash>, Error> {
+    if chain_length > u32::from(tip.chain_length()) {
+        return Ok(None);
+    }
+    let storage = context.blockchain()?.storage();
+    for block in storage
+        .get_blocks_by_chain_length(chain_length.into())
+        .await?
+    {
+        if storage.is_ancestor(block.id(), tip.hash()).await?.is_some() {
+            return Ok(Some(block.id()));
+        }
+    }
+    Ok(None)
+}
+
 pub async fn get_account_state(
     context: &Context,
     account_id_hex: &str,
+    at: Option<&str>,
 ) -> Result<Option<AccountState>, Error> {
-    Ok(context
-        .blockchain_tip()?
-        .get_ref()
-        .await
+    Ok(get_ref_at(context, at)
+        .await?
         .ledger()
         .accounts()
         .get_state(&parse_account_id(account_id_hex)?)
@@ -120,18 +220,215 @@ pub async fn get_message_logs(context: &Context) -> Result<Vec<FragmentLog>, Err
     reply_future.await.map_err(Into::into)
 }
 
+/// the logs of the given fragments, the fragments without a log are
+/// skipped
+async fn get_fragment_logs(
+    context: &Context,
+    ids: Vec<FragmentId>,
+) -> Result<Vec<FragmentLog>, Error> {
+    let logger = context.logger()?.new(o!("request" => "fragment_statuses"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::GetStatuses(ids, reply_handle))
+        .await
+        .map_err(|e| {
+            debug!(&logger, "error getting fragment statuses"; "reason" => %e);
+            Error::MsgSendError(e)
+        })?;
+    reply_future.await.map_err(Into::into)
+}
+
+/// the logs of the given fragments, in the order of the request. The
+/// fragments the node has no log for are reported as not found.
+pub async fn get_fragment_statuses(
+    context: &Context,
+    ids: Vec<String>,
+) -> Result<Vec<FragmentLookup>, Error> {
+    if ids.len() > MAX_FRAGMENTS_PER_REQUEST {
+        return Err(Error::TooManyFragments {
+            count: ids.len(),
+            max: MAX_FRAGMENTS_PER_REQUEST,
+        });
+    }
+    let ids = ids
+        .iter()
+        .map(|id_hex| parse_fragment_id(id_hex))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut logs: HashMap<_, _> = get_fragment_logs(context, ids.clone())
+        .await?
+        .into_iter()
+        .map(|log| (*log.fragment_id(), log))
+        .collect();
+    Ok(ids
+        .into_iter()
+        .map(|id| match logs.remove(&id.into()) {
+            Some(log) => FragmentLookup::Found(log),
+            None => FragmentLookup::not_found(id),
+        })
+        .collect())
+}
+
+pub async fn get_fragment_status(
+    context: &Context,
+    id_hex: &str,
+) -> Result<Option<FragmentLog>, Error> {
+    let id = parse_fragment_id(id_hex)?;
+    get_fragment_logs(context, vec![id])
+        .await
+        .map(|logs| logs.into_iter().next())
+}
+
+pub async fn get_pending_fragments(context: &Context) -> Result<PendingFragments, Error> {
+    let logger = context.logger()?.new(o!("request" => "pending_fragments"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::GetPending(reply_handle))
+        .await
+        .map_err(|e| {
+            debug!(&logger, "error getting pending fragments"; "reason" => %e);
+            Error::MsgSendError(e)
+        })?;
+    reply_future.await.map_err(Into::into)
+}
+
+pub async fn delete_pending_fragment(
+    context: &Context,
+    id_hex: &str,
+) -> Result<Option<Vec<String>>, Error> {
+    let fragment_id = parse_fragment_id(id_hex)?;
+    let logger = context
+        .logger()?
+        .new(o!("request" => "delete_pending_fragment"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::EvictTransaction(fragment_id, reply_handle))
+        .await
+        .map_err(|e| {
+            debug!(&logger, "error evicting pending fragment"; "reason" => %e);
+            Error::MsgSendError(e)
+        })?;
+    let evicted = reply_future.await?;
+    Ok(evicted.map(|ids| ids.iter().map(ToString::to_string).collect()))
+}
+
 pub async fn post_message(context: &Context, message: &[u8]) -> Result<String, Error> {
     let fragment = Fragment::deserialize(message).map_err(Error::Deserialize)?;
     let fragment_id = fragment.id().to_string();
-    let msg = TransactionMsg::SendTransaction(FragmentOrigin::Rest, vec![fragment]);
+    let msg = TransactionMsg::SendTransaction(FragmentOrigin::Rest, None, vec![fragment], None);
     context.try_full()?.transaction_task.clone().try_send(msg)?;
     Ok(fragment_id)
 }
 
+fn decode_fragment(fragment_hex: &str) -> Result<Fragment, String> {
+    let bytes = hex::decode(fragment_hex).map_err(|e| format!("invalid hex encoding: {}", e))?;
+    Fragment::deserialize(bytes.as_slice()).map_err(|e| format!("malformed fragment: {}", e))
+}
+
+pub async fn post_fragments(
+    context: &Context,
+    fragments_hex: Vec<String>,
+) -> Result<Vec<FragmentSubmission>, Error> {
+    if fragments_hex.len() > MAX_FRAGMENTS_PER_REQUEST {
+        return Err(Error::TooManyFragments {
+            count: fragments_hex.len(),
+            max: MAX_FRAGMENTS_PER_REQUEST,
+        });
+    }
+    let mut fragments = Vec::with_capacity(fragments_hex.len());
+    let mut malformed = Vec::new();
+    for (index, fragment_hex) in fragments_hex.iter().enumerate() {
+        match decode_fragment(fragment_hex) {
+            Ok(fragment) => fragments.push(fragment),
+            Err(reason) => malformed.push((index, FragmentSubmission::rejected(None, reason))),
+        }
+    }
+
+    let logger = context.logger()?.new(o!("request" => "post_fragments"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::SendTransaction(
+        FragmentOrigin::Rest,
+        None,
+        fragments,
+        Some(reply_handle),
+    ))
+    .await
+    .map_err(|e| {
+        debug!(&logger, "error sending fragments"; "reason" => %e);
+        Error::MsgSendError(e)
+    })?;
+    let mut submissions = reply_future.await?;
+
+    // the malformed fragments are put back at their position in the request
+    for (index, submission) in malformed {
+        submissions.insert(index, submission);
+    }
+    Ok(submissions)
+}
+
+pub async fn subscribe_events(context: &Context) -> Result<broadcast::Receiver<Event>, Error> {
+    Ok(context.try_full()?.events.subscribe())
+}
+
+#[derive(Serialize)]
+struct BlockEvent {
+    hash: String,
+    parent: String,
+    date: String,
+    chain_length: u32,
+}
+
+impl BlockEvent {
+    fn new(header: &Header) -> Self {
+        BlockEvent {
+            hash: header.hash().to_string(),
+            parent: header.parent_id().to_string(),
+            date: header.block_date().to_string(),
+            chain_length: header.chain_length().into(),
+        }
+    }
+}
+
+/// name and JSON data of an event of the events stream
+pub fn event_data(event: Event) -> Result<(&'static str, serde_json::Value), Error> {
+    let (name, data) = match event {
+        Event::NewBlock(header) => ("block", serde_json::to_value(BlockEvent::new(&header))),
+        Event::TipChanged(header) => ("tip", serde_json::to_value(BlockEvent::new(&header))),
+        Event::FragmentLog(log) => ("fragment", serde_json::to_value(log)),
+        Event::LeadershipLog(log) => ("leadership", serde_json::to_value(log)),
+    };
+    Ok((name, data?))
+}
+
 pub async fn get_tip(context: &Context) -> Result<String, Error> {
     Ok(context.blockchain_tip()?.get_ref().await.hash().to_string())
 }
 
+#[derive(Serialize)]
+pub struct BranchInfo {
+    hash: String,
+    chain_length: u32,
+    last_updated: SystemTime,
+}
+
+/// the branches known by the node, the longest first
+pub async fn get_branches(context: &Context) -> Result<Vec<BranchInfo>, Error> {
+    let mut branches = context
+        .blockchain()?
+        .branches()
+        .branches_with_last_updated()
+        .await;
+    branches.sort_by(|(a, _), (b, _)| b.chain_length().cmp(&a.chain_length()));
+    Ok(branches
+        .into_iter()
+        .map(|(reference, last_updated)| BranchInfo {
+            hash: reference.hash().to_string(),
+            chain_length: reference.chain_length().into(),
+            last_updated: SystemTime::from(last_updated),
+        })
+        .collect())
+}
+
 pub async fn get_stats_counter(context: &Context) -> Result<NodeStatsDto, Error> {
     let stats = create_stats(&context).await?;
     Ok(NodeStatsDto {
@@ -224,6 +521,7 @@ async fn create_stats(context: &Context) -> Result<Option<NodeStats>, Error> {
         peer_quarantined_cnt: nodes_count.quarantined_count,
         peer_total_cnt: nodes_count.all_count,
         peer_unreachable_cnt: nodes_count.not_reachable_count,
+        rejected_deep_fork_cnt: stats.rejected_deep_fork_cnt(),
         tx_recv_cnt: stats.tx_recv_cnt(),
         uptime: stats.uptime_sec().into(),
     };
@@ -240,6 +538,76 @@ pub async fn get_block_id(context: &Context, block_id_hex: &str) -> Result<Optio
         .transpose()
 }
 
+#[derive(Serialize)]
+pub struct LedgerSnapshotInfo {
+    block: String,
+    chain_length: u32,
+}
+
+/// the ledger snapshots of the blocks of the tip's branch that are deep
+/// enough to not be rolled back, the most recent first
+async fn stable_snapshots(context: &Context, tip: &Ref) -> Result<Vec<(u32, Hash)>, Error> {
+    let blockchain = context.blockchain()?;
+    let snapshots = match blockchain.snapshots() {
+        Some(snapshots) => snapshots,
+        None => return Ok(Vec::new()),
+    };
+    let epoch_stability_depth = tip.epoch_ledger_parameters().epoch_stability_depth;
+    let tip_chain_length: u32 = tip.chain_length().into();
+
+    let mut stable = Vec::new();
+    for (chain_length, block) in snapshots.list()? {
+        let chain_length: u32 = chain_length.into();
+        if chain_length + epoch_stability_depth > tip_chain_length {
+            continue;
+        }
+        let on_tip_branch = blockchain
+            .storage()
+            .find_closest_ancestor(vec![block], tip.hash())
+            .await?
+            .is_some();
+        if on_tip_branch {
+            stable.push((chain_length, block));
+        }
+    }
+    Ok(stable)
+}
+
+pub async fn get_ledger_snapshots(context: &Context) -> Result<Vec<LedgerSnapshotInfo>, Error> {
+    let tip = context.blockchain_tip()?.get_ref().await;
+    Ok(stable_snapshots(context, &tip)
+        .await?
+        .into_iter()
+        .map(|(chain_length, block)| LedgerSnapshotInfo {
+            block: block.to_string(),
+            chain_length,
+        })
+        .collect())
+}
+
+pub async fn get_ledger_snapshot(
+    context: &Context,
+    block_id_hex: &str,
+) -> Result<Option<Vec<u8>>, Error> {
+    let block_id = parse_block_hash(block_id_hex)?;
+    let tip = context.blockchain_tip()?.get_ref().await;
+    let stable = stable_snapshots(context, &tip)
+        .await?
+        .into_iter()
+        .any(|(_, block)| block == block_id);
+    if !stable {
+        return Ok(None);
+    }
+
+    let snapshots = match context.blockchain()?.snapshots() {
+        Some(snapshots) => snapshots.clone(),
+        None => return Ok(None),
+    };
+    tokio::task::spawn_blocking(move || snapshots.read_raw(&block_id))
+        .await?
+        .map_err(Into::into)
+}
+
 pub async fn get_block_next_id(
     context: &Context,
     block_id_hex: &str,
@@ -277,8 +645,9 @@ pub async fn get_block_next_id(
 
 pub async fn get_stake_distribution(
     context: &Context,
+    at: Option<&str>,
 ) -> Result<Option<StakeDistributionDto>, Error> {
-    let blockchain_tip = context.blockchain_tip()?.get_ref().await;
+    let blockchain_tip = get_ref_at(context, at).await?;
     let leadership = blockchain_tip.epoch_leadership_schedule();
     if let LeadershipConsensus::GenesisPraos(gp) = leadership.consensus() {
         let last_epoch = blockchain_tip.block_date().epoch;
@@ -445,6 +814,7 @@ pub async fn get_network_stats(context: &Context) -> Result<Vec<PeerStats>, Erro
             last_block_received: info.stats.last_block_received().map(SystemTime::from),
             last_fragment_received: info.stats.last_fragment_received().map(SystemTime::from),
             last_gossip_received: info.stats.last_gossip_received().map(SystemTime::from),
+            score: info.score,
         })
         .collect())
 }
@@ -453,37 +823,64 @@ pub async fn get_rewards_info_epoch(
     context: &Context,
     epoch: u32,
 ) -> Result<Option<EpochRewardsInfo>, Error> {
-    let mut tip_ref = context.blockchain_tip()?.get_ref().await;
+    let tip = context.blockchain_tip()?.get_ref().await;
 
-    if epoch > tip_ref.block_date().epoch {
+    if epoch > tip.block_date().epoch {
         return Ok(None);
     }
 
+    let mut epoch_ref = Arc::clone(&tip);
     loop {
-        if tip_ref.block_date().epoch == epoch {
+        if epoch_ref.block_date().epoch == epoch {
             break;
         }
-        match tip_ref.last_ref_previous_epoch() {
-            Some(previous_epoch) => {
-                if epoch > previous_epoch.block_date().epoch {
-                    return Ok(None);
-                }
-                tip_ref = Arc::clone(previous_epoch);
+        match epoch_ref.last_ref_previous_epoch() {
+            Some(previous_epoch) if epoch <= previous_epoch.block_date().epoch => {
+                epoch_ref = Arc::clone(previous_epoch);
             }
-            _ => return Ok(None),
+            _ => return get_dumped_rewards_info(context, &tip, epoch).await,
         }
     }
 
-    if let Some(epoch_rewards_info) = tip_ref.epoch_rewards_info() {
+    if let Some(epoch_rewards_info) = epoch_ref.epoch_rewards_info() {
         Ok(Some(EpochRewardsInfo::from(
-            tip_ref.block_date().epoch,
+            epoch_ref.block_date().epoch,
             epoch_rewards_info.as_ref(),
         )))
     } else {
-        Ok(None)
+        get_dumped_rewards_info(context, &tip, epoch).await
     }
 }
 
+/// the rewards distributed at the beginning of `epoch` on the tip's branch,
+/// as written in the rewards dump
+async fn get_dumped_rewards_info(
+    context: &Context,
+    tip: &Ref,
+    epoch: u32,
+) -> Result<Option<EpochRewardsInfo>, Error> {
+    let blockchain = context.blockchain()?;
+    let rewards_dump = match blockchain.rewards_dump() {
+        Some(rewards_dump) => rewards_dump,
+        None => return Ok(None),
+    };
+
+    let dump = rewards_dump.clone();
+    let parents = tokio::task::spawn_blocking(move || dump.list(epoch)).await??;
+    for parent in parents {
+        let on_tip_branch = blockchain
+            .storage()
+            .is_ancestor(parent, tip.hash())
+            .await?
+            .is_some();
+        if on_tip_branch {
+            let dump = rewards_dump.clone();
+            return Ok(tokio::task::spawn_blocking(move || dump.read(epoch, &parent)).await??);
+        }
+    }
+    Ok(None)
+}
+
 pub async fn get_rewards_info_history(
     context: &Context,
     length: usize,
@@ -511,16 +908,63 @@ pub async fn get_rewards_info_history(
     Ok(vec)
 }
 
+#[derive(Serialize)]
+pub struct AccountRewardInfo {
+    epoch: u32,
+    received: u64,
+}
+
+/// the rewards received by the account in the epochs of the tip's branch,
+/// the oldest first. Returns `None` if the rewards index is not enabled.
+pub async fn get_rewards_info_account(
+    context: &Context,
+    account_id_hex: &str,
+) -> Result<Option<Vec<AccountRewardInfo>>, Error> {
+    let account = AccountIdentifier::from(parse_account_id(account_id_hex)?);
+    let blockchain = context.blockchain()?;
+    let rewards_index = match blockchain.rewards_index() {
+        Some(rewards_index) => rewards_index.clone(),
+        None => return Ok(None),
+    };
+    let tip = context.blockchain_tip()?.get_ref().await;
+
+    let rewards = tokio::task::spawn_blocking(move || rewards_index.read(&account)).await??;
+
+    rewards_on_branch(blockchain.storage(), rewards, tip.hash())
+        .await
+        .map(Some)
+}
+
+/// the indexed rewards distributed on the branch of `tip`, the oldest first
+async fn rewards_on_branch(
+    storage: &Storage,
+    rewards: Vec<AccountReward>,
+    tip: Hash,
+) -> Result<Vec<AccountRewardInfo>, Error> {
+    // a distribution may have been indexed more than once, keep one entry
+    // per epoch
+    let mut received: BTreeMap<u32, u64> = BTreeMap::new();
+    for reward in rewards {
+        let on_tip_branch = storage.is_ancestor(reward.parent, tip).await?.is_some();
+        if on_tip_branch {
+            received.insert(reward.epoch, reward.received.into());
+        }
+    }
+    Ok(received
+        .into_iter()
+        .map(|(epoch, received)| AccountRewardInfo { epoch, received })
+        .collect())
+}
+
 pub async fn get_utxo(
     context: &Context,
     fragment_id_hex: &str,
     output_index: u8,
+    at: Option<&str>,
 ) -> Result<Option<TransactionOutput>, Error> {
     let fragment_id = parse_fragment_id(fragment_id_hex)?;
-    Ok(context
-        .blockchain_tip()?
-        .get_ref()
-        .await
+    Ok(get_ref_at(context, at)
+        .await?
         .ledger()
         .utxo_out(fragment_id, output_index)
         .cloned()
@@ -647,3 +1091,81 @@ pub async fn get_active_vote_plans(context: &Context) -> Result<Vec<VotePlanStat
         .collect();
     Ok(vp)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::test_chain::{apply_all, TestChain};
+
+    #[test]
+    fn ref_at_is_a_chain_length_or_a_block_hash() {
+        assert_eq!(parse_ref_at("0").unwrap(), RefAt::ChainLength(0));
+        assert_eq!(parse_ref_at("1234").unwrap(), RefAt::ChainLength(1234));
+
+        let block = Hash::hash_bytes(&[0]);
+        assert_eq!(
+            parse_ref_at(&block.to_string()).unwrap(),
+            RefAt::Block(block)
+        );
+        // a hash made of digits only does not fit a chain length
+        let digits = "1".repeat(64);
+        assert_eq!(
+            parse_ref_at(&digits).unwrap(),
+            RefAt::Block(parse_block_hash(&digits).unwrap())
+        );
+
+        assert!(parse_ref_at("").is_err());
+        assert!(parse_ref_at("-1").is_err());
+        assert!(parse_ref_at("not-a-block").is_err());
+    }
+
+    #[test]
+    fn replay_too_deep_is_reported() {
+        let block = Hash::hash_bytes(&[0]);
+        let error = replay_error(BlockchainErrorKind::ReplayTooDeep(block, 4096).into());
+        assert!(matches!(error, Error::ReplayTooDeep(_)));
+
+        let error = replay_error(BlockchainErrorKind::MissingParentBlock(block).into());
+        assert!(matches!(error, Error::LedgerReplay(_)));
+    }
+
+    fn reward(epoch: u32, parent: &ChainBlock, received: u64) -> AccountReward {
+        AccountReward {
+            epoch,
+            parent: parent.header.hash(),
+            received: received.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rewards_are_looked_up_on_the_branch_of_the_tip() {
+        let chain = TestChain::new("rest-rewards-on-branch", 10);
+        let blockchain = chain.blockchain(64, None);
+        blockchain
+            .load_from_block0(chain.block0.clone())
+            .await
+            .unwrap();
+
+        // the epoch 1 starts on the main branch and on a fork, both on top
+        // of the last block of the epoch 0
+        let main = chain.branch(&chain.block0.header, 12, 0);
+        apply_all(&blockchain, main.clone()).await;
+        let fork = chain.branch(&main[8].header, 1, 1);
+        apply_all(&blockchain, fork.clone()).await;
+        let tip = main[11].header.hash();
+
+        let rewards = vec![
+            reward(1, &main[8], 10),
+            reward(1, &fork[0], 20),
+            // the same distribution indexed twice
+            reward(1, &main[8], 10),
+        ];
+        let received: Vec<(u32, u64)> = rewards_on_branch(&chain.storage, rewards, tip)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|info| (info.epoch, info.received))
+            .collect();
+        assert_eq!(received, vec![(1, 10)]);
+    }
+}
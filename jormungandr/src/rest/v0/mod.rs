@@ -1,7 +1,11 @@
 mod handlers;
 pub mod logic;
 
-use crate::rest::{display_internal_server_error, ContextLock};
+use crate::{
+    blockchain::StorageError,
+    rest::{context, error_reply, ContextLock},
+};
+use chain_network::error::Code;
 
 use warp::{http::StatusCode, Filter, Rejection, Reply};
 
@@ -19,6 +23,7 @@ pub fn filter(
 
     let account = warp::path!("account" / String)
         .and(warp::get())
+        .and(warp::query())
         .and(with_context.clone())
         .and_then(handlers::get_account_state)
         .boxed();
@@ -42,11 +47,61 @@ pub fn filter(
         root.and(get.or(get_next)).boxed()
     };
 
-    let fragment = warp::path!("fragment" / "logs")
-        .and(warp::get())
-        .and(with_context.clone())
-        .and_then(handlers::get_message_logs)
-        .boxed();
+    let ledger = {
+        let root = warp::path!("ledger" / "snapshots" / ..);
+
+        let list = warp::path::end()
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_ledger_snapshots)
+            .boxed();
+
+        let get = warp::path!(String)
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_ledger_snapshot)
+            .boxed();
+
+        root.and(list.or(get)).boxed()
+    };
+
+    let fragment = {
+        let root = warp::path!("fragment" / ..);
+
+        let logs = warp::path!("logs")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_message_logs)
+            .boxed();
+
+        let status = warp::path!("status" / String)
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_fragment_status)
+            .boxed();
+
+        let statuses = warp::path!("statuses")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(with_context.clone())
+            .and_then(handlers::get_fragment_statuses)
+            .boxed();
+
+        let pending = warp::path!("pending")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_pending_fragments)
+            .boxed();
+
+        let evict = warp::path!("pending" / String)
+            .and(warp::delete())
+            .and(with_context.clone())
+            .and_then(handlers::delete_pending_fragment)
+            .boxed();
+
+        root.and(logs.or(status).or(statuses).or(pending).or(evict))
+            .boxed()
+    };
 
     let leaders = {
         let root = warp::path!("leaders" / ..).boxed();
@@ -145,6 +200,7 @@ pub fn filter(
 
         let get = warp::path::end()
             .and(warp::get())
+            .and(warp::query())
             .and(with_context.clone())
             .and_then(handlers::get_stake_distribution)
             .boxed();
@@ -177,6 +233,19 @@ pub fn filter(
         .and_then(handlers::post_message)
         .boxed();
 
+    let events = warp::path!("events")
+        .and(warp::get())
+        .and(with_context.clone())
+        .and_then(handlers::get_events)
+        .boxed();
+
+    let fragments = warp::path!("fragments")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_context.clone())
+        .and_then(handlers::post_fragments)
+        .boxed();
+
     let node_stats = warp::path!("node" / "stats")
         .and(warp::get())
         .and(with_context.clone())
@@ -189,6 +258,12 @@ pub fn filter(
         .and_then(handlers::get_tip)
         .boxed();
 
+    let branches = warp::path!("branches")
+        .and(warp::get())
+        .and(with_context.clone())
+        .and_then(handlers::get_branches)
+        .boxed();
+
     let rewards = {
         let root = warp::path!("rewards" / ..);
 
@@ -204,11 +279,18 @@ pub fn filter(
             .and_then(handlers::get_rewards_info_epoch)
             .boxed();
 
-        root.and(history.or(epoch)).boxed()
+        let account = warp::path!("account" / String)
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_rewards_info_account)
+            .boxed();
+
+        root.and(history.or(epoch).or(account)).boxed()
     };
 
     let utxo = warp::path!("utxo" / String / u8)
         .and(warp::get())
+        .and(warp::query())
         .and(with_context.clone())
         .and_then(handlers::get_utxo)
         .boxed();
@@ -238,6 +320,7 @@ pub fn filter(
     let routes = shutdown
         .or(account)
         .or(block)
+        .or(ledger)
         .or(fragment)
         .or(leaders)
         .or(network)
@@ -246,8 +329,11 @@ pub fn filter(
         .or(stake_pools)
         .or(stake_pool)
         .or(message)
+        .or(fragments)
+        .or(events)
         .or(node_stats)
         .or(tip)
+        .or(branches)
         .or(rewards)
         .or(utxo)
         .or(diagnostic)
@@ -257,21 +343,62 @@ pub fn filter(
     root.and(routes).recover(handle_rejection).boxed()
 }
 
-/// Convert rejections to actual HTTP errors
+/// Convert rejections to actual HTTP errors, with a JSON body describing
+/// the error
 async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
     if let Some(err) = err.find::<logic::Error>() {
-        let (body, code) = match err {
-            logic::Error::PublicKey(_) | logic::Error::Hash(_) => {
-                (err.to_string(), StatusCode::BAD_REQUEST)
-            }
-            err => (
-                display_internal_server_error(err),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            ),
-        };
-
-        return Ok(warp::reply::with_status(body, code));
+        return Ok(error_reply(err, status_code(err)));
+    }
+    if let Some(err) = err.find::<handlers::NotFound>() {
+        return Ok(error_reply(err, StatusCode::NOT_FOUND));
+    }
+    if let Some(err) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        return Ok(error_reply(err, StatusCode::BAD_REQUEST));
     }
 
     Err(err)
 }
+
+fn status_code(err: &logic::Error) -> StatusCode {
+    match err {
+        logic::Error::PublicKey(_)
+        | logic::Error::Hash(_)
+        | logic::Error::Digest(_)
+        | logic::Error::InvalidTopic => StatusCode::BAD_REQUEST,
+        logic::Error::TooManyFragments { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        logic::Error::ReplayTooDeep(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        logic::Error::Deserialize(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        // the node is still bootstrapping
+        logic::Error::ContextError(context::Error::FullContext)
+        | logic::Error::ContextError(context::Error::Blockchain)
+        | logic::Error::ContextError(context::Error::BlockchainTip) => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        logic::Error::TxMsgSendError(_) | logic::Error::MsgSendError(_) => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        logic::Error::Storage(StorageError::BlockNotFound) | logic::Error::BlockNotFound(_) => {
+            StatusCode::NOT_FOUND
+        }
+        logic::Error::IntercomError(err) => match err.code() {
+            Code::NotFound => StatusCode::NOT_FOUND,
+            Code::InvalidArgument => StatusCode::BAD_REQUEST,
+            Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        },
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_too_deep_is_unprocessable() {
+        let error = logic::Error::ReplayTooDeep("too deep".to_owned());
+        assert_eq!(status_code(&error), StatusCode::UNPROCESSABLE_ENTITY);
+        let error = logic::Error::LedgerReplay("failed".to_owned());
+        assert_eq!(status_code(&error), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
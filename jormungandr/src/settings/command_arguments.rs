@@ -113,6 +113,88 @@ pub struct CommandLine {
     /// this option is useful for scripting retrieving the logs of the version of this application.
     #[structopt(long = "source-version")]
     pub source_version: bool,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// commands run on the storage instead of starting the node
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// write blocks of the storage to the standard output, to be imported
+    /// in another storage with `import-blocks`
+    ExportBlocks(ExportBlocksArguments),
+
+    /// read blocks written by `export-blocks` from the standard input and
+    /// apply them on top of the blockchain of the storage
+    ImportBlocks(ImportBlocksArguments),
+
+    /// check or repair the blockchain storage
+    Storage(StorageCommand),
+}
+
+#[derive(StructOpt, Debug)]
+pub enum StorageCommand {
+    /// validate every block of the storage from the block0 to the tip and
+    /// report the first invalid or missing block
+    Verify(VerifyStorageArguments),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ExportBlocksArguments {
+    /// Path to the blockchain pool storage directory
+    #[structopt(long = "storage", parse(from_os_str))]
+    pub storage: PathBuf,
+
+    /// export the blocks after this block, use the hash of the block0 to
+    /// export the whole branch
+    #[structopt(long = "from", parse(try_from_str))]
+    pub from: HeaderHash,
+
+    /// export the blocks up to this block, included
+    #[structopt(long = "to", parse(try_from_str))]
+    pub to: HeaderHash,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ImportBlocksArguments {
+    /// Path to the blockchain pool storage directory
+    #[structopt(long = "storage", parse(from_os_str))]
+    pub storage: PathBuf,
+
+    #[structopt(flatten)]
+    pub block_0: Block0Arguments,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct VerifyStorageArguments {
+    /// Path to the blockchain pool storage directory
+    #[structopt(long = "storage", parse(from_os_str))]
+    pub storage: PathBuf,
+
+    #[structopt(flatten)]
+    pub block_0: Block0Arguments,
+
+    /// set the tip of the storage to the last valid block if an invalid or
+    /// missing block is found, so the node can synchronize from there
+    #[structopt(long = "repair")]
+    pub repair: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct Block0Arguments {
+    /// Path to the genesis block (the block0) of the blockchain
+    #[structopt(
+        long = "genesis-block",
+        parse(from_os_str),
+        required_unless = "block_0_hash"
+    )]
+    pub block_0_path: Option<PathBuf>,
+
+    /// the genesis block hash (the hash of the block0), to retrieve the
+    /// genesis block from the storage
+    #[structopt(long = "genesis-block-hash", parse(try_from_str))]
+    pub block_0_hash: Option<HeaderHash>,
 }
 
 impl CommandLine {
@@ -1,9 +1,13 @@
 use crate::{
-    network::p2p::{layers::LayersConfig, topic, Address, PolicyConfig},
+    blockchain::RewardsDumpFormat,
+    network::{
+        p2p::{layers::LayersConfig, topic, Address, PolicyConfig},
+        rate_limit::RateLimitsConfig,
+    },
     settings::logging::{LogFormat, LogOutput},
     settings::LOG_FILTER_LEVEL_POSSIBLE_VALUES,
 };
-use jormungandr_lib::{interfaces::Mempool, time::Duration};
+use jormungandr_lib::{crypto::hash::Hash, interfaces::Mempool, time::Duration};
 
 use serde::{de::Error as _, de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
 use slog::FilterLevel;
@@ -25,6 +29,26 @@ pub struct Config {
     #[serde(default)]
     pub leadership: Leadership,
 
+    /// snapshots of the ledger state kept in the storage directory
+    #[serde(default)]
+    pub ledger_snapshots: LedgerSnapshots,
+
+    /// bootstrap from a ledger snapshot served by another node, instead of
+    /// applying every block from the block0
+    pub fast_sync: Option<FastSync>,
+
+    /// removal of the branches that can no longer be selected
+    #[serde(default)]
+    pub branch_gc: BranchGc,
+
+    /// dump of the rewards distributed at every epoch boundary
+    pub rewards: Option<Rewards>,
+
+    /// index of the rewards received by every account kept in the storage
+    /// directory
+    #[serde(default)]
+    pub rewards_index: RewardsIndex,
+
     pub rest: Option<Rest>,
 
     #[serde(default)]
@@ -177,6 +201,17 @@ pub struct P2pConfig {
     /// gossip with the trusted peers if any are defined.
     #[serde(default)]
     pub max_bootstrap_attempts: Option<usize>,
+
+    /// TLS of the gRPC connections with the other nodes. If not set, the
+    /// connections are made and accepted in plain text.
+    #[serde(default)]
+    pub tls: Option<P2pTls>,
+
+    /// limits of the requests and the items accepted from the other nodes.
+    /// The default is to only limit the number of concurrent requests on
+    /// every connection.
+    #[serde(default)]
+    pub rate_limits: RateLimitsConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -190,6 +225,32 @@ pub struct TrustedPeer {
     // TODO: to remove once we can afford having a config breaking change
     #[serde(skip, default)]
     pub id: Option<poldercast::Id>,
+
+    /// Path to the PEM-encoded certificate pinned for this peer when TLS is
+    /// enabled: the peer has to present exactly this certificate, which is
+    /// then accepted whether or not it is issued by the authorities of
+    /// `p2p.tls.ca_file`. Only the first certificate of the file is pinned.
+    #[serde(default)]
+    pub certificate_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct P2pTls {
+    /// Path to the X.509 certificate chain file of the node, must be
+    /// PEM-encoded. It is presented both to the nodes connecting to this
+    /// node and to the nodes this node connects to.
+    pub cert_file: PathBuf,
+    /// Path to the private key file of the node, must be PKCS8 with single
+    /// PEM-encoded, unencrypted key
+    pub priv_key_file: PathBuf,
+    /// Path to the PEM-encoded certificates of the authorities issuing the
+    /// certificates of the nodes. The connections from nodes without such a
+    /// certificate are refused.
+    pub ca_file: PathBuf,
+    /// the DNS name the certificates of the nodes are issued to, checked when
+    /// connecting to another node
+    pub server_name: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -201,6 +262,16 @@ pub struct Leadership {
     pub logs_capacity: usize,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LedgerSnapshots {
+    /// take a snapshot of the ledger state at every epoch boundary and resume
+    /// from the most recent one when the node restarts.
+    pub enabled: bool,
+    /// the number of snapshots to keep, the oldest ones are removed.
+    pub keep: usize,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct Topic(pub poldercast::Topic);
 
@@ -246,6 +317,8 @@ impl Default for P2pConfig {
             gossip_interval: None,
             topology_force_reset_interval: None,
             max_bootstrap_attempts: None,
+            tls: None,
+            rate_limits: RateLimitsConfig::default(),
         }
     }
 }
@@ -258,6 +331,70 @@ impl Default for Leadership {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FastSync {
+    /// base URL of the REST API of the node serving the ledger snapshot,
+    /// for example `http://127.0.0.1:8443/api`
+    pub snapshot_service: String,
+    /// the block of the ledger snapshot, the first block of an epoch
+    pub block: Hash,
+    /// the expected hash (Blake2b256) of the ledger snapshot
+    pub snapshot_hash: Hash,
+    /// maximum size in bytes of the downloaded ledger snapshot, 1 GiB if not
+    /// set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_snapshot_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BranchGc {
+    /// the time interval between two removals of the stale branches
+    pub interval: Duration,
+    /// remove the branches whose tip is more than this number of blocks
+    /// behind the tip of the main branch. The branches behind by more than
+    /// the `epoch_stability_depth` are always removed.
+    pub max_lag: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Rewards {
+    /// the directory where a report is written for every rewards distribution
+    pub dump_directory: PathBuf,
+    /// the format of the reports: `csv` or `json_lines`
+    #[serde(default)]
+    pub format: RewardsDumpFormat,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RewardsIndex {
+    /// index the rewards received by every account at every epoch boundary,
+    /// the rewards of the accounts are kept in memory as with
+    /// `--rewards-report-all`.
+    pub enabled: bool,
+}
+
+impl Default for BranchGc {
+    fn default() -> Self {
+        BranchGc {
+            interval: Duration::new(120, 0),
+            max_lag: None,
+        }
+    }
+}
+
+impl Default for LedgerSnapshots {
+    fn default() -> Self {
+        LedgerSnapshots {
+            enabled: true,
+            keep: 2,
+        }
+    }
+}
+
 impl std::str::FromStr for TrustedPeer {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -281,6 +418,7 @@ impl std::str::FromStr for TrustedPeer {
         Ok(TrustedPeer {
             address,
             id: optional_id,
+            certificate_file: None,
         })
     }
 }
@@ -1,14 +1,20 @@
 pub mod config;
 pub mod network;
 
-use self::config::{Config, Leadership};
+use self::config::{
+    BranchGc, Config, FastSync, Leadership, LedgerSnapshots, Rewards, RewardsIndex,
+};
 pub use self::config::{Cors, Rest, Tls};
 use self::network::Protocol;
+use crate::network::p2p::CertificateBindings;
 use crate::settings::logging::{LogFormat, LogOutput, LogSettings, LogSettingsEntry};
 use crate::settings::{command_arguments::*, Block0Info};
 use jormungandr_lib::interfaces::Mempool;
 use slog::{FilterLevel, Logger};
-use std::{fs::File, path::PathBuf};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
 use thiserror::Error;
 
 const DEFAULT_FILTER_LEVEL: FilterLevel = FilterLevel::Info;
@@ -26,6 +32,16 @@ pub enum Error {
     ExpectedBlock0Info,
     #[error("In the node configuration file, the `p2p.listen_address` value is not a valid address. Use format `/ip4/x.x.x.x/tcp/4920")]
     ListenAddressNotValid,
+    #[error("Cannot read the P2P TLS file {}", path.display())]
+    TlsFile {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Invalid P2P TLS settings")]
+    Tls(#[from] network::TlsError),
+    #[error("The certificate file {} of a trusted peer does not contain a PEM-encoded certificate", path.display())]
+    PinnedCertificate { path: PathBuf },
 }
 
 /// Overall Settings for node
@@ -38,6 +54,11 @@ pub struct Settings {
     pub mempool: Mempool,
     pub rewards_report_all: bool,
     pub leadership: Leadership,
+    pub ledger_snapshots: LedgerSnapshots,
+    pub fast_sync: Option<FastSync>,
+    pub branch_gc: BranchGc,
+    pub rewards: Option<Rewards>,
+    pub rewards_index: RewardsIndex,
     pub explorer: bool,
     pub no_blockchain_updates_warning_interval: std::time::Duration,
 }
@@ -179,6 +200,17 @@ impl RawSettings {
             leadership: config
                 .as_ref()
                 .map_or(Leadership::default(), |cfg| cfg.leadership.clone()),
+            ledger_snapshots: config.as_ref().map_or(LedgerSnapshots::default(), |cfg| {
+                cfg.ledger_snapshots.clone()
+            }),
+            fast_sync: config.as_ref().and_then(|cfg| cfg.fast_sync.clone()),
+            branch_gc: config
+                .as_ref()
+                .map_or(BranchGc::default(), |cfg| cfg.branch_gc.clone()),
+            rewards: config.as_ref().and_then(|cfg| cfg.rewards.clone()),
+            rewards_index: config
+                .as_ref()
+                .map_or(RewardsIndex::default(), |cfg| cfg.rewards_index.clone()),
             explorer,
             no_blockchain_updates_warning_interval: config
                 .as_ref()
@@ -227,6 +259,7 @@ fn generate_network(
                     Some(config::TrustedPeer {
                         address,
                         id: peer.id.clone(),
+                        certificate_file: peer.certificate_file.clone(),
                     })
                 }
                 Ok(None) => {
@@ -272,16 +305,39 @@ fn generate_network(
         .map(|v| multiaddr_to_socket_addr(v.multi_address()).ok_or(Error::ListenAddressNotValid))
         .transpose()?;
 
+    let trusted_peers: Vec<network::TrustedPeer> = p2p
+        .trusted_peers
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|tp| {
+            let certificate = tp
+                .certificate_file
+                .as_deref()
+                .map(|path| {
+                    network::pinned_certificate(&read_tls_file(path)?).ok_or_else(|| {
+                        Error::PinnedCertificate {
+                            path: path.to_path_buf(),
+                        }
+                    })
+                })
+                .transpose()?;
+            Ok(network::TrustedPeer {
+                certificate,
+                ..tp.into()
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+    let certificates = CertificateBindings::new(trusted_peers.iter().filter_map(|tp| {
+        let certificate = tp.certificate.as_deref()?;
+        let address = multiaddr_to_socket_addr(tp.address.multi_address())?;
+        Some((address, certificate))
+    }));
+
     let mut network = network::Configuration {
         profile: profile.build(),
         listen_address,
-        trusted_peers: p2p
-            .trusted_peers
-            .clone()
-            .unwrap_or_default()
-            .into_iter()
-            .map(Into::into)
-            .collect(),
+        trusted_peers,
         protocol: Protocol::Grpc,
         policy: p2p.policy.clone(),
         layers: p2p.layers.clone(),
@@ -304,6 +360,22 @@ fn generate_network(
         bootstrap_from_trusted_peers,
         skip_bootstrap,
         legacy_node_id: Some(legacy_node_id),
+        tls: p2p
+            .tls
+            .as_ref()
+            .map(|tls| -> Result<_, Error> {
+                let tls = network::Tls {
+                    certificate: read_tls_file(&tls.cert_file)?,
+                    private_key: read_tls_file(&tls.priv_key_file)?,
+                    ca_certificate: read_tls_file(&tls.ca_file)?,
+                    server_name: tls.server_name.clone(),
+                };
+                tls.check()?;
+                Ok(tls)
+            })
+            .transpose()?,
+        certificates,
+        rate_limits: p2p.rate_limits.clone(),
     };
 
     if network.max_inbound_connections > network.max_connections {
@@ -318,3 +390,10 @@ fn generate_network(
 
     Ok(network)
 }
+
+fn read_tls_file(path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|source| Error::TlsFile {
+        path: path.to_path_buf(),
+        source,
+    })
+}
@@ -1,6 +1,11 @@
-use crate::network::p2p::{layers::LayersConfig, Address, PolicyConfig};
+use crate::network::{
+    p2p::{layers::LayersConfig, Address, CertificateBindings, PolicyConfig},
+    rate_limit::RateLimitsConfig,
+};
 use poldercast::NodeProfile;
+use rustls::{internal::pemfile, Certificate, PrivateKey, RootCertStore};
 use std::{net::SocketAddr, str, time::Duration};
+use thiserror::Error;
 
 /// Protocol to use for a connection.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -10,7 +15,7 @@ pub enum Protocol {
 }
 
 /// represent a connection peer
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug)]
 pub struct Peer {
     /// the connection to connect to
     pub connection: SocketAddr,
@@ -18,6 +23,36 @@ pub struct Peer {
     pub protocol: Protocol,
     /// a timeout in case of inactivity or timout between request.
     pub timeout: Duration,
+    /// TLS settings of the connection, plain text is used if not set
+    pub tls: Option<Tls>,
+    /// the certificates bound to the addresses of the nodes, checked when
+    /// TLS is used
+    pub certificates: CertificateBindings,
+}
+
+/// TLS settings of the gRPC connections, with the content of the files given
+/// in the `p2p.tls` configuration
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tls {
+    /// PEM-encoded certificate chain of the node
+    pub certificate: Vec<u8>,
+    /// PEM-encoded private key of the node
+    pub private_key: Vec<u8>,
+    /// PEM-encoded certificates of the authorities the certificates of the
+    /// other nodes are issued by
+    pub ca_certificate: Vec<u8>,
+    /// the DNS name the certificates of the other nodes are issued to
+    pub server_name: String,
+}
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("the certificate file does not contain a PEM-encoded certificate")]
+    Certificate,
+    #[error("the private key file does not contain a PEM-encoded PKCS8 or RSA private key")]
+    PrivateKey,
+    #[error("the CA file does not contain a PEM-encoded certificate")]
+    CaCertificate,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -95,12 +130,24 @@ pub struct Configuration {
 
     /// A pre-0.9 node ID to put in "node-id-bin" metadata when subscribing
     pub legacy_node_id: Option<poldercast::Id>,
+
+    /// TLS settings of the gRPC connections, plain text is used if not set
+    pub tls: Option<Tls>,
+
+    /// the certificates bound to the addresses of the nodes, when TLS is
+    /// used, starting with the certificates pinned for the trusted peers
+    pub certificates: CertificateBindings,
+
+    /// limits of the requests and the items accepted from the other nodes
+    pub rate_limits: RateLimitsConfig,
 }
 
 #[derive(Clone)]
 pub struct TrustedPeer {
     pub address: poldercast::Address,
     pub legacy_node_id: Option<poldercast::Id>,
+    /// DER-encoded certificate pinned for this peer
+    pub certificate: Option<Vec<u8>>,
 }
 
 impl From<super::config::TrustedPeer> for TrustedPeer {
@@ -108,20 +155,27 @@ impl From<super::config::TrustedPeer> for TrustedPeer {
         TrustedPeer {
             address: tp.address,
             legacy_node_id: tp.id,
+            certificate: None,
         }
     }
 }
 
 impl Peer {
-    pub fn new(connection: SocketAddr) -> Self {
-        Peer::with_timeout(connection, DEFAULT_TIMEOUT)
+    pub fn new(connection: SocketAddr, certificates: CertificateBindings) -> Self {
+        Peer::with_timeout(connection, DEFAULT_TIMEOUT, certificates)
     }
 
-    pub fn with_timeout(connection: SocketAddr, timeout: Duration) -> Self {
+    pub fn with_timeout(
+        connection: SocketAddr,
+        timeout: Duration,
+        certificates: CertificateBindings,
+    ) -> Self {
         Peer {
             connection,
             protocol: Protocol::Grpc,
             timeout,
+            tls: None,
+            certificates,
         }
     }
 
@@ -144,11 +198,69 @@ impl Listen {
     }
 }
 
+impl Tls {
+    /// the certificate chain of the node
+    pub fn certificate_chain(&self) -> Result<Vec<Certificate>, TlsError> {
+        parse_certificates(&self.certificate).ok_or(TlsError::Certificate)
+    }
+
+    /// the private key of the node, PKCS8 or RSA
+    pub fn private_key(&self) -> Result<PrivateKey, TlsError> {
+        pemfile::pkcs8_private_keys(&mut &self.private_key[..])
+            .ok()
+            .and_then(|mut keys| keys.pop())
+            .or_else(|| {
+                pemfile::rsa_private_keys(&mut &self.private_key[..])
+                    .ok()
+                    .and_then(|mut keys| keys.pop())
+            })
+            .ok_or(TlsError::PrivateKey)
+    }
+
+    /// the authorities the certificates of the other nodes are issued by
+    pub fn ca_certificates(&self) -> Result<RootCertStore, TlsError> {
+        let mut roots = RootCertStore::empty();
+        match roots.add_pem_file(&mut &self.ca_certificate[..]) {
+            Ok((valid, _)) if valid > 0 => Ok(roots),
+            _ => Err(TlsError::CaCertificate),
+        }
+    }
+
+    /// check the PEM-encoded files can be used
+    pub fn check(&self) -> Result<(), TlsError> {
+        self.certificate_chain()?;
+        self.private_key()?;
+        self.ca_certificates()?;
+        Ok(())
+    }
+}
+
+/// the DER encoding of the first certificate of a PEM-encoded file
+pub fn pinned_certificate(pem: &[u8]) -> Option<Vec<u8>> {
+    parse_certificates(pem)
+        .and_then(|certificates| certificates.into_iter().next())
+        .map(|certificate| certificate.0)
+}
+
+fn parse_certificates(pem: &[u8]) -> Option<Vec<Certificate>> {
+    pemfile::certs(&mut &pem[..])
+        .ok()
+        .filter(|certificates| !certificates.is_empty())
+}
+
 impl Configuration {
     pub fn address(&self) -> Option<&Address> {
         self.profile.address()
     }
 
+    /// the settings to connect to the peer at the given address
+    pub fn peer(&self, connection: SocketAddr) -> Peer {
+        Peer {
+            tls: self.tls.clone(),
+            ..Peer::new(connection, self.certificates.clone())
+        }
+    }
+
     /// Returns the listener configuration, if the options defining it
     /// were set.
     pub fn listen(&self) -> Option<Listen> {
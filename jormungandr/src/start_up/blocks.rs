@@ -0,0 +1,368 @@
+//! commands working on the blocks of a storage without starting the node.
+//!
+//! The blocks can be exported to a file and imported in another storage, to
+//! move a chain between nodes without synchronizing over the network. They
+//! are written one after the other with the same encoding as the block0 file,
+//! each one being followed by its child.
+//!
+//! The blocks of a storage can also be verified, and the tip of the storage
+//! reset to the last valid block.
+
+use super::{Error, ErrorKind};
+use crate::{
+    blockcfg::{Block, HeaderHash},
+    blockchain::{
+        self, Blockchain, CheckHeaderProof, ComparisonResult, PreCheckedHeader, Ref, Storage, Tip,
+        MAIN_BRANCH_TAG,
+    },
+};
+use chain_core::property::{Deserialize as _, Serialize as _};
+use futures::prelude::*;
+use slog::Logger;
+use std::{
+    io::{BufRead, Write},
+    sync::Arc,
+};
+
+/// write the blocks after `from` up to `to`, included, to the given writer.
+/// `from` has to be an ancestor of `to`. Returns the number of blocks written.
+pub async fn export_blocks<W: Write>(
+    storage: &Storage,
+    from: HeaderHash,
+    to: HeaderHash,
+    mut writer: W,
+) -> Result<usize, Error> {
+    let mut stream = storage.stream_from_to(from, to).await?;
+    let mut count = 0;
+
+    while let Some(block) = stream.next().await {
+        let block = block.map_err(Error::ExportBlocks)?;
+        block.serialize(&mut writer).map_err(|err| Error::IO {
+            source: err,
+            reason: ErrorKind::Blocks,
+        })?;
+        count += 1;
+    }
+    writer.flush().map_err(|err| Error::IO {
+        source: err,
+        reason: ErrorKind::Blocks,
+    })?;
+
+    Ok(count)
+}
+
+/// read the blocks written by `export_blocks` and apply them on top of the
+/// blockchain, with the same validation as the blocks received from the
+/// network. The parent of the first block has to be in the blockchain.
+/// Returns the number of blocks that were not already in the storage. The
+/// import fails if the last block forks from the current branch deeper than
+/// the stability depth, the blocks stay in the storage but the tip does not
+/// change.
+pub async fn import_blocks<R: BufRead>(
+    mut blockchain: Blockchain,
+    tip: Tip,
+    mut reader: R,
+    logger: &Logger,
+) -> Result<usize, Error> {
+    let mut last_ref: Option<Arc<Ref>> = None;
+    let mut count = 0;
+
+    while has_data_left(&mut reader)? {
+        let block = Block::deserialize(&mut reader).map_err(|err| Error::ParseError {
+            source: err,
+            reason: ErrorKind::Blocks,
+        })?;
+
+        match blockchain.pre_check_header(block.header(), true).await? {
+            PreCheckedHeader::AlreadyPresent {
+                header,
+                cached_reference,
+            } => {
+                debug!(logger, "block is already present"; "hash" => %header.hash());
+                if cached_reference.is_some() {
+                    last_ref = cached_reference;
+                }
+            }
+            PreCheckedHeader::MissingParent { header } => {
+                return Err(Error::ImportMissingParent(header.hash()));
+            }
+            PreCheckedHeader::HeaderWithCache { header, parent_ref } => {
+                let post_checked = blockchain
+                    .post_check_header(header, parent_ref, CheckHeaderProof::Enabled)
+                    .await?;
+                let applied = blockchain
+                    .apply_and_store_block(post_checked, block)
+                    .await?;
+                let block_ref = applied.cached_ref();
+                debug!(
+                    logger,
+                    "block imported";
+                    "hash" => %block_ref.hash(),
+                    "date" => %block_ref.header().block_date(),
+                );
+                last_ref = Some(block_ref);
+                if applied.new_ref().is_some() {
+                    count += 1;
+                }
+            }
+        }
+    }
+
+    if let Some(last_ref) = last_ref {
+        let last_block = last_ref.hash();
+        let comparison =
+            blockchain::process_new_ref(logger, &mut blockchain, tip, last_ref).await?;
+        if comparison == ComparisonResult::RejectDeepFork {
+            return Err(Error::ImportDeepFork(last_block));
+        }
+    }
+
+    Ok(count)
+}
+
+/// validate the blocks of the storage from the block0 to its tip. If an
+/// invalid or missing block is found and `repair` is set, the tip of the
+/// storage is set to the last valid block.
+pub async fn verify_storage(
+    blockchain: Blockchain,
+    block0: Block,
+    repair: bool,
+    logger: &Logger,
+) -> Result<(), Error> {
+    let verification = blockchain.verify_storage(block0, logger).await?;
+    let last_valid = verification.last_valid.header().description();
+
+    let (invalid, reason) = match verification.first_invalid {
+        None => {
+            info!(
+                logger,
+                "storage is valid, verified {} blocks up to {}", verification.verified, last_valid
+            );
+            return Ok(());
+        }
+        Some(first_invalid) => first_invalid,
+    };
+
+    error!(
+        logger,
+        "invalid block {} found after {} valid blocks", invalid, verification.verified;
+        "last_valid" => %last_valid,
+        "reason" => %reason,
+    );
+
+    if !repair {
+        return Err(Error::StorageVerificationFailed(invalid));
+    }
+
+    blockchain
+        .storage()
+        .put_tag(MAIN_BRANCH_TAG.to_owned(), verification.last_valid.hash())
+        .await?;
+    warn!(
+        logger,
+        "storage tip reset to the last valid block {}", last_valid
+    );
+    Ok(())
+}
+
+fn has_data_left<R: BufRead>(reader: &mut R) -> Result<bool, Error> {
+    reader
+        .fill_buf()
+        .map(|buf| !buf.is_empty())
+        .map_err(|err| Error::IO {
+            source: err,
+            reason: ErrorKind::Blocks,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::test_chain::{apply_all, logger, TestChain};
+
+    const CACHE_CAPACITY: usize = 64;
+
+    /// a blockchain over the storage of the chain, loaded from the block0,
+    /// with the given blocks on top of it as the tip
+    async fn load(chain: &TestChain, blocks: Vec<Block>) -> (Blockchain, Tip) {
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        let mut branch = blockchain
+            .load_from_block0(chain.block0.clone())
+            .await
+            .unwrap();
+        if !blocks.is_empty() {
+            let tip = apply_all(&blockchain, blocks).await;
+            chain
+                .storage
+                .put_tag(MAIN_BRANCH_TAG.to_owned(), tip.hash())
+                .await
+                .unwrap();
+            branch.update_ref(tip).await;
+        }
+        (blockchain, Tip::new(branch))
+    }
+
+    async fn head(chain: &TestChain) -> HeaderHash {
+        chain
+            .storage
+            .get_tag(MAIN_BRANCH_TAG.to_owned())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn exported_blocks_are_imported() {
+        let source = TestChain::new("blocks-export-source", 10);
+        let blocks = source.branch(&source.block0.header, 5, 0);
+        let last = blocks[4].header.hash();
+        load(&source, blocks).await;
+
+        let mut exported = Vec::new();
+        let written = export_blocks(
+            &source.storage,
+            source.block0.header.hash(),
+            last,
+            &mut exported,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written, 5);
+
+        let target = source.with_empty_storage("blocks-import-target");
+        let (blockchain, tip) = load(&target, Vec::new()).await;
+        let imported = import_blocks(blockchain.clone(), tip.clone(), &exported[..], &logger())
+            .await
+            .unwrap();
+        assert_eq!(imported, 5);
+        assert_eq!(tip.get_ref().await.hash(), last);
+        assert_eq!(head(&target).await, last);
+
+        // the blocks already in the storage are not counted again
+        let imported = import_blocks(blockchain, tip.clone(), &exported[..], &logger())
+            .await
+            .unwrap();
+        assert_eq!(imported, 0);
+        assert_eq!(tip.get_ref().await.hash(), last);
+    }
+
+    #[tokio::test]
+    async fn import_without_the_parent_fails() {
+        let source = TestChain::new("blocks-missing-parent-source", 10);
+        let blocks = source.branch(&source.block0.header, 5, 0);
+        let from = blocks[1].header.hash();
+        let first_exported = blocks[2].header.hash();
+        let last = blocks[4].header.hash();
+        load(&source, blocks).await;
+
+        let mut exported = Vec::new();
+        export_blocks(&source.storage, from, last, &mut exported)
+            .await
+            .unwrap();
+
+        let target = source.with_empty_storage("blocks-missing-parent-target");
+        let (blockchain, tip) = load(&target, Vec::new()).await;
+        match import_blocks(blockchain, tip.clone(), &exported[..], &logger()).await {
+            Err(Error::ImportMissingParent(hash)) => assert_eq!(hash, first_exported),
+            other => panic!("unexpected import result {:?}", other),
+        }
+        assert_eq!(tip.get_ref().await.hash(), target.block0.header.hash());
+        assert_eq!(head(&target).await, target.block0.header.hash());
+    }
+
+    #[tokio::test]
+    async fn import_of_a_deep_fork_keeps_the_tip() {
+        let source = TestChain::new("blocks-deep-fork-source", 2);
+        let fork = source.branch(&source.block0.header, 8, 1);
+        let fork_tip = fork[7].header.hash();
+        load(&source, fork).await;
+
+        let mut exported = Vec::new();
+        export_blocks(
+            &source.storage,
+            source.block0.header.hash(),
+            fork_tip,
+            &mut exported,
+        )
+        .await
+        .unwrap();
+
+        // the main branch of the target is shorter than the fork, but the
+        // fork starts deeper than the stability depth below its tip
+        let target = source.with_empty_storage("blocks-deep-fork-target");
+        let main = target.branch(&target.block0.header, 6, 0);
+        let main_tip = main[5].header.hash();
+        let (blockchain, tip) = load(&target, main).await;
+
+        match import_blocks(blockchain, tip.clone(), &exported[..], &logger()).await {
+            Err(Error::ImportDeepFork(hash)) => assert_eq!(hash, fork_tip),
+            other => panic!("unexpected import result {:?}", other),
+        }
+        assert_eq!(tip.get_ref().await.hash(), main_tip);
+        assert_eq!(head(&target).await, main_tip);
+        assert!(target.storage.get(fork_tip).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn valid_storage_is_verified() {
+        let chain = TestChain::new("blocks-verify-valid", 10);
+        let blocks = chain.branch(&chain.block0.header, 4, 0);
+        let last = blocks[3].header.hash();
+        load(&chain, blocks).await;
+
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        verify_storage(blockchain, chain.block0.clone(), false, &logger())
+            .await
+            .unwrap();
+        assert_eq!(head(&chain).await, last);
+    }
+
+    #[tokio::test]
+    async fn invalid_block_fails_the_verification() {
+        let chain = TestChain::new("blocks-verify-invalid", 10);
+        let blocks = chain.branch(&chain.block0.header, 4, 0);
+        let forged = chain.forged_block_after(&blocks[3].header);
+        let forged_hash = forged.header.hash();
+        load(&chain, blocks).await;
+        chain.storage.put_block(forged).await.unwrap();
+        chain
+            .storage
+            .put_tag(MAIN_BRANCH_TAG.to_owned(), forged_hash)
+            .await
+            .unwrap();
+
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        match verify_storage(blockchain, chain.block0.clone(), false, &logger()).await {
+            Err(Error::StorageVerificationFailed(hash)) => assert_eq!(hash, forged_hash),
+            other => panic!("unexpected verification result {:?}", other),
+        }
+        assert_eq!(head(&chain).await, forged_hash);
+    }
+
+    #[tokio::test]
+    async fn repair_resets_the_tip_to_the_last_valid_block() {
+        let chain = TestChain::new("blocks-verify-repair", 10);
+        let blocks = chain.branch(&chain.block0.header, 4, 0);
+        let last_valid = blocks[3].header.hash();
+        let forged = chain.forged_block_after(&blocks[3].header);
+        let forged_hash = forged.header.hash();
+        load(&chain, blocks).await;
+        chain.storage.put_block(forged).await.unwrap();
+        chain
+            .storage
+            .put_tag(MAIN_BRANCH_TAG.to_owned(), forged_hash)
+            .await
+            .unwrap();
+
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        verify_storage(blockchain, chain.block0.clone(), true, &logger())
+            .await
+            .unwrap();
+        assert_eq!(head(&chain).await, last_valid);
+
+        let blockchain = chain.blockchain(CACHE_CAPACITY, None);
+        verify_storage(blockchain, chain.block0.clone(), false, &logger())
+            .await
+            .unwrap();
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     blockcfg, blockchain,
     diagnostic::DiagnosticError,
-    explorer, network, secure,
+    explorer, fragment, intercom, network, secure,
     settings::{self, logging},
 };
 use chain_storage::Error as StorageError;
@@ -14,6 +14,8 @@ pub enum ErrorKind {
     SQLite,
     #[error("Block0")]
     Block0,
+    #[error("blocks file")]
+    Blocks,
 }
 
 #[derive(Debug, Error)]
@@ -41,6 +43,16 @@ pub enum Error {
     },
     #[error("Storage error")]
     StorageError(#[from] StorageError),
+    #[error("Error while accessing the block storage")]
+    BlockStorage(#[from] blockchain::StorageError),
+    #[error("Error while reading the blocks to export")]
+    ExportBlocks(#[source] intercom::Error),
+    #[error("Block {0} cannot be imported, its parent block is not in the storage")]
+    ImportMissingParent(blockcfg::HeaderId),
+    #[error("Block {0} forks from the current branch deeper than the stability depth, it cannot become the tip")]
+    ImportDeepFork(blockcfg::HeaderId),
+    #[error("The storage is invalid from block {0}")]
+    StorageVerificationFailed(blockcfg::HeaderId),
     #[error("Error while loading the legacy blockchain state")]
     Blockchain(#[from] blockchain::Error),
     #[error("Error in the genesis-block")]
@@ -55,6 +67,8 @@ pub enum Error {
     Block0InFuture,
     #[error("Error while loading the explorer from storage")]
     ExplorerBootstrapError(#[from] explorer::error::Error),
+    #[error("Error while opening the mempool journal")]
+    MempoolJournal(#[from] fragment::JournalError),
     #[error("A service has terminated with an error")]
     ServiceTerminatedWithError,
     #[error("Unable to get system limits: {0}")]
@@ -92,6 +106,12 @@ impl Error {
             Error::ExplorerBootstrapError { .. } => 11,
             Error::ServiceTerminatedWithError => 12,
             Error::DiagnosticError { .. } => 13,
+            Error::MempoolJournal { .. } => 14,
+            Error::BlockStorage { .. } => 15,
+            Error::ExportBlocks { .. } => 16,
+            Error::ImportMissingParent { .. } => 17,
+            Error::StorageVerificationFailed { .. } => 18,
+            Error::ImportDeepFork { .. } => 19,
         }
     }
 }
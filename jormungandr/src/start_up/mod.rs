@@ -1,14 +1,21 @@
+mod blocks;
 mod error;
 
+pub use self::blocks::{export_blocks, import_blocks, verify_storage};
 pub use self::error::{Error, ErrorKind};
 use crate::{
     blockcfg::{Block, HeaderId},
-    blockchain::{Blockchain, ErrorKind as BlockchainError, Storage, Tip},
-    log, network,
-    settings::start::Settings,
+    blockchain::{
+        Blockchain, ErrorKind as BlockchainError, RewardsDump, RewardsIndex, RewardsReport,
+        Snapshots, Storage, Tip,
+    },
+    log,
+    network::{self, p2p::peer_db::PeerDb},
+    settings::{start::Settings, Block0Arguments},
 };
 use chain_storage::{BlockStore, BlockStoreBuilder, BlockStoreConnection};
 use slog::Logger;
+use std::path::Path;
 
 pub type NodeStorage = BlockStore<Block>;
 pub type NodeStorageConnection = BlockStoreConnection<Block>;
@@ -18,26 +25,32 @@ const BLOCKSTORE_BUSY_TIMEOUT: u64 = 1000;
 /// prepare the block storage from the given settings
 ///
 pub fn prepare_storage(setting: &Settings, logger: &Logger) -> Result<Storage, Error> {
-    let raw_block_store = match &setting.storage {
+    match &setting.storage {
         None => {
             info!(logger, "storing blockchain in memory");
-            BlockStoreBuilder::memory()
+            let raw_block_store = BlockStoreBuilder::memory()
                 .busy_timeout(BLOCKSTORE_BUSY_TIMEOUT)
-                .build()
+                .build();
+            Ok(Storage::new(
+                raw_block_store,
+                logger.new(o!(log::KEY_SUB_TASK => "storage")),
+            ))
         }
-        Some(dir) => {
-            std::fs::create_dir_all(dir).map_err(|err| Error::IO {
-                source: err,
-                reason: ErrorKind::SQLite,
-            })?;
-            let mut sqlite = dir.clone();
-            sqlite.push("blocks.sqlite");
-            info!(logger, "storing blockchain in '{:?}'", sqlite);
-            BlockStoreBuilder::file(sqlite)
-                .busy_timeout(BLOCKSTORE_BUSY_TIMEOUT)
-                .build()
-        }
-    };
+        Some(dir) => open_storage(dir, logger),
+    }
+}
+
+/// open the block storage kept in the given directory, creating it if needed
+pub fn open_storage(dir: &Path, logger: &Logger) -> Result<Storage, Error> {
+    std::fs::create_dir_all(dir).map_err(|err| Error::IO {
+        source: err,
+        reason: ErrorKind::SQLite,
+    })?;
+    let sqlite = dir.join("blocks.sqlite");
+    info!(logger, "storing blockchain in '{:?}'", sqlite);
+    let raw_block_store = BlockStoreBuilder::file(sqlite)
+        .busy_timeout(BLOCKSTORE_BUSY_TIMEOUT)
+        .build();
 
     Ok(Storage::new(
         raw_block_store,
@@ -45,6 +58,58 @@ pub fn prepare_storage(setting: &Settings, logger: &Logger) -> Result<Storage, E
     ))
 }
 
+/// prepare the ledger snapshots from the given settings, snapshots are only
+/// taken if the blockchain is kept in a storage directory
+pub fn prepare_ledger_snapshots(setting: &Settings, logger: &Logger) -> Option<Snapshots> {
+    let dir = setting.storage.as_ref()?;
+    if !setting.ledger_snapshots.enabled {
+        return None;
+    }
+    Some(Snapshots::new(
+        dir.join("ledger-snapshots"),
+        setting.ledger_snapshots.keep,
+        logger.new(o!(log::KEY_SUB_TASK => "ledger_snapshots")),
+    ))
+}
+
+/// prepare the database of the peers known to the node, the peers are only
+/// saved if the blockchain is kept in a storage directory
+pub fn prepare_peer_db(setting: &Settings, logger: &Logger) -> Option<PeerDb> {
+    let dir = setting.storage.as_ref()?;
+    Some(PeerDb::new(
+        dir.join("peer-db"),
+        logger.new(o!(log::KEY_SUB_TASK => "peer_db")),
+    ))
+}
+
+/// prepare the reporting of the rewards distributions from the given
+/// settings, the rewards index is only kept if the blockchain is kept in a
+/// storage directory
+pub fn prepare_rewards_report(setting: &Settings, logger: &Logger) -> RewardsReport {
+    let dump = setting.rewards.as_ref().map(|rewards| {
+        RewardsDump::new(
+            rewards.dump_directory.clone(),
+            rewards.format,
+            logger.new(o!(log::KEY_SUB_TASK => "rewards_dump")),
+        )
+    });
+    let index = setting
+        .storage
+        .as_ref()
+        .filter(|_| setting.rewards_index.enabled)
+        .map(|dir| {
+            RewardsIndex::new(
+                dir.join("rewards-index"),
+                logger.new(o!(log::KEY_SUB_TASK => "rewards_index")),
+            )
+        });
+    RewardsReport {
+        report_all: setting.rewards_report_all,
+        dump,
+        index,
+    }
+}
+
 /// Try to fetch the block0_id from the HTTP base URL (services) in the array
 ///
 /// The HTTP url is expecting to be of the form: URL/<hash-id>.block0
@@ -119,35 +184,10 @@ pub async fn prepare_block_0(
     logger: &Logger,
 ) -> Result<Block, Error> {
     use crate::settings::Block0Info;
-    use chain_core::property::Deserialize as _;
     match &settings.block_0 {
         Block0Info::Path(path, opt_block0_id) => {
             debug!(logger, "parsing block0 from file path `{:?}'", path);
-            let f = std::fs::File::open(path).map_err(|err| Error::IO {
-                source: err,
-                reason: ErrorKind::Block0,
-            })?;
-            let reader = std::io::BufReader::new(f);
-            let block = Block::deserialize(reader).map_err(|err| Error::ParseError {
-                source: err,
-                reason: ErrorKind::Block0,
-            })?;
-
-            // check if the block0 match, the optional expected hash value
-            match opt_block0_id {
-                None => {}
-                Some(expected_hash) => {
-                    let got = block.header.id();
-                    if &got != expected_hash {
-                        return Err(Error::Block0Mismatch {
-                            got,
-                            expected: expected_hash.clone(),
-                        });
-                    }
-                }
-            };
-
-            Ok(block)
+            read_block_0(path, opt_block0_id.as_ref())
         }
         Block0Info::Hash(block0_id) => {
             let storage_or_http_block0 = {
@@ -186,18 +226,67 @@ pub async fn prepare_block_0(
     }
 }
 
+/// read the block0 from the given file, checking it matches the optional
+/// expected hash value
+pub fn read_block_0(path: &Path, expected_hash: Option<&HeaderId>) -> Result<Block, Error> {
+    use chain_core::property::Deserialize as _;
+
+    let f = std::fs::File::open(path).map_err(|err| Error::IO {
+        source: err,
+        reason: ErrorKind::Block0,
+    })?;
+    let reader = std::io::BufReader::new(f);
+    let block = Block::deserialize(reader).map_err(|err| Error::ParseError {
+        source: err,
+        reason: ErrorKind::Block0,
+    })?;
+
+    if let Some(expected_hash) = expected_hash {
+        let got = block.header.id();
+        if &got != expected_hash {
+            return Err(Error::Block0Mismatch {
+                got,
+                expected: expected_hash.clone(),
+            });
+        }
+    }
+
+    Ok(block)
+}
+
+/// load the block0 given on the command line of the commands working on the
+/// storage, either from a file or from the storage
+pub async fn load_block_0(args: &Block0Arguments, storage: &Storage) -> Result<Block, Error> {
+    match &args.block_0_path {
+        Some(path) => read_block_0(path, args.block_0_hash.as_ref()),
+        None => {
+            let block0_hash = args
+                .block_0_hash
+                .expect("the block0 hash is required without the block0 path");
+            match storage.get(block0_hash).await? {
+                Some(block0) => Ok(block0),
+                None => Err(Error::Blockchain(
+                    BlockchainError::Block0NotAlreadyInStorage.into(),
+                )),
+            }
+        }
+    }
+}
+
 pub async fn load_blockchain(
     block0: Block,
     storage: Storage,
     cache_capacity: usize,
-    rewards_report_all: bool,
+    rewards_report: RewardsReport,
+    snapshots: Option<Snapshots>,
     logger: &Logger,
 ) -> Result<(Blockchain, Tip), Error> {
     let blockchain = Blockchain::new(
         block0.header.hash(),
         storage,
         cache_capacity,
-        rewards_report_all,
+        rewards_report,
+        snapshots,
     );
 
     let main_branch = match blockchain.load_from_block0(block0.clone()).await {
@@ -20,6 +20,7 @@ struct StatsCounterImpl {
     slot_start_time: AtomicU64,
     tip_block: ArcSwapOption<Block>,
     peers_connected_cnt: AtomicUsize,
+    rejected_deep_fork_cnt: AtomicUsize,
 }
 
 impl Default for StatsCounterImpl {
@@ -31,6 +32,7 @@ impl Default for StatsCounterImpl {
             slot_start_time: AtomicU64::new(SLOT_START_TIME_UNDEFINED),
             tip_block: ArcSwapOption::from(None),
             peers_connected_cnt: AtomicUsize::default(),
+            rejected_deep_fork_cnt: AtomicUsize::default(),
         }
     }
 }
@@ -55,6 +57,16 @@ impl StatsCounter {
         self.stats.block_recv_cnt.load(Ordering::Relaxed) as u64
     }
 
+    pub fn add_rejected_deep_fork_cnt(&self, count: usize) {
+        self.stats
+            .rejected_deep_fork_cnt
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn rejected_deep_fork_cnt(&self) -> u64 {
+        self.stats.rejected_deep_fork_cnt.load(Ordering::Relaxed) as u64
+    }
+
     pub fn add_peer_connected_cnt(&self, count: usize) -> usize {
         self.stats
             .peers_connected_cnt
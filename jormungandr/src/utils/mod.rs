@@ -2,3 +2,5 @@ pub mod async_msg;
 pub mod borrow;
 pub mod fire_forget_scheduler;
 pub mod task;
+#[cfg(test)]
+pub mod test_dir;
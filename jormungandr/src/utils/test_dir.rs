@@ -0,0 +1,28 @@
+//! temporary directories for the tests touching the filesystem
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// a fresh directory in the system's temporary directory, removed on drop
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+    pub fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("jormungandr-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        TestDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
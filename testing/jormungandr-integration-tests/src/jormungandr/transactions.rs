@@ -22,6 +22,7 @@ pub fn accounts_funds_are_updated_after_transaction() {
             .with_mempool(Mempool {
                 pool_max_entries: 1_000_000usize.into(),
                 log_max_entries: 1_000_000usize.into(),
+                ..Mempool::default()
             }),
     )
     .unwrap();
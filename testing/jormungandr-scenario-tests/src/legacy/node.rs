@@ -330,6 +330,11 @@ impl LegacyNodeController {
                         ));
                         return Ok(status);
                     }
+                    Expired => {
+                        self.progress_bar
+                            .log_info(format!("Fragment '{}' expired", check.fragment_id()));
+                        return Ok(status);
+                    }
                 }
             } else {
                 return Err(Error::FragmentNotInMemPoolLogs {
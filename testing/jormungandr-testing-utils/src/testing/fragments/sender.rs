@@ -225,6 +225,11 @@ impl<'a> FragmentSender<'a> {
                 logs: FragmentNode::log_content(node),
             }),
             FragmentStatus::InABlock { .. } => Ok(()),
+            FragmentStatus::Expired => Err(FragmentSenderError::FragmentNotInBlock {
+                alias: FragmentNode::alias(node).to_string(),
+                reason: "fragment expired".to_string(),
+                logs: FragmentNode::log_content(node),
+            }),
             _ => unimplemented!(),
         }
     }
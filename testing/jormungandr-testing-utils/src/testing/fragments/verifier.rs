@@ -99,6 +99,12 @@ impl FragmentVerifier {
                         block.clone(),
                     );
                 }
+                FragmentStatus::Expired => {
+                    node.log_rejected_fragment(
+                        check.fragment_id().clone(),
+                        "fragment expired".to_string(),
+                    );
+                }
             }
             return Ok(status);
         }
@@ -130,6 +136,7 @@ impl FragmentVerifier {
             match status {
                 FragmentStatus::Rejected { .. } => return Ok(status),
                 FragmentStatus::InABlock { .. } => return Ok(status),
+                FragmentStatus::Expired => return Ok(status),
                 _ => (),
             }
             std::thread::sleep(duration);